@@ -0,0 +1,377 @@
+use super::*;
+use permissions::Credentials;
+
+// Where each user's home directory is made.
+pub const USERS_PATH: &'static str = "/Users";
+// Shown in the topbar and used as the home directory name for guests.
+pub const GUEST_NAME: &'static str = "Guest";
+// Accounts get uids (and a group of their own with the same id) from here up.
+pub const FIRST_ACCOUNT_UID: u32 = 1001;
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+// Who is logged in, as far as the client needs to know.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Account{
+    pub username:String,
+    pub credentials:Credentials,
+    // Guests share Credentials::DEFAULT_USER and their files are gone when they log out.
+    pub guest:bool,
+}
+
+impl Account{
+    pub fn guest() -> Self {
+        Self{username:GUEST_NAME.to_string(),credentials:Credentials::DEFAULT_USER,guest:true}
+    }
+
+    pub fn home(&self) -> String {
+        format!("{USERS_PATH}/{}", self.username)
+    }
+}
+
+// Usernames become directory names, so they are kept to what's safe in a path.
+pub fn validate_username(username:&str) -> Result<(),String> {
+    if username.is_empty() || username.len() > 32 {
+        return Err("Usernames are 1 to 32 characters long.".to_string());
+    }
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err("Usernames can only have letters, digits, _ and -.".to_string());
+    }
+    if username.eq_ignore_ascii_case(GUEST_NAME) {
+        return Err(format!("{GUEST_NAME} is reserved."));
+    }
+    Ok(())
+}
+
+// The account the request's session is logged in as, None before logging in.
+#[server(CurrentAccount, "/api")]
+pub async fn current_account() -> Result<Option<Account>,ServerFnError> {
+    let (session, state) = session_state()?;
+    Ok(state.account(&session))
+}
+
+#[server(Register, "/api")]
+pub async fn register(username:String, password:String) -> Result<Account,ServerFnError> {
+    validate_username(&username).map_err(ServerFnError::ServerError)?;
+    if password.len() < MIN_PASSWORD_LEN {
+        return Err(ServerFnError::ServerError(format!("Passwords need at least {MIN_PASSWORD_LEN} characters.")));
+    }
+    let (_, state) = session_state()?;
+    let password_hash = hash_password(&password)
+        .map_err(|err| backend_utils::handle_server_err(err, "Couldn't create the account.".to_string()))?;
+    let account = state.users().create(&username, password_hash).await
+        .map_err(ServerFnError::ServerError)?;
+    start_session(&state, account.clone())?;
+    Ok(account)
+}
+
+#[server(Login, "/api")]
+pub async fn login(username:String, password:String) -> Result<Account,ServerFnError> {
+    let (_, state) = session_state()?;
+    let wrong = || ServerFnError::ServerError("Wrong username or password.".to_string());
+    let user = state.users().find(&username).await.ok_or_else(wrong)?;
+    if !verify_password(&password, &user.password_hash) {
+        return Err(wrong());
+    }
+    start_session(&state, user.account.clone())?;
+    Ok(user.account)
+}
+
+// Logs in as a guest with a desktop of its own that is dropped at logout.
+#[server(LoginAsGuest, "/api")]
+pub async fn login_as_guest() -> Result<Account,ServerFnError> {
+    let (_, state) = session_state()?;
+    let account = Account::guest();
+    start_session(&state, account.clone())?;
+    Ok(account)
+}
+
+// Saves the runtime of an account so its next login picks up where it left off. Guests'
+// runtimes are dropped instead. Tabs can send anything, so the runtime is checked against the
+// one the server kept first. A locked session can log out but its runtime isn't taken.
+#[server(Logout, "/api")]
+pub async fn logout(snapshot:Option<String>) -> Result<(),ServerFnError> {
+    let (session, state) = session_state()?;
+    if let (Some(account), Some(snapshot)) = (state.account(&session), snapshot) {
+        if !account.guest && !state.is_locked(&session) {
+            let key = state.runtime_key(&session, &account);
+            if let Err(err) = state.save_client_snapshot(&key, &account, &snapshot) {
+                log::warn!("kept the runtime of {key}, the one sent at logout was refused: {err}");
+            }
+        }
+    }
+    state.logout(&session);
+    Ok(())
+}
+
+#[cfg(feature="ssr")]
+pub use store::*;
+
+#[cfg(feature="ssr")]
+mod store{
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+    use argon2::{Argon2,PasswordHash,PasswordHasher,PasswordVerifier};
+    use argon2::password_hash::{SaltString,rand_core::OsRng};
+
+    #[derive(Debug,Clone,PartialEq)]
+    pub struct StoredUser{
+        pub account:Account,
+        // PHC string, see hash_password.
+        pub password_hash:String,
+    }
+
+    // Where accounts are kept. The errors are messages for the person registering.
+    #[async_trait::async_trait]
+    pub trait UserStore: Send + Sync + std::fmt::Debug {
+        async fn create(&self, username:&str, password_hash:String) -> Result<Account,String>;
+        async fn find(&self, username:&str) -> Option<StoredUser>;
+    }
+
+    // Accounts for as long as the server runs.
+    #[derive(Debug,Default)]
+    pub struct MemoryUserStore{
+        users:RwLock<HashMap<String,StoredUser>>,
+    }
+
+    #[async_trait::async_trait]
+    impl UserStore for MemoryUserStore{
+        async fn create(&self, username:&str, password_hash:String) -> Result<Account,String> {
+            let mut users = self.users.write().unwrap();
+            // Usernames are case insensitive so two homes can't differ only in case.
+            let key = username.to_lowercase();
+            if users.contains_key(&key) {
+                return Err(format!("{username} is taken."));
+            }
+            let uid = FIRST_ACCOUNT_UID + users.len() as u32;
+            let account = Account{username:username.to_string(),credentials:Credentials{uid,gid:uid},guest:false};
+            users.insert(key, StoredUser{account:account.clone(),password_hash});
+            Ok(account)
+        }
+        async fn find(&self, username:&str) -> Option<StoredUser> {
+            self.users.read().unwrap().get(&username.to_lowercase()).cloned()
+        }
+    }
+
+    pub fn hash_password(password:&str) -> Result<String,argon2::password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+    }
+
+    pub fn verify_password(password:&str, password_hash:&str) -> bool {
+        PasswordHash::new(password_hash)
+            .map_or(false, |hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+    }
+
+    pub(crate) fn session_state() -> Result<(String,server_state::ServerState),ServerFnError> {
+        let session = session::current_session().ok_or_else(|| ServerFnError::ServerError("No session.".to_string()))?;
+        let state = session::server_state().ok_or_else(|| ServerFnError::ServerError("No server state.".to_string()))?;
+        Ok((session, state))
+    }
+
+    // The session, its state and account for requests that read or change a desktop. Refused
+    // before logging in and while the session is locked, see power::lock_session.
+    pub(crate) fn unlocked_account() -> Result<(String,server_state::ServerState,Account),ServerFnError> {
+        let (session, state) = session_state()?;
+        let account = state.account(&session).ok_or_else(|| ServerFnError::ServerError("Not logged in.".to_string()))?;
+        if state.is_locked(&session) {
+            return Err(ServerFnError::ServerError("The session is locked.".to_string()));
+        }
+        Ok((session, state, account))
+    }
+
+    // Logging in gets a new session id, so one picked before logging in can't be reused.
+    pub(crate) fn start_session(state:&server_state::ServerState, account:Account) -> Result<(),ServerFnError> {
+        let session = session::new_session().ok_or_else(|| ServerFnError::ServerError("No session.".to_string()))?;
+        state.login(&session, account);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature="ssr"))]
+mod tests {
+    use super::*;
+    use session::{Session,SESSION_COOKIE};
+    use server_state::ServerState;
+    use tower_cookies::Cookies;
+
+    // Runs f as a request from session would, returning the cookies it set.
+    async fn request<T, F:std::future::Future<Output=T>>(state:&ServerState, session:&str, f:impl FnOnce() -> F) -> (T, Cookies) {
+        let runtime = create_runtime();
+        let cookies = Cookies::default();
+        let (mut parts, _) = http::Request::new(()).into_parts();
+        parts.extensions.insert(state.clone());
+        parts.extensions.insert(Session(session.to_string()));
+        parts.extensions.insert(cookies.clone());
+        provide_context(parts);
+        let out = f().await;
+        runtime.dispose();
+        (out, cookies)
+    }
+
+    fn set_session(cookies:&Cookies) -> String {
+        cookies.get(SESSION_COOKIE).expect("a session cookie").value().to_string()
+    }
+
+    #[tokio::test]
+    async fn memory_store_keeps_accounts() {
+        let store = MemoryUserStore::default();
+        let ada = store.create("Ada", "hash".to_string()).await.unwrap();
+        let bob = store.create("bob", "hash".to_string()).await.unwrap();
+        assert_eq!(ada.credentials, Credentials{uid:FIRST_ACCOUNT_UID,gid:FIRST_ACCOUNT_UID});
+        assert_eq!(bob.credentials.uid, FIRST_ACCOUNT_UID + 1);
+        assert!(store.create("ada", "other".to_string()).await.is_err());
+        assert_eq!(store.find("ADA").await.map(|user| user.account), Some(ada));
+        assert_eq!(store.find("carol").await, None);
+    }
+
+    #[test]
+    fn passwords_verify_against_their_hash() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+        assert_ne!(hash_password("correct horse").unwrap(), hash);
+    }
+
+    #[tokio::test]
+    async fn login_starts_a_new_session() {
+        let state = ServerState::default();
+        let (account, cookies) = request(&state, "visitor", || register("ada".to_string(), "password1".to_string())).await;
+        let account = account.unwrap();
+        assert_eq!(state.account(&set_session(&cookies)), Some(account.clone()));
+
+        let (wrong, _) = request(&state, "visitor", || login("ada".to_string(), "password2".to_string())).await;
+        assert!(wrong.is_err());
+        let (logged_in, cookies) = request(&state, "visitor", || login("ada".to_string(), "password1".to_string())).await;
+        assert_eq!(logged_in.unwrap(), account);
+        let session = set_session(&cookies);
+        assert_ne!(session, "visitor");
+        assert_eq!(state.account("visitor"), None);
+        let (current, _) = request(&state, &session, current_account).await;
+        assert_eq!(current.unwrap(), Some(account));
+    }
+
+    #[tokio::test]
+    async fn logout_saves_what_the_account_could_change() {
+        let state = ServerState::default();
+        let (account, cookies) = request(&state, "visitor", || register("ada".to_string(), "password1".to_string())).await;
+        let account = account.unwrap();
+        let session = set_session(&cookies);
+        let mut runtime = state.runtime(&session).unwrap();
+        let notes = format!("{}/notes.txt", account.home());
+        runtime.file_system_mut().set_user(account.credentials);
+        runtime.file_system_mut().create_file(notes.clone(), file_system::Metadata::new(file_system::FileType::File, "/text.png"), b"hi".to_vec()).unwrap();
+        let snapshot = snapshot::to_json(&runtime).unwrap();
+        let (out, _) = request(&state, &session, || logout(Some(snapshot))).await;
+        out.unwrap();
+        assert_eq!(state.account(&session), None);
+
+        state.login("again", account.clone());
+        let mut runtime = state.runtime("again").unwrap();
+        assert_eq!(runtime.file_system_mut().read_file(&notes).unwrap().as_ref(), b"hi");
+        // Only root could make this change, so the runtime is kept as it was.
+        runtime.file_system_mut().set_user(Credentials::ROOT);
+        runtime.file_system_mut().chown(&notes, 0, 0).unwrap();
+        let snapshot = snapshot::to_json(&runtime).unwrap();
+        let (out, _) = request(&state, "again", || logout(Some(snapshot))).await;
+        out.unwrap();
+        state.login("third", account.clone());
+        let runtime = state.runtime("third").unwrap();
+        assert_eq!(runtime.file_system().lstat(&notes).unwrap().owner, account.credentials.uid);
+    }
+
+    #[tokio::test]
+    async fn guests_runtimes_are_dropped_at_logout() {
+        let state = ServerState::default();
+        let (account, cookies) = request(&state, "visitor", login_as_guest).await;
+        let account = account.unwrap();
+        assert!(account.guest);
+        let session = set_session(&cookies);
+        let mut runtime = state.runtime(&session).unwrap();
+        let notes = format!("{}/notes.txt", account.home());
+        runtime.file_system_mut().set_user(account.credentials);
+        runtime.file_system_mut().create_file(notes.clone(), file_system::Metadata::new(file_system::FileType::File, "/text.png"), Vec::new()).unwrap();
+        let snapshot = snapshot::to_json(&runtime).unwrap();
+        let (out, _) = request(&state, &session, || logout(Some(snapshot))).await;
+        out.unwrap();
+        assert_eq!(state.account(&session), None);
+
+        state.login(&session, account);
+        assert!(state.runtime(&session).unwrap().file_system().lstat(&notes).is_err());
+    }
+
+    #[tokio::test]
+    async fn locked_sessions_are_refused_until_unlocked() {
+        let state = ServerState::default();
+        let (account, cookies) = request(&state, "visitor", || register("ada".to_string(), "password1".to_string())).await;
+        let account = account.unwrap();
+        let session = set_session(&cookies);
+        let windows = serde_json::to_string(&windows::WindowManager::default()).unwrap();
+        let (out, _) = request(&state, &session, power::lock_session).await;
+        out.unwrap();
+        let (published, _) = request(&state, &session, || events::publish_changes("tab".to_string(), Vec::new())).await;
+        assert!(published.is_err());
+        let (saved, _) = request(&state, &session, || session_restore::save_windows(windows.clone())).await;
+        assert!(saved.is_err());
+        let (listed, _) = request(&state, &session, || remote_store::list_server_files("docs".to_string())).await;
+        assert!(listed.is_err());
+
+        let (wrong, _) = request(&state, &session, || power::unlock("password2".to_string())).await;
+        assert!(wrong.is_err());
+        assert!(state.is_locked(&session));
+        let (out, _) = request(&state, &session, || power::unlock("password1".to_string())).await;
+        out.unwrap();
+        let (published, _) = request(&state, &session, || events::publish_changes("tab".to_string(), Vec::new())).await;
+        published.unwrap();
+        let (saved, _) = request(&state, &session, || session_restore::save_windows(windows.clone())).await;
+        saved.unwrap();
+
+        // Logging out while locked keeps the runtime the server had.
+        let mut runtime = state.runtime(&session).unwrap();
+        let notes = format!("{}/notes.txt", account.home());
+        runtime.file_system_mut().set_user(account.credentials);
+        runtime.file_system_mut().create_file(notes.clone(), file_system::Metadata::new(file_system::FileType::File, "/text.png"), Vec::new()).unwrap();
+        let snapshot = snapshot::to_json(&runtime).unwrap();
+        state.lock(&session);
+        let (out, _) = request(&state, &session, || logout(Some(snapshot))).await;
+        out.unwrap();
+        state.login("again", account);
+        assert!(state.runtime("again").unwrap().file_system().lstat(&notes).is_err());
+    }
+
+    #[tokio::test]
+    async fn requests_get_one_session() {
+        use axum::{Router,Extension,routing::get};
+        use tower::ServiceExt;
+        let app = Router::new()
+            .route("/", get(|Extension(Session(session)):Extension<Session>| async move { [("x-session", session)] }))
+            .layer(axum::middleware::from_fn(session::with_session))
+            .layer(tower_cookies::CookieManagerLayer::new());
+
+        let response = app.clone().oneshot(http::Request::new(axum::body::Body::empty())).await.unwrap();
+        let set_cookies = response.headers().get_all(http::header::SET_COOKIE).iter().collect::<Vec<_>>();
+        assert_eq!(set_cookies.len(), 1);
+        let set_cookie = set_cookies[0].to_str().unwrap();
+        let session = response.headers()["x-session"].to_str().unwrap();
+        assert!(set_cookie.starts_with(&format!("{SESSION_COOKIE}={session};")));
+        assert!(set_cookie.contains("HttpOnly"));
+        // Plain http, as the server is run by default.
+        assert!(!set_cookie.contains("Secure"));
+
+        let request = http::Request::builder()
+            .header("x-forwarded-proto", "https")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert!(response.headers()[http::header::SET_COOKIE].to_str().unwrap().contains("Secure"));
+
+        let request = http::Request::builder()
+            .header(http::header::COOKIE, format!("{SESSION_COOKIE}=known"))
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.headers()["x-session"], "known");
+        assert!(response.headers().get(http::header::SET_COOKIE).is_none());
+    }
+}
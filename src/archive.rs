@@ -0,0 +1,335 @@
+use super::*;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use file_system::{FileSystem,FileType,FsError,Metadata,parent_path};
+use vfs::{Backend,MountSource};
+
+const TAR_BLOCK: usize = 512;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ArchiveKind{
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind{
+    pub fn from_name(name:&str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+enum Location{
+    Dir,
+    Zip(zip::ZipEntry),
+    Tar{offset:usize,size:usize},
+}
+
+#[derive(Debug,Clone,PartialEq)]
+struct ArchiveEntry{
+    metadata:Metadata,
+    location:Location,
+}
+
+// An archive file in the FileSystem opened as a read-only directory. Only the listing is read
+// when mounting, entries are decompressed when they are read.
+#[derive(Debug,Clone,PartialEq)]
+pub struct MountedArchive{
+    kind:ArchiveKind,
+    // The archive file's content as of mounting, already inflated for a tar.gz since it has to
+    // be inflated as a whole to find its entries.
+    data:Vec<u8>,
+    // Keyed by absolute path inside the archive, e.g. "/docs/readme.txt".
+    entries:BTreeMap<String,ArchiveEntry>,
+}
+
+impl MountedArchive{
+    // Indexes the content of the archive file. Entries inherit the archive's owner and get
+    // its timestamps when the archive doesn't record any, with write bits cleared.
+    pub fn mount(kind:ArchiveKind, content:&[u8], archive:&Metadata) -> Result<Self,FsError> {
+        let malformed = |err:String| FsError::Malformed(err);
+        let mut mounted = Self{kind,data:Vec::new(),entries:BTreeMap::new()};
+        let listing = match kind {
+            ArchiveKind::Zip => zip::ZipReader::new(content).map_err(|err| malformed(err.to_string()))?
+                .entries().iter()
+                .map(|entry| {
+                    let location = if entry.is_dir() { Location::Dir } else { Location::Zip(entry.clone()) };
+                    (entry.name.clone(), entry.modified, entry.mode, location)
+                })
+                .collect::<Vec<_>>(),
+            ArchiveKind::Tar => tar_entries(content).map_err(malformed)?,
+            ArchiveKind::TarGz => {
+                let inflated = gunzip(content).map_err(malformed)?;
+                let listing = tar_entries(&inflated).map_err(malformed)?;
+                mounted.data = inflated;
+                listing
+            },
+        };
+        if kind != ArchiveKind::TarGz {
+            mounted.data = content.to_vec();
+        }
+        for (name,modified,mode,location) in listing {
+            let path = file_system::normalize_path(&name);
+            if path == "/" {
+                continue;
+            }
+            let modified = if modified == 0 { archive.modified } else { modified };
+            mounted.add_parents(&path, archive);
+            let mut metadata = match location {
+                Location::Dir => Metadata::new(FileType::Directory, "/folder.png"),
+                _ => {
+                    let mime = mime::from_extension(&path).unwrap_or(mime::OCTET_STREAM);
+                    let mut metadata = Metadata::new(FileType::File, mime::icon(mime));
+                    metadata.mime_type = Some(mime.to_string());
+                    metadata
+                },
+            };
+            metadata.created = modified;
+            metadata.modified = modified;
+            metadata.accessed = archive.accessed;
+            metadata.owner = archive.owner;
+            metadata.group = archive.group;
+            metadata.mode = mode & 0o555;
+            mounted.entries.insert(path, ArchiveEntry{metadata,location});
+        }
+        Ok(mounted)
+    }
+
+    // Archives often leave out entries for directories, make them up.
+    fn add_parents(&mut self, path:&str, archive:&Metadata) {
+        let mut dir = parent_path(path);
+        while dir != "/" && !self.entries.contains_key(dir) {
+            let mut metadata = Metadata::new(FileType::Directory, "/folder.png");
+            metadata.created = archive.modified;
+            metadata.modified = archive.modified;
+            metadata.accessed = archive.accessed;
+            metadata.owner = archive.owner;
+            metadata.group = archive.group;
+            metadata.mode = 0o555;
+            self.entries.insert(dir.to_string(), ArchiveEntry{metadata,location:Location::Dir});
+            dir = parent_path(dir);
+        }
+    }
+}
+
+impl Backend for MountedArchive{
+    fn source(&self) -> MountSource {
+        MountSource::Archive
+    }
+    // Nothing inside a mounted archive can change, not even as root.
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn metadata(&self, inner:&str) -> Option<&Metadata> {
+        self.entries.get(inner).map(|entry| &entry.metadata)
+    }
+    // Names of the direct children of a directory in the archive, "/" is the archive's top level.
+    fn list(&self, inner:&str) -> Result<Vec<String>,FsError> {
+        if inner != "/" && !self.metadata(inner).map_or(false,|metadata| metadata.is_dir()) {
+            return Err(FsError::NotADirectory(inner.to_string()));
+        }
+        let prefix = if inner == "/" { "/".to_string() } else { format!("{inner}/") };
+        Ok(self.entries.range(prefix.clone()..)
+            .take_while(|(path,_)| path.starts_with(&prefix))
+            .filter(|(path,_)| !path[prefix.len()..].contains('/'))
+            .map(|(path,_)| path[prefix.len()..].to_string())
+            .collect())
+    }
+    // Entries are decompressed on every read.
+    fn read(&self, inner:&str) -> Result<Cow<'_,[u8]>,FsError> {
+        let entry = self.entries.get(inner).ok_or_else(|| FsError::NotFound(inner.to_string()))?;
+        match &entry.location {
+            Location::Dir => Err(FsError::NotADirectory(inner.to_string())),
+            Location::Zip(entry) => zip::read_entry(&self.data, entry)
+                .map(Cow::Owned)
+                .map_err(|err| FsError::Malformed(err.to_string())),
+            Location::Tar{offset,size} => self.data.get(*offset..offset + size)
+                .map(Cow::Borrowed)
+                .ok_or_else(|| FsError::Malformed("truncated tar entry".to_string())),
+        }
+    }
+    fn box_clone(&self) -> Box<dyn Backend> {
+        Box::new(self.clone())
+    }
+    fn box_eq(&self, other:&dyn Backend) -> bool {
+        vfs::same_backend(self, other)
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+// (name, modified, mode, location) for every entry of a tar, following ustar and the GNU
+// long name extension.
+fn tar_entries(data:&[u8]) -> Result<Vec<(String,i64,u16,Location)>,String> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    let mut long_name : Option<String> = None;
+    while pos + TAR_BLOCK <= data.len() {
+        let header = &data[pos..pos + TAR_BLOCK];
+        // Two zero blocks end the archive.
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+        let size = octal(&header[124..136]).ok_or("bad tar entry size")? as usize;
+        let offset = pos + TAR_BLOCK;
+        pos = offset + size.div_ceil(TAR_BLOCK) * TAR_BLOCK;
+        let mut name = c_string(&header[0..100]);
+        if &header[257..262] == b"ustar" {
+            let prefix = c_string(&header[345..500]);
+            if !prefix.is_empty() {
+                name = format!("{prefix}/{name}");
+            }
+        }
+        if let Some(long_name) = long_name.take() {
+            name = long_name;
+        }
+        let mode = octal(&header[100..108]).unwrap_or(0o644) as u16;
+        let modified = octal(&header[136..148]).unwrap_or(0) as i64 * 1000;
+        match header[156] {
+            b'0' | 0 | b'7' => entries.push((name,modified,mode,Location::Tar{offset,size})),
+            b'5' => entries.push((name,modified,mode,Location::Dir)),
+            b'L' => long_name = Some(c_string(data.get(offset..offset + size).ok_or("truncated tar long name")?)),
+            // Links, devices and pax headers aren't shown.
+            _ => {},
+        }
+    }
+    Ok(entries)
+}
+
+fn c_string(field:&[u8]) -> String {
+    let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// None for a field that is blank as well as one that isn't octal, so callers fall back to
+// their default instead of reading a blank mode as 0.
+fn octal(field:&[u8]) -> Option<u64> {
+    let text = c_string(field);
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+// Strips the gzip header (rfc 1952) and inflates the deflate stream after it.
+fn gunzip(data:&[u8]) -> Result<Vec<u8>,String> {
+    const FEXTRA: u8 = 1 << 2;
+    const FNAME: u8 = 1 << 3;
+    const FCOMMENT: u8 = 1 << 4;
+    const FHCRC: u8 = 1 << 1;
+    if data.len() < 18 || data[0..3] != [0x1f,0x8b,8] {
+        return Err("not a gzip file".to_string());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = u16::from_le_bytes([data[pos],data[pos + 1]]) as usize;
+        pos += 2 + len;
+    }
+    for flag in [FNAME,FCOMMENT] {
+        if flags & flag != 0 {
+            pos += data.get(pos..).and_then(|rest| rest.iter().position(|byte| *byte == 0))
+                .ok_or("truncated gzip header")? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    let stream = data.get(pos..).ok_or("truncated gzip header")?;
+    miniz_oxide::inflate::decompress_to_vec(stream).map_err(|err| format!("gzip: {err:?}"))
+}
+
+// Zips paths into a new archive next to the first of them, returning the archive's path.
+pub fn compress(file_system:&mut FileSystem, paths:&[String]) -> Result<String,FsError> {
+    let Some(first) = paths.first() else { return Err(FsError::NotFound(String::new())) };
+    let content = export::zip_paths(file_system, paths)?;
+    let name = match paths {
+        [path] => format!("{}.zip", path.split('/').last().unwrap_or("Archive")),
+        _ => "Archive.zip".to_string(),
+    };
+    let path = file_system.unique_path(parent_path(first), &name);
+    let mut metadata = Metadata::new(FileType::File, mime::icon("application/zip"));
+    metadata.mime_type = Some("application/zip".to_string());
+    file_system.create_file(path.clone(), metadata, content)?;
+    Ok(path)
+}
+
+// Copies everything in an archive into a new folder next to it named after the archive,
+// returning the folder's path. Mounts the archive if it isn't already.
+pub fn extract_all(file_system:&mut FileSystem, archive_path:&str) -> Result<String,FsError> {
+    let archive_path = file_system.resolve(archive_path)?;
+    if !file_system.is_mounted(&archive_path) {
+        file_system.mount_archive(&archive_path)?;
+    }
+    let name = archive_path.split('/').last().unwrap_or_default();
+    let stem = [".tar.gz",".tgz",".tar",".zip"].iter()
+        .find_map(|extension| name.strip_suffix(extension))
+        .unwrap_or(name);
+    let dest = file_system.unique_path(parent_path(&archive_path), stem);
+    file_system.create_dir(dest.clone())?;
+    for child in file_system.list_dir(&archive_path)? {
+        file_system.copy(&child, &dest)?;
+    }
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tar with one entry per header, all empty, and the two zero blocks ending it.
+    fn tar(headers:&[(&str,&[u8],&[u8],u8)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (name,mode,size,type_flag) in headers {
+            let mut header = vec![0;TAR_BLOCK];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[100..100 + mode.len()].copy_from_slice(mode);
+            header[124..124 + size.len()].copy_from_slice(size);
+            header[136..147].copy_from_slice(b"00000000012");
+            header[156] = *type_flag;
+            data.extend(header);
+        }
+        data.extend([0;2 * TAR_BLOCK]);
+        data
+    }
+
+    #[test]
+    fn octal_fields() {
+        assert_eq!(octal(b"0000755\0"), Some(0o755));
+        assert_eq!(octal(b" 644 \0"), Some(0o644));
+        assert_eq!(octal(b"\0\0\0\0\0\0\0\0"), None);
+        assert_eq!(octal(b"       \0"), None);
+        assert_eq!(octal(b"0000999\0"), None);
+    }
+
+    #[test]
+    fn blank_modes_get_the_default() {
+        let data = tar(&[
+            ("set.txt", b"0000600\0", b"00000000000", b'0'),
+            ("blank.txt", b"", b"00000000000", b'0'),
+            ("spaces", b"       \0", b"00000000000", b'5'),
+        ]);
+        let entries = tar_entries(&data).unwrap();
+        let modes = entries.iter().map(|(name,modified,mode,_)| (name.as_str(),*modified,*mode)).collect::<Vec<_>>();
+        assert_eq!(modes, vec![("set.txt",10_000,0o600), ("blank.txt",10_000,0o644), ("spaces",10_000,0o644)]);
+        assert_eq!(entries[2].3, Location::Dir);
+    }
+
+    #[test]
+    fn blank_sizes_are_refused() {
+        let data = tar(&[("file.txt", b"0000644\0", b"", b'0')]);
+        assert_eq!(tar_entries(&data), Err("bad tar entry size".to_string()));
+    }
+}
@@ -1,7 +0,0 @@
-use super::*;
-
-#[derive(Debug,Clone,PartialEq,Default)]
-pub struct ClientState{
-    pub user_msg: user_msg::UserMsg,
-}
-
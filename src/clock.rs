@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64,Ordering};
+
+// Source of timestamps for Metadata. Times are milliseconds since the unix epoch, the same unit
+// js Date uses, so the wasm and server builds agree.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> i64;
+}
+
+// Reads the real time. chrono's wasmbind feature makes this work in the browser too.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+}
+
+// A clock that only moves when told to. Clones share the same time.
+#[derive(Debug,Clone,Default)]
+pub struct MockClock(Arc<AtomicI64>);
+
+impl MockClock {
+    pub fn new(now:i64) -> Self {
+        Self(Arc::new(AtomicI64::new(now)))
+    }
+    pub fn set(&self, now:i64) {
+        self.0.store(now,Ordering::SeqCst);
+    }
+    pub fn advance(&self, millis:i64) {
+        self.0.fetch_add(millis,Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> i64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// The clock a FileSystem stamps with. Two FileSystems compare equal regardless of their clocks.
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub fn new(clock:impl Clock + 'static) -> Self {
+        Self(Arc::new(clock))
+    }
+    pub fn now(&self) -> i64 {
+        self.0.now()
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl std::fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f,"SharedClock({})",self.now())
+    }
+}
+
+impl PartialEq for SharedClock {
+    fn eq(&self, _other:&Self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,357 @@
+use super::*;
+use std::collections::HashSet;
+use file_system::{FileSystem,FileType,Metadata,SystemRuntime,normalize_path,parent_path};
+use permissions::{Credentials,SYSTEM_APP_MODE};
+use accounts::Account;
+
+// Built into the binary, used when the server isn't given a config of its own.
+pub const DEFAULT_CONFIG: &'static str = include_str!("../desktop.toml");
+// Env var naming the config file the server loads at start.
+pub const CONFIG_ENV_VAR: &'static str = "SITE_OS_CONFIG";
+
+// Describes a fresh desktop: the apps, what's in the dock, the files and folders that exist
+// before the visitor does anything, and how it looks. See desktop.toml.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DesktopConfig{
+    pub wallpaper:String,
+    #[serde(default)]
+    pub theme:Theme,
+    // App ids in dock order.
+    #[serde(default)]
+    pub dock:Vec<String>,
+    #[serde(default)]
+    pub apps:Vec<AppConfig>,
+    #[serde(default)]
+    pub directories:Vec<DirectoryConfig>,
+    #[serde(default)]
+    pub files:Vec<FileConfig>,
+    #[serde(default)]
+    pub aliases:Vec<AliasConfig>,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Default,Serialize,Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum Theme{
+    #[default]
+    Dark,
+    Light,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig{
+    pub id:String,
+    pub path:String,
+    pub icon:String,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Default,Serialize,Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum Owner{
+    #[default]
+    Root,
+    User,
+}
+
+impl Owner{
+    fn credentials(&self, account:&Account) -> Credentials {
+        match self {
+            Self::Root => Credentials::ROOT,
+            Self::User => account.credentials,
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectoryConfig{
+    pub path:String,
+    #[serde(default)]
+    pub owner:Owner,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig{
+    pub path:String,
+    #[serde(default)]
+    pub content:String,
+    #[serde(default)]
+    pub owner:Owner,
+}
+
+// A symbolic link, shown as an alias on the desktop when it's in /Desktop.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AliasConfig{
+    pub path:String,
+    pub target:String,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum ConfigError{
+    Read(String),
+    Parse(String),
+    // Every problem found, not just the first.
+    Invalid(Vec<String>),
+}
+
+impl std::fmt::Display for ConfigError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(reason) => write!(f,"couldn't read desktop config: {reason}"),
+            Self::Parse(reason) => write!(f,"couldn't parse desktop config: {reason}"),
+            Self::Invalid(problems) => {
+                writeln!(f,"invalid desktop config:")?;
+                for problem in problems {
+                    writeln!(f,"  - {problem}")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+impl std::error::Error for ConfigError{}
+
+impl Default for DesktopConfig{
+    fn default() -> Self {
+        Self::parse(DEFAULT_CONFIG).expect("desktop.toml to be valid")
+    }
+}
+
+impl DesktopConfig{
+    pub fn parse(text:&str) -> Result<Self,ConfigError> {
+        let config = toml::from_str::<Self>(text).map_err(|err| ConfigError::Parse(err.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // The file named by SITE_OS_CONFIG, or the built in config when it isn't set.
+    #[cfg(feature="ssr")]
+    pub fn load() -> Result<Self,ConfigError> {
+        match std::env::var(CONFIG_ENV_VAR) {
+            Ok(path) => {
+                let text = std::fs::read_to_string(&path).map_err(|err| ConfigError::Read(format!("{path}: {err}")))?;
+                Self::parse(&text)
+            },
+            Err(_) => Self::parse(DEFAULT_CONFIG),
+        }
+    }
+
+    // Checks what building the FileSystem wouldn't catch, or would only catch one at a time.
+    pub fn validate(&self) -> Result<(),ConfigError> {
+        let mut problems = Vec::new();
+        let mut ids = HashSet::new();
+        let mut paths = HashSet::new();
+        for app in &self.apps {
+            if !ids.insert(app.id.as_str()) {
+                problems.push(format!("app id \"{}\" is used twice", app.id));
+            }
+            if !app.icon.starts_with('/') {
+                problems.push(format!("app \"{}\": icon \"{}\" has to start with / (it's served from public/)", app.id, app.icon));
+            }
+        }
+        if !self.wallpaper.starts_with('/') {
+            problems.push(format!("wallpaper \"{}\" has to start with / (it's served from public/)", self.wallpaper));
+        }
+        let all_paths = self.apps.iter().map(|app| ("app", &app.path))
+            .chain(self.directories.iter().map(|dir| ("directory", &dir.path)))
+            .chain(self.files.iter().map(|file| ("file", &file.path)))
+            .chain(self.aliases.iter().map(|alias| ("alias", &alias.path)));
+        for (kind, path) in all_paths {
+            if !path.starts_with('/') || normalize_path(path) != *path || path == "/" {
+                problems.push(format!("{kind} path \"{path}\" has to be absolute and normalized, like /Desktop/notes.txt"));
+            } else if !paths.insert(path.as_str()) {
+                problems.push(format!("{kind} path \"{path}\" is used twice"));
+            }
+        }
+        // Directories are created in order.
+        let mut dirs = HashSet::from(["/"]);
+        for dir in &self.directories {
+            if !dirs.contains(parent_path(&dir.path)) {
+                problems.push(format!("directory \"{}\" comes before its parent \"{}\"", dir.path, parent_path(&dir.path)));
+            }
+            dirs.insert(dir.path.as_str());
+        }
+        for (kind, path) in self.apps.iter().map(|app| ("app", &app.path))
+            .chain(self.files.iter().map(|file| ("file", &file.path)))
+            .chain(self.aliases.iter().map(|alias| ("alias", &alias.path))) {
+            if !dirs.contains(parent_path(path)) {
+                problems.push(format!("{kind} \"{path}\" is in \"{}\", which isn't a configured directory", parent_path(path)));
+            }
+        }
+        for id in &self.dock {
+            if !ids.contains(id.as_str()) {
+                problems.push(format!("dock has \"{id}\", which isn't an app id"));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    // The desktop a guest gets.
+    pub fn runtime(&self) -> Result<SystemRuntime,ConfigError> {
+        self.runtime_for(&Account::guest())
+    }
+
+    // Lays out a FileSystem from the config for an account, with its home directory under
+    // /Users. Apps and root owned entries are created as root, everything after is done as
+    // the account, which the FileSystem is left acting as.
+    pub fn runtime_for(&self, account:&Account) -> Result<SystemRuntime,ConfigError> {
+        let invalid = |path:&str, err:file_system::FsError| ConfigError::Invalid(vec![format!("{path}: {err}")]);
+        let user = account.credentials;
+        let mut file_system = FileSystem::new();
+        for app in &self.apps {
+            file_system.add_file(app.path.clone(), Metadata::new(FileType::File, &app.icon).with_mode(SYSTEM_APP_MODE))
+                .map_err(|err| invalid(&app.path, err))?;
+        }
+        for dir in &self.directories {
+            file_system.create_dir(dir.path.clone()).map_err(|err| invalid(&dir.path, err))?;
+            give_to(&mut file_system, &dir.path, dir.owner.credentials(account)).map_err(|err| invalid(&dir.path, err))?;
+        }
+        if file_system.get_file_metadata(accounts::USERS_PATH).is_err() {
+            file_system.create_dir(accounts::USERS_PATH.to_string()).map_err(|err| invalid(accounts::USERS_PATH, err))?;
+        }
+        let home = account.home();
+        file_system.create_dir(home.clone()).map_err(|err| invalid(&home, err))?;
+        give_to(&mut file_system, &home, user).map_err(|err| invalid(&home, err))?;
+        for file in &self.files {
+            let mime = mime::from_extension(&file.path).unwrap_or("text/plain");
+            let mut metadata = Metadata::new(FileType::File, mime::icon(mime));
+            metadata.mime_type = Some(mime.to_string());
+            file_system.create_file(file.path.clone(), metadata, file.content.clone().into_bytes())
+                .map_err(|err| invalid(&file.path, err))?;
+            give_to(&mut file_system, &file.path, file.owner.credentials(account)).map_err(|err| invalid(&file.path, err))?;
+        }
+        file_system.set_user(user);
+        for alias in &self.aliases {
+            file_system.symlink(&alias.target, alias.path.clone()).map_err(|err| invalid(&alias.path, err))?;
+        }
+        let dock = self.dock.iter()
+            .filter_map(|id| self.apps.iter().find(|app| &app.id == id))
+            .map(|app| app.path.clone())
+            .collect();
+        let mut runtime = SystemRuntime::new(file_system, dock);
+        let appearance = &mut runtime.settings_mut().appearance;
+        appearance.theme = theme::builtin_name(self.theme).to_string();
+        appearance.wallpaper = settings::Wallpaper::Public(self.wallpaper.clone());
+        Ok(runtime)
+    }
+}
+
+fn give_to(file_system:&mut FileSystem, path:&str, owner:Credentials) -> Result<(),file_system::FsError> {
+    file_system.chown(path, owner.uid, owner.gid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL: &str = r#"
+        wallpaper = "/bg.png"
+        dock = ["finder"]
+
+        [[apps]]
+        id = "finder"
+        path = "/finder"
+        icon = "/folder.png"
+
+        [[directories]]
+        path = "/Desktop"
+    "#;
+
+    fn problems(text:&str) -> Vec<String> {
+        match DesktopConfig::parse(text) {
+            Err(ConfigError::Invalid(problems)) => problems,
+            other => panic!("expected it to be invalid, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn bundled_config_is_valid() {
+        let config = DesktopConfig::parse(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn theme_defaults_to_dark() {
+        assert_eq!(DesktopConfig::parse(MINIMAL).unwrap().theme, Theme::Dark);
+    }
+
+    #[test]
+    fn wallpaper_is_required() {
+        let text = MINIMAL.replace(r#"wallpaper = "/bg.png""#, "");
+        assert!(matches!(DesktopConfig::parse(&text), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn unknown_themes_dont_parse() {
+        let text = format!("theme = \"sepia\"\n{MINIMAL}");
+        assert!(matches!(DesktopConfig::parse(&text), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn duplicate_app_ids_are_refused() {
+        let text = format!("{MINIMAL}
+            [[apps]]
+            id = \"finder\"
+            path = \"/finder2\"
+            icon = \"/folder.png\"
+        ");
+        assert_eq!(problems(&text), vec!["app id \"finder\" is used twice".to_string()]);
+    }
+
+    #[test]
+    fn dock_entries_need_an_app() {
+        let text = MINIMAL.replace(r#"dock = ["finder"]"#, r#"dock = ["finder", "mail"]"#);
+        assert_eq!(problems(&text), vec!["dock has \"mail\", which isn't an app id".to_string()]);
+    }
+
+    #[test]
+    fn public_paths_start_with_a_slash() {
+        let text = MINIMAL.replace(r#""/bg.png""#, r#""bg.png""#).replace(r#""/folder.png""#, r#""folder.png""#);
+        assert_eq!(problems(&text), vec![
+            "app \"finder\": icon \"folder.png\" has to start with / (it's served from public/)".to_string(),
+            "wallpaper \"bg.png\" has to start with / (it's served from public/)".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn bad_paths_are_all_reported() {
+        let text = format!("{MINIMAL}
+            [[directories]]
+            path = \"/Documents/Old\"
+
+            [[directories]]
+            path = \"/Desktop/\"
+
+            [[files]]
+            path = \"notes.txt\"
+
+            [[files]]
+            path = \"/Desktop\"
+
+            [[aliases]]
+            path = \"/Music/Finder\"
+            target = \"/finder\"
+        ");
+        assert_eq!(problems(&text), vec![
+            "directory path \"/Desktop/\" has to be absolute and normalized, like /Desktop/notes.txt".to_string(),
+            "file path \"notes.txt\" has to be absolute and normalized, like /Desktop/notes.txt".to_string(),
+            "file path \"/Desktop\" is used twice".to_string(),
+            "directory \"/Documents/Old\" comes before its parent \"/Documents\"".to_string(),
+            "alias \"/Music/Finder\" is in \"/Music\", which isn't a configured directory".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn unknown_fields_dont_parse() {
+        let text = format!("colour = \"blue\"\n{MINIMAL}");
+        assert!(matches!(DesktopConfig::parse(&text), Err(ConfigError::Parse(_))));
+    }
+}
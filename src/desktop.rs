@@ -1,15 +1,31 @@
 use super::*;
+use file_system::{SystemRuntime,DirEntry,DESKTOP_PATH};
 
-#[component]
+#[island]
 pub fn Desktop() -> impl IntoView{
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let imports = expect_context::<RwSignal<import::Imports>>();
+    let selection = expect_context::<RwSignal<Selection>>();
+    let mobile = expect_context::<RwSignal<mobile::MobileLayout>>();
     view!{
+    <Show when=move || mobile().0 fallback=move || view!{
     <div 
         class="grid gap-10 p-16" 
         style="repeat(auto-fill, minmax(6rem, 1fr));"
         on:dragover = move |ev| ev.prevent_default()
+        on:drop = move |ev| {
+            ev.prevent_default();
+            import::import_drop(&ev, DESKTOP_PATH.to_string(), system, imports);
+        }
+        on:click = move |_| selection.set(Selection::default())
          >
         <DesktopItem src="hard-disk.png"/>
+        <DesktopAliases/>
     </div>
+    }>
+        <mobile::HomeScreen/>
+    </Show>
+    <import::ImportProgressList/>
       
     }
 }
@@ -23,4 +39,71 @@ pub fn DesktopItem(src:&'static str) -> impl IntoView{
         <img src=src clas="w-16" />
         </div>
     }
-}
\ No newline at end of file
+}
+
+// Everything in DESKTOP_PATH, mostly aliases to apps.
+#[component]
+pub fn DesktopAliases() -> impl IntoView{
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    view!{
+        <For
+        each=move || system.with(|system| system.dir_entries(DESKTOP_PATH))
+        key=|entry| (entry.path.clone(),entry.dangling)
+        children=move |entry| view!{<AliasItem entry/>}
+        />
+    }
+}
+
+// Paths selected on the desktop, what File > Download acts on.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Selection(pub Vec<String>);
+
+#[component]
+fn AliasItem(entry:DirEntry) -> impl IntoView{
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let selection = expect_context::<RwSignal<Selection>>();
+    let DirEntry{path,name,img_src,dangling,..} = entry;
+    let selected = {
+        let path = path.clone();
+        move || selection.with(|selection| selection.0.contains(&path))
+    };
+    let select_path = path.clone();
+    let press_path = path.clone();
+    let selected_on_press = selected.clone();
+    view!{
+        <div class="w-24 flex flex-col items-center rounded-theme" id=path.clone()
+        class=("bg-selected", selected.clone())
+        class=("bg-opacity-50", selected)
+        title=dangling.then(|| "The original item can't be found.")
+        on:click=move |ev| {
+            ev.stop_propagation();
+            // Cmd/Ctrl+click adds to or removes from the selection.
+            let multi = ev.meta_key() || ev.ctrl_key();
+            selection.update(|selection| {
+                if !multi {
+                    selection.0 = vec![select_path.clone()];
+                } else if let Some(idx) = selection.0.iter().position(|p| p == &select_path) {
+                    selection.0.remove(idx);
+                } else {
+                    selection.0.push(select_path.clone());
+                }
+            });
+        }
+        on:dblclick=move |_| finder::open(system, &path)
+        // Right clicks, or long presses on touch screens, select the item they're on.
+        on:contextmenu=move |ev| {
+            ev.prevent_default();
+            if !selected_on_press() {
+                selection.set(Selection(vec![press_path.clone()]));
+            }
+        }
+        >
+        <div class="relative">
+            <img src=img_src class="w-16" class=("opacity-50",dangling)/>
+            <span class="absolute bottom-0 right-0 text-xs rounded-full bg-danger pl-1 pr-1"
+            class=("hidden",!dangling)>"?"</span>
+        </div>
+        <p class="text-sm text-center" class=("italic",dangling)>{name}</p>
+        </div>
+    }
+}
@@ -0,0 +1,316 @@
+use super::*;
+use std::collections::HashMap;
+use futures::channel::oneshot;
+use file_system::{SystemRuntime,normalize_path,parent_path};
+
+// What Tab cycles through inside a dialog.
+const FOCUSABLE: &'static str = "button:not([disabled]), input:not([disabled]), [tabindex]:not([tabindex='-1'])";
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum DialogKind{
+    Confirm{message:String,confirm_label:String},
+    Prompt{message:String,value:String},
+    OpenFile{dir:String},
+    SaveFile{dir:String,name:String},
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum DialogResult{
+    Cancelled,
+    Confirmed,
+    Text(String),
+    Path(String),
+}
+
+// What a dialog blocks until it's answered.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Modality{
+    // The whole desktop.
+    System,
+    // Only the window of the app at this path, see WindowDialogs.
+    Window(String),
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Dialog{
+    pub id:u64,
+    pub title:String,
+    pub kind:DialogKind,
+    pub modality:Modality,
+}
+
+// Opens dialogs and hands their results back. Each method returns once the dialog is answered,
+// a dialog that goes away unanswered counts as cancelled.
+//
+//     let dialogs = expect_context::<Dialogs>();
+//     spawn_local(async move {
+//         if dialogs.confirm(Modality::System, "Log Out", "Log out now?").await { ... }
+//     });
+#[derive(Debug,Clone,Copy)]
+pub struct Dialogs{
+    // Open dialogs, the last one is on top.
+    open:RwSignal<Vec<Dialog>>,
+    senders:StoredValue<HashMap<u64,oneshot::Sender<DialogResult>>>,
+    next_id:StoredValue<u64>,
+}
+
+impl Dialogs{
+    pub async fn show(&self, title:impl Into<String>, kind:DialogKind, modality:Modality) -> DialogResult {
+        let id = self.next_id.get_value() + 1;
+        self.next_id.set_value(id);
+        let (sender, receiver) = oneshot::channel();
+        self.senders.update_value(|senders| { senders.insert(id, sender); });
+        self.open.update(|open| open.push(Dialog{id,title:title.into(),kind,modality}));
+        receiver.await.unwrap_or(DialogResult::Cancelled)
+    }
+
+    pub async fn confirm(&self, modality:Modality, title:impl Into<String>, message:impl Into<String>) -> bool {
+        let kind = DialogKind::Confirm{message:message.into(),confirm_label:"OK".to_string()};
+        self.show(title, kind, modality).await == DialogResult::Confirmed
+    }
+
+    // None when cancelled.
+    pub async fn prompt(&self, modality:Modality, title:impl Into<String>, message:impl Into<String>, value:impl Into<String>) -> Option<String> {
+        match self.show(title, DialogKind::Prompt{message:message.into(),value:value.into()}, modality).await {
+            DialogResult::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    // The path of an existing file picked starting in dir.
+    pub async fn open_file(&self, modality:Modality, dir:impl Into<String>) -> Option<String> {
+        match self.show("Open", DialogKind::OpenFile{dir:dir.into()}, modality).await {
+            DialogResult::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    // Where to save, starting in dir with name filled in. The path may not exist yet.
+    pub async fn save_file(&self, modality:Modality, dir:impl Into<String>, name:impl Into<String>) -> Option<String> {
+        match self.show("Save", DialogKind::SaveFile{dir:dir.into(),name:name.into()}, modality).await {
+            DialogResult::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn resolve(&self, id:u64, result:DialogResult) {
+        self.open.update(|open| open.retain(|dialog| dialog.id != id));
+        if let Some(sender) = self.senders.try_update_value(|senders| senders.remove(&id)).flatten() {
+            // The caller stopped waiting, nothing to tell.
+            _ = sender.send(result);
+        }
+    }
+
+    pub fn cancel(&self, id:u64) {
+        self.resolve(id, DialogResult::Cancelled);
+    }
+
+    fn with_modality(&self, modality:&Modality) -> Vec<Dialog> {
+        self.open.with(|open| open.iter().filter(|dialog| &dialog.modality == modality).cloned().collect())
+    }
+}
+
+// Called once by OperatingSystemProvider.
+pub fn provide_dialogs() -> Dialogs {
+    let dialogs = Dialogs{open:create_rw_signal(Vec::new()),senders:store_value(HashMap::new()),next_id:store_value(0)};
+    provide_context(dialogs);
+    dialogs
+}
+
+// System modal dialogs, over everything but the power overlay.
+#[island]
+pub fn DialogLayer() -> impl IntoView {
+    let dialogs = expect_context::<Dialogs>();
+    view!{
+        <For
+        each=move || dialogs.with_modality(&Modality::System)
+        key=|dialog| dialog.id
+        children=move |dialog| view!{
+            <div class="fixed inset-0 z-50 flex items-center justify-center bg-backdrop bg-opacity-40">
+                <DialogView dialog/>
+            </div>
+        }
+        />
+    }
+}
+
+// Dialogs modal to one app's window. Goes inside the window, which has to be positioned, and
+// covers only it.
+#[component]
+pub fn WindowDialogs(#[prop(into)] app:String) -> impl IntoView {
+    let dialogs = expect_context::<Dialogs>();
+    let modality = Modality::Window(app);
+    view!{
+        <For
+        each=move || dialogs.with_modality(&modality)
+        key=|dialog| dialog.id
+        children=move |dialog| view!{
+            <div class="absolute inset-0 z-10 flex items-start justify-center pt-8 bg-backdrop bg-opacity-40">
+                <DialogView dialog/>
+            </div>
+        }
+        />
+    }
+}
+
+// One dialog. Keeps the focus inside itself and cancels on Escape.
+#[component]
+fn DialogView(dialog:Dialog) -> impl IntoView {
+    let dialogs = expect_context::<Dialogs>();
+    let container = create_node_ref::<leptos::html::Div>();
+    let Dialog{id,title,kind,..} = dialog;
+    create_effect(move |_| {
+        if let Some(first) = container.get().and_then(|container| focusable(&container).into_iter().next()) {
+            _ = first.focus();
+        }
+    });
+    let body = match kind {
+        DialogKind::Confirm{message,confirm_label} => view!{
+            <p>{message}</p>
+            <div class="flex justify-end gap-2">
+                <button class="px-3 rounded-theme bg-control hover:bg-highlight" on:click=move |_| dialogs.cancel(id)>"Cancel"</button>
+                <button class="px-3 rounded-theme bg-selected hover:bg-highlight"
+                on:click=move |_| dialogs.resolve(id, DialogResult::Confirmed)>{confirm_label}</button>
+            </div>
+        }.into_view(),
+        DialogKind::Prompt{message,value} => {
+            let value = create_rw_signal(value);
+            view!{
+                <form class="flex flex-col gap-2"
+                on:submit=move |ev| {
+                    ev.prevent_default();
+                    dialogs.resolve(id, DialogResult::Text(value.get_untracked()));
+                }>
+                    <label class="flex flex-col gap-1">
+                        {message}
+                        <input class="rounded-theme px-2 py-1 bg-field outline-none"
+                        prop:value=value
+                        on:input=move |ev| value.set(event_target_value(&ev))/>
+                    </label>
+                    <div class="flex justify-end gap-2">
+                        <button type="button" class="px-3 rounded-theme bg-control hover:bg-highlight" on:click=move |_| dialogs.cancel(id)>"Cancel"</button>
+                        <button type="submit" class="px-3 rounded-theme bg-selected hover:bg-highlight">"OK"</button>
+                    </div>
+                </form>
+            }.into_view()
+        },
+        DialogKind::OpenFile{dir} => view!{<FilePicker id dir save_name=None/>}.into_view(),
+        DialogKind::SaveFile{dir,name} => view!{<FilePicker id dir save_name=Some(name)/>}.into_view(),
+    };
+    view!{
+        <div class="w-96 flex flex-col gap-3 p-4 rounded-theme-lg bg-surface bg-opacity-90 backdrop-blur-theme"
+        role="dialog" aria-modal="true"
+        _ref=container
+        on:keydown=move |ev| match ev.key().as_str() {
+            "Escape" => {
+                ev.stop_propagation();
+                dialogs.cancel(id);
+            },
+            "Tab" => {
+                let Some(container) = container.get_untracked() else { return };
+                let elements = focusable(&container);
+                let (Some(first), Some(last)) = (elements.first(), elements.last()) else { return };
+                let active = document().active_element();
+                let wrap_to = if ev.shift_key() { (first, last) } else { (last, first) };
+                if active.as_ref() == Some(wrap_to.0.unchecked_ref::<web_sys::Element>()) {
+                    ev.prevent_default();
+                    _ = wrap_to.1.focus();
+                }
+            },
+            _ => {},
+        }>
+            <p class="font-bold">{title}</p>
+            {body}
+        </div>
+    }
+}
+
+fn focusable(container:&web_sys::HtmlElement) -> Vec<web_sys::HtmlElement> {
+    let Ok(nodes) = container.query_selector_all(FOCUSABLE) else { return Vec::new() };
+    (0..nodes.length())
+        .filter_map(|i| nodes.get(i))
+        .filter_map(|node| node.dyn_into::<web_sys::HtmlElement>().ok())
+        .collect()
+}
+
+// Browses the FileSystem. Opening picks an existing file, saving picks a directory and a name
+// typed into save_name's field.
+#[component]
+fn FilePicker(id:u64, dir:String, save_name:Option<String>) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let dialogs = expect_context::<Dialogs>();
+    let saving = save_name.is_some();
+    let dir = create_rw_signal(dir);
+    let selected = create_rw_signal(None::<String>);
+    let name = create_rw_signal(save_name.unwrap_or_default());
+    let entries = move || system.with(|system| {
+        system.dir_entries(&dir()).into_iter()
+            .map(|entry| {
+                let is_dir = system.file_system().get_file_metadata(&entry.path).map_or(false, |metadata| metadata.is_dir());
+                (entry, is_dir)
+            })
+            .collect::<Vec<_>>()
+    });
+    let choose = move || {
+        let path = if saving {
+            let name = name.get_untracked();
+            (!name.trim().is_empty()).then(|| normalize_path(&format!("{}/{}", dir.get_untracked(), name.trim())))
+        } else {
+            selected.get_untracked()
+        };
+        if let Some(path) = path {
+            dialogs.resolve(id, DialogResult::Path(path));
+        }
+    };
+    view!{
+        <div class="flex items-center gap-2 text-sm">
+            <button class="px-2 rounded-theme bg-control hover:bg-highlight" title="Enclosing Folder"
+            disabled=move || dir() == "/"
+            on:click=move |_| dir.update(|dir| *dir = parent_path(dir).to_string())>"‹"</button>
+            <span class="truncate">{dir}</span>
+        </div>
+        <div class="h-56 flex flex-col overflow-y-auto rounded-theme bg-field p-1 text-sm">
+            <For
+            each=entries
+            key=|(entry,is_dir)| (entry.path.clone(),*is_dir)
+            children=move |(entry,is_dir)| {
+                let path = entry.path.clone();
+                let is_selected = {
+                    let path = path.clone();
+                    move || selected.with(|selected| selected.as_ref() == Some(&path))
+                };
+                let name_on_click = entry.name.clone();
+                view!{
+                    <button class="flex items-center gap-2 px-1 rounded-theme text-left"
+                    class=("bg-selected", is_selected)
+                    on:click=move |_| if is_dir {
+                        selected.set(None);
+                        dir.set(path.clone());
+                    } else {
+                        selected.set(Some(path.clone()));
+                        if saving {
+                            name.set(name_on_click.clone());
+                        }
+                    }
+                    on:dblclick=move |_| if !is_dir { choose() }>
+                        <img class="w-4" src=entry.img_src/>
+                        {entry.name}
+                    </button>
+                }
+            }
+            />
+        </div>
+        {saving.then(|| view!{
+            <input class="rounded-theme px-2 py-1 bg-field outline-none" placeholder="Name"
+            prop:value=name
+            on:input=move |ev| name.set(event_target_value(&ev))
+            on:keydown=move |ev| if ev.key() == "Enter" { choose() }/>
+        })}
+        <div class="flex justify-end gap-2">
+            <button class="px-3 rounded-theme bg-control hover:bg-highlight" on:click=move |_| dialogs.cancel(id)>"Cancel"</button>
+            <button class="px-3 rounded-theme bg-selected hover:bg-highlight"
+            disabled=move || if saving { name.with(|name| name.trim().is_empty()) } else { selected.with(Option::is_none) }
+            on:click=move |_| choose()>{if saving { "Save" } else { "Open" }}</button>
+        </div>
+    }
+}
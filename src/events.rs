@@ -0,0 +1,250 @@
+use super::*;
+use file_system::{Metadata,SystemRuntime};
+
+// Name of the leptos_sse signal the server pushes events through.
+pub const EVENTS_SIGNAL: &'static str = "site_os_events";
+// Where the event stream is served, see main.rs.
+pub const EVENTS_URL: &'static str = "/events";
+// Events the server keeps per desktop for clients that reconnect.
+#[cfg(feature="ssr")]
+const REPLAY_LEN: usize = 256;
+// How far a connection can fall behind before it's dropped. Less than REPLAY_LEN so what it
+// missed is still kept when it reconnects.
+#[cfg(feature="ssr")]
+const CHANNEL_LEN: usize = REPLAY_LEN / 2;
+
+// A change to a FileSystem's tree, journaled by the tab that made it and replayed by the
+// others, see FileSystem::take_journal and FileSystem::apply_change.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub enum FsChange{
+    Created{path:String,metadata:Metadata},
+    Written{path:String,content:Vec<u8>},
+    Removed{path:String},
+    Renamed{from:String,to:String},
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub enum EventKind{
+    Fs(FsChange),
+    Notification{title:String,body:String},
+    // Events the tab needed are no longer kept, it has to start over from the server's runtime.
+    Resync,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct ServerEvent{
+    // Increases by one per event of a desktop, sent as the SSE event id so a reconnecting
+    // EventSource resumes after the last one it saw. Higher after a restart of the server than
+    // before it.
+    pub id:u64,
+    // The tab that caused the event, which doesn't apply it again.
+    pub origin:String,
+    pub kind:EventKind,
+}
+
+// What the leptos_sse signal holds. Each push replaces latest, the client reacts to every
+// change of it.
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+pub struct EventLog{
+    pub latest:Option<ServerEvent>,
+}
+
+// Identifies this tab to the server, so its own events aren't applied twice.
+#[derive(Debug,Clone,PartialEq)]
+pub struct TabId(pub String);
+
+// Connects to the event stream, applies other tabs' changes to the runtime, shows the server's
+// notifications and sends this tab's changes to the server. Called once by
+// OperatingSystemProvider. The browser's EventSource reconnects on its own and resumes from
+// the last event id it received.
+pub fn provide_events(system:RwSignal<SystemRuntime>, notifications:RwSignal<notifications::Notifications>) {
+    let tab = new_tab_id();
+    provide_context(tab.clone());
+    if let Err(err) = leptos_sse::provide_sse(EVENTS_URL) {
+        log::error!("couldn't connect to {EVENTS_URL}: {err:?}");
+        return;
+    }
+    let log = leptos_sse::create_sse_signal::<EventLog>(EVENTS_SIGNAL);
+    let origin = tab.0.clone();
+    let applied = store_value(0u64);
+    create_effect(move |_| {
+        let Some(event) = log.with(|log| log.latest.clone()) else { return };
+        // A replay after reconnecting can repeat events this tab already applied.
+        if event.id <= applied.get_value() {
+            return;
+        }
+        applied.set_value(event.id);
+        match event.kind {
+            EventKind::Fs(change) if event.origin != origin => {
+                system.update(|system| system.file_system_mut().apply_change(change));
+            },
+            EventKind::Fs(_) => {},
+            EventKind::Notification{title,body} => {
+                notifications::notify(notifications, notifications::Notification::new(notifications::Severity::Info, title).with_body(body));
+            },
+            // The page is rendered from the server's runtime, which has every change.
+            EventKind::Resync => _ = window().location().reload(),
+        }
+    });
+    let origin = tab.0;
+    create_effect(move |_| {
+        if !system.with(|system| system.file_system().has_journal()) {
+            return;
+        }
+        let changes = system.try_update_untracked(|system| system.file_system_mut().take_journal()).unwrap_or_default();
+        let origin = origin.clone();
+        spawn_local(async move {
+            if let Err(err) = publish_changes(origin, changes).await {
+                log::error!("couldn't publish changes: {err}");
+            }
+        });
+    });
+}
+
+// js_sys only works in the browser. The server renders the island with an empty id, its
+// effects never run so it never publishes.
+fn new_tab_id() -> TabId {
+    if cfg!(feature="ssr") {
+        return TabId(String::new());
+    }
+    TabId(format!("{:x}", (js_sys::Math::random() * u64::MAX as f64) as u64))
+}
+
+#[server(PublishChanges, "/api")]
+pub async fn publish_changes(origin:String, changes:Vec<FsChange>) -> Result<(),ServerFnError> {
+    let (session, state, account) = accounts::unlocked_account()?;
+    let key = state.runtime_key(&session, &account);
+    let (applied, refused) = state.apply_changes(&key, &account, changes);
+    // Other tabs only get what the server allowed.
+    for change in applied {
+        state.events().publish(&key, origin.clone(), EventKind::Fs(change));
+    }
+    match refused.first() {
+        Some(err) => Err(ServerFnError::ServerError(format!("{} of the changes were refused: {err}", refused.len()))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature="ssr")]
+pub use hub::*;
+
+#[cfg(feature="ssr")]
+mod hub{
+    use super::*;
+    use std::collections::{HashMap,VecDeque};
+    use std::sync::{Arc,Mutex};
+    use std::sync::atomic::{AtomicU64,Ordering};
+    use axum::response::sse::{Event,KeepAlive,Sse};
+    use futures::future;
+    use futures::stream::{self,Stream,StreamExt};
+    use tokio::sync::broadcast;
+
+    struct Channel{
+        next_id:u64,
+        recent:VecDeque<ServerEvent>,
+        sender:broadcast::Sender<ServerEvent>,
+    }
+
+    impl Channel{
+        fn new(first_id:u64) -> Self {
+            Self{next_id:first_id,recent:VecDeque::new(),sender:broadcast::channel(CHANNEL_LEN).0}
+        }
+    }
+
+    // Where this run of the server starts numbering events: the time it started in ms, shifted
+    // to leave room for 2^20 events per ms it runs. Ids stay above those of earlier runs, so tabs
+    // that saw those don't take new events for ones they already applied.
+    fn first_event_id() -> u64 {
+        let started = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        (started.as_millis() as u64) << 20
+    }
+
+    // Fans events out to every connection to a desktop, keeping the last REPLAY_LEN of them.
+    // Desktops are named by ServerState::runtime_key, so every tab and device an account is
+    // logged in on shares one.
+    #[derive(Clone)]
+    pub struct EventHub{
+        channels:Arc<Mutex<HashMap<String,Channel>>>,
+        first_id:u64,
+    }
+
+    impl Default for EventHub{
+        fn default() -> Self {
+            Self{channels:Default::default(),first_id:first_event_id()}
+        }
+    }
+
+    impl std::fmt::Debug for EventHub{
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("EventHub").finish_non_exhaustive()
+        }
+    }
+
+    impl EventHub{
+        pub fn publish(&self, key:&str, origin:String, kind:EventKind) {
+            let mut channels = self.channels.lock().unwrap();
+            let channel = channels.entry(key.to_string()).or_insert_with(|| Channel::new(self.first_id));
+            let event = ServerEvent{id:channel.next_id,origin,kind};
+            channel.next_id += 1;
+            if channel.recent.len() == REPLAY_LEN {
+                channel.recent.pop_front();
+            }
+            channel.recent.push_back(event.clone());
+            // No receivers just means no tab is connected right now.
+            _ = channel.sender.send(event);
+        }
+
+        // Events after last_id that are still kept, and a receiver for the ones to come. When
+        // some after last_id are no longer kept the replay starts with EventKind::Resync.
+        pub fn subscribe(&self, key:&str, last_id:u64) -> (Vec<ServerEvent>,broadcast::Receiver<ServerEvent>) {
+            let mut channels = self.channels.lock().unwrap();
+            let channel = channels.entry(key.to_string()).or_insert_with(|| Channel::new(self.first_id));
+            let mut replay = Vec::new();
+            match channel.recent.front() {
+                Some(first) if last_id != 0 && first.id > last_id + 1 => {
+                    replay.push(ServerEvent{id:first.id - 1,origin:String::new(),kind:EventKind::Resync});
+                },
+                _ => {},
+            }
+            replay.extend(channel.recent.iter().filter(|event| event.id > last_id).cloned());
+            (replay, channel.sender.subscribe())
+        }
+    }
+
+    // GET /events. Replays what the client missed according to its Last-Event-ID, then streams
+    // events as they're published. Each one goes out as a leptos_sse patch of EventLog.
+    // Nothing goes to a locked session: the stream ends at the first event while it's locked,
+    // and the EventSource reconnecting gets it replayed once the session is unlocked.
+    pub async fn event_stream(
+        headers:http::HeaderMap,
+        axum::Extension(state):axum::Extension<server_state::ServerState>,
+        axum::Extension(session::Session(session)):axum::Extension<session::Session>,
+    ) -> Sse<impl Stream<Item=Result<Event,axum::BoxError>>> {
+        // Sessions that aren't logged in get a channel of their own that nothing publishes to.
+        let key = state.session_key(&session).unwrap_or_else(|| format!("anonymous:{session}"));
+        let last_id = headers.get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        let (replay, receiver) = state.events().subscribe(&key, last_id);
+        // Also ends when the connection fell behind, the EventSource then reconnects with the
+        // last id it got and what it missed is replayed, see EventHub::subscribe.
+        let live = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.ok().map(|event| (event, receiver))
+        });
+        // leptos_sse builds the Event, the id of the event it was built from is kept here so
+        // it can be attached afterwards.
+        let current_id = Arc::new(AtomicU64::new(last_id));
+        let set_id = current_id.clone();
+        let logs = stream::iter(replay).chain(live)
+            .take_while(move |_| future::ready(!state.is_locked(&session)))
+            .map(move |event| {
+                set_id.store(event.id, Ordering::SeqCst);
+                Ok::<_,axum::BoxError>(EventLog{latest:Some(event)})
+            });
+        let events = leptos_sse::ServerSentEvents::new(EVENTS_SIGNAL, logs)
+            .expect("EventLog to serialize")
+            .map(move |event| event.map(|event| event.id(current_id.load(Ordering::SeqCst).to_string())));
+        Sse::new(events).keep_alive(KeepAlive::default())
+    }
+}
@@ -0,0 +1,91 @@
+use super::*;
+use file_system::{FileSystem,FsError,SystemRuntime,parent_path};
+use wasm_bindgen::JsValue;
+
+// How long the browser gets to start a download before its object url is revoked.
+const REVOKE_URL_AFTER_MS: u32 = 10_000;
+
+// Downloads a single file as itself, or anything else (folders, several paths) as a zip.
+// Files in remote mounts are fetched first.
+pub fn download_paths(system:RwSignal<SystemRuntime>, paths:Vec<String>) {
+    spawn_local(async move {
+        if let Err(err) = vfs::fetch(system, &paths).await {
+            log::error!("download failed: {err}");
+            return;
+        }
+        let Some(result) = system.try_update(|system| export(system.file_system_mut(), &paths)) else { return };
+        match result.map_err(|err| JsValue::from_str(&err.to_string()))
+            .and_then(|(name,mime,content)| download(&name, &mime, &content)) {
+            Ok(()) => {},
+            Err(err) => log::error!("download failed: {err:?}"),
+        }
+    });
+}
+
+// The file name, mime type and bytes that downloading paths produces.
+pub fn export(file_system:&mut FileSystem, paths:&[String]) -> Result<(String,String,Vec<u8>),FsError> {
+    if let [path] = paths {
+        let metadata = file_system.get_file_metadata(path)?;
+        if !metadata.is_dir() {
+            let mime = metadata.mime_type.clone().unwrap_or_else(|| mime::OCTET_STREAM.to_string());
+            let name = path.split('/').last().unwrap_or_default().to_string();
+            return Ok((name, mime, file_system.read_file(path)?.to_vec()));
+        }
+        let name = format!("{}.zip", path.split('/').last().unwrap_or("Archive"));
+        return Ok((name, "application/zip".to_string(), zip_paths(file_system, paths)?));
+    }
+    Ok(("Archive.zip".to_string(), "application/zip".to_string(), zip_paths(file_system, paths)?))
+}
+
+// Zips each path with everything under it. Entries are named relative to the directory holding
+// each path, so "/Desktop/notes" becomes "notes/..." in the archive.
+pub fn zip_paths(file_system:&mut FileSystem, paths:&[String]) -> Result<Vec<u8>,FsError> {
+    let mut writer = zip::ZipWriter::new();
+    for path in paths {
+        let base = parent_path(path).trim_end_matches('/').len() + 1;
+        let mut stack = vec![path.clone()];
+        while let Some(path) = stack.pop() {
+            let name = &path[base..];
+            let is_link = file_system.lstat(&path)?.is_sym();
+            // Links to folders aren't followed so a cycle can't make the archive endless,
+            // dangling links are left out.
+            let Ok(metadata) = file_system.get_file_metadata(&path).cloned() else { continue };
+            if metadata.is_dir() {
+                if is_link {
+                    continue;
+                }
+                writer.add_dir(name, metadata.modified, metadata.mode).map_err(|err| FsError::Io(err.to_string()))?;
+                let mut children = file_system.list_dir(&path)?;
+                // Popped in reverse, this keeps the archive in name order.
+                children.reverse();
+                stack.extend(children);
+            } else {
+                let content = file_system.read_file(&path)?;
+                writer.add_file(name, metadata.modified, metadata.mode, &content).map_err(|err| FsError::Io(err.to_string()))?;
+            }
+        }
+    }
+    Ok(writer.finish())
+}
+
+// Hands bytes to the browser as a file download through a Blob and an object url.
+pub fn download(name:&str, mime:&str, content:&[u8]) -> Result<(),JsValue> {
+    let url = object_url(mime, content)?;
+    let anchor = document().create_element("a")?.unchecked_into::<web_sys::HtmlAnchorElement>();
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+    gloo::timers::callback::Timeout::new(REVOKE_URL_AFTER_MS, move || {
+        _ = web_sys::Url::revoke_object_url(&url);
+    }).forget();
+    Ok(())
+}
+
+// A url for bytes held in memory, which has to be revoked once it's no longer used.
+pub fn object_url(mime:&str, content:&[u8]) -> Result<String,JsValue> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(content));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    web_sys::Url::create_object_url_with_blob(&blob)
+}
@@ -1,11 +1,13 @@
 use super::*;
 use std::collections::{HashSet,BTreeSet};
 
-#[derive(Debug,Clone,PartialEq)]
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
 pub struct SystemRuntime{
     pub running_app_paths:HashSet<String>,
     file_system:FileSystem,
     task_bar_paths:Vec<String>,
+    settings:settings::Settings,
+    windows:windows::WindowManager,
 }
 
 impl SystemRuntime{
@@ -14,6 +16,8 @@ impl SystemRuntime{
             running_app_paths:HashSet::new(),
             task_bar_paths,
             file_system,
+            settings:settings::Settings::default(),
+            windows:windows::WindowManager::default(),
         }
     }
     pub fn swap_taskbar(&mut self, swappee_path:String,swapped_path:String) {
@@ -26,34 +30,171 @@ impl SystemRuntime{
     pub fn task_bar_paths(&self) -> Vec<String> {
         self.task_bar_paths.clone()
     }
+    // Aliases run the app they point at, dangling ones do nothing. An app that's already running
+    // has its last used window brought to the front instead.
     pub fn run_app(&mut self, path:&str) {
-        if let Some(file) = self.file_system.tree.get_mut(path) {
-             // fix this
-                    //file.metadata.accessed=
-            self.running_app_paths.insert(String::from(path));
+        let Ok(app_path) = self.file_system.open(path) else { return };
+        match self.windows.app_windows(&app_path).next().map(|window| window.id) {
+            Some(id) => self.windows.focus(id),
+            None => {
+                let title = app_path.rsplit('/').next().unwrap_or_default().to_string();
+                self.windows.open(&app_path, title);
+            },
         }
-  
+        self.running_app_paths.insert(app_path);
     }
+    // An app whose file is gone, or a link that doesn't lead anywhere, gets the generic icon.
     pub fn app_img_src(&self,path:&str) -> String {
-        self.file_system.get_file_metadata(path).unwrap().img_src.clone()
+        self.file_system.get_file_metadata(path)
+            .map_or_else(|_| mime::icon(mime::OCTET_STREAM).to_string(), |metadata| metadata.img_src.clone())
     }
     pub fn close_app(&mut self, path:String) {
+        self.windows.close_app(&path);
         self.running_app_paths.remove(&path);
     }
+    // Closing the last window of an app quits it.
+    pub fn close_window(&mut self, id:u64) {
+        let Some(window) = self.windows.close(id) else { return };
+        if self.windows.app_windows(&window.app).next().is_none() {
+            self.running_app_paths.remove(&window.app);
+        }
+    }
+    // Puts back windows saved earlier, the apps they belong to run again.
+    pub fn restore_windows(&mut self, windows:windows::WindowManager) {
+        self.running_app_paths = windows.windows().iter().map(|window| window.app.clone()).collect();
+        self.windows = windows;
+    }
+    pub fn quit_all_apps(&mut self) {
+        self.windows.clear();
+        self.running_app_paths.clear();
+    }
+    // The wallpaper of a workspace: its own, or the one chosen in System Preferences.
+    pub fn workspace_wallpaper(&self, id:u64) -> &settings::Wallpaper {
+        self.windows.workspaces().iter()
+            .find(|workspace| workspace.id == id)
+            .and_then(|workspace| workspace.wallpaper.as_ref())
+            .unwrap_or(&self.settings.appearance.wallpaper)
+    }
+    // The wallpaper showing.
+    pub fn wallpaper(&self) -> &settings::Wallpaper {
+        self.workspace_wallpaper(self.windows.current_workspace().id)
+    }
+    pub fn windows(&self) -> &windows::WindowManager {
+        &self.windows
+    }
+    pub fn windows_mut(&mut self) -> &mut windows::WindowManager {
+        &mut self.windows
+    }
+    pub fn search(&self, query:&search::SearchQuery) -> Vec<search::SearchHit> {
+        self.file_system.search(query)
+    }
+    pub fn settings(&self) -> &settings::Settings {
+        &self.settings
+    }
+    pub fn settings_mut(&mut self) -> &mut settings::Settings {
+        &mut self.settings
+    }
+    pub fn file_system(&self) -> &FileSystem {
+        &self.file_system
+    }
+    pub fn file_system_mut(&mut self) -> &mut FileSystem {
+        &mut self.file_system
+    }
+    pub fn list_dir(&self, path:&str) -> Vec<String> {
+        self.file_system.list_dir(path).unwrap_or_default()
+    }
+    pub fn dir_entries(&self, path:&str) -> Vec<DirEntry> {
+        self.file_system.dir_entries(path).unwrap_or_default()
+    }
+    pub fn chmod(&mut self, path:&str, mode:u16) -> Result<(),FsError> {
+        self.file_system.chmod(path,mode)
+    }
+    pub fn chown(&mut self, path:&str, owner:u32, group:u32) -> Result<(),FsError> {
+        self.file_system.chown(path,owner,group)
+    }
+    // Remote sources are mounted asynchronously, see vfs::mount_remote.
+    pub fn mount(&mut self, dir:&str, source:vfs::MountSource) -> Result<(),FsError> {
+        self.file_system.mount_source(dir,source)
+    }
+    pub fn unmount(&mut self, dir:&str) -> Result<(),FsError> {
+        self.file_system.unmount(dir)
+    }
 
 }
 
 use std::collections::BTreeMap;
-#[derive(Debug,PartialEq,Clone)]
+use std::borrow::Cow;
+use std::rc::Rc;
+use clock::SharedClock;
+use permissions::*;
+use vfs::{Backend,MountSource};
+
+// Virtual folder listing files by last access, it has no node in the tree.
+pub const RECENTS_PATH: &'static str = "/Recents";
+const RECENTS_LEN: usize = 20;
+// Holds the aliases shown on the desktop.
+pub const DESKTOP_PATH: &'static str = "/Desktop";
+// Links followed while resolving one path before giving up, like ELOOP on linux.
+pub const MAX_LINK_DEPTH: usize = 40;
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum FsError{
+    NotFound(String),
+    NotADirectory(String),
+    AlreadyExists(String),
+    PermissionDenied(String),
+    // Inside a mounted archive or another read-only mount.
+    ReadOnly(String),
+    // An archive that can't be parsed.
+    Malformed(String),
+    // Resolving the path followed more than MAX_LINK_DEPTH links, usually a cycle.
+    LinkDepthExceeded(String),
+    // Removing a directory that still has children in a mount.
+    NotEmpty(String),
+    // A file in a remote mount whose content hasn't been fetched yet, see vfs::fetch.
+    NotLoaded(String),
+    // A backend failed to reach its storage.
+    Io(String),
+}
+
+impl std::fmt::Display for FsError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(path) => write!(f,"{path}: No such file or directory"),
+            Self::NotADirectory(path) => write!(f,"{path}: Not a directory"),
+            Self::AlreadyExists(path) => write!(f,"{path}: File exists"),
+            Self::PermissionDenied(path) => write!(f,"{path}: Permission denied"),
+            Self::ReadOnly(path) => write!(f,"{path}: Read-only file system"),
+            Self::Malformed(reason) => write!(f,"{reason}"),
+            Self::LinkDepthExceeded(path) => write!(f,"{path}: Too many levels of symbolic links"),
+            Self::NotEmpty(path) => write!(f,"{path}: Directory not empty"),
+            Self::NotLoaded(path) => write!(f,"{path}: Not loaded yet"),
+            Self::Io(reason) => write!(f,"{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for FsError{}
+
+// Timestamps are milliseconds since the unix epoch, see clock::Clock.
+#[derive(Debug,PartialEq,Clone,Serialize,Deserialize)]
 pub struct Metadata{
     pub accessed:i64,
     pub created:i64,
     pub modified:i64,
     pub file_type:FileType,
     pub img_src:String,
+    // Only set for FileType::SymbolicLink, relative targets are relative to the link's directory.
+    pub link_target:Option<String>,
+    pub owner:u32,
+    pub group:u32,
+    // rwx bits for owner, group and others, e.g. 0o755.
+    pub mode:u16,
+    // Known for files that came from outside, e.g. imported from the host machine.
+    pub mime_type:Option<String>,
 }
 
-#[derive(Debug,PartialEq,Clone,Copy)]
+#[derive(Debug,PartialEq,Clone,Copy,Serialize,Deserialize)]
 pub enum FileType{
     Directory,
     File,
@@ -61,6 +202,28 @@ pub enum FileType{
 }
 
 impl Metadata{
+    pub fn new(file_type:FileType, img_src:&str) -> Self {
+        Self{
+            accessed:0,
+            created:0,
+            modified:0,
+            file_type,
+            img_src:img_src.to_string(),
+            link_target:None,
+            owner:0,
+            group:0,
+            mode:match file_type {
+                FileType::Directory => DEFAULT_DIR_MODE,
+                FileType::File => DEFAULT_FILE_MODE,
+                FileType::SymbolicLink => SYMLINK_MODE,
+            },
+            mime_type:None,
+        }
+    }
+    pub fn with_mode(mut self, mode:u16) -> Self {
+        self.mode = mode;
+        self
+    }
     pub fn is_dir(&self) -> bool {
         &self.file_type == &FileType::Directory
     }
@@ -71,40 +234,677 @@ impl Metadata{
         &self.file_type == &FileType::SymbolicLink
     }
 }
+
+// What a Finder or the desktop needs to draw one item of a directory.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct DirEntry{
+    pub path:String,
+    pub name:String,
+    // The link's target icon for links that resolve.
+    pub img_src:String,
+    pub file_type:FileType,
+    // A link whose target doesn't exist.
+    pub dangling:bool,
+}
+
 // Define the file system node
-#[derive(Debug,Clone,PartialEq)]
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
 struct FileSystemNode {
     pub name: String,
     pub metadata: Metadata, // This struct provides metadata information about a file.
+    pub content: Vec<u8>,
 }
 
 // Define the filesystem as a B-tree map
 #[derive(Debug,PartialEq,Clone)]
 pub struct FileSystem {
     tree: BTreeMap<String, FileSystemNode>,
+    // Kept in sync by every method that mutates the tree.
+    index: search::SearchIndex,
+    clock: SharedClock,
+    // Every operation is checked against these, see set_user.
+    user: Credentials,
+    // Mount point -> the backend serving everything under it. Directories are mounted over,
+    // archive files are mounted at their own path and browse as read-only directories.
+    mounts: BTreeMap<String, Box<dyn Backend>>,
+    // Mounts read back from a snapshot that couldn't be mounted again synchronously,
+    // see take_detached_mounts.
+    detached_mounts: Vec<(String, MountSource)>,
+    // Changes made to the tree since the last take_journal, sent to the user's other tabs,
+    // see events. Changes inside mounts are left out, they are synced by their backend.
+    journal: Vec<events::FsChange>,
+}
+
+// What a FileSystem serializes to. The search index is rebuilt and the clock is the system
+// clock after deserializing, mounts are stored by source and mounted again. Memory mounts
+// come back empty.
+#[derive(Serialize)]
+struct FileSystemRef<'a> {
+    tree: &'a BTreeMap<String, FileSystemNode>,
+    user: Credentials,
+    mounts: Vec<(String, MountSource)>,
+}
+
+#[derive(Deserialize)]
+struct FileSystemData {
+    tree: BTreeMap<String, FileSystemNode>,
+    user: Credentials,
+    #[serde(default)]
+    mounts: Vec<(String, MountSource)>,
+}
+
+impl Serialize for FileSystem {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FileSystemRef {
+            tree: &self.tree,
+            user: self.user,
+            mounts: self.mounts().into_iter().chain(self.detached_mounts.iter().cloned()).collect(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileSystem {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = FileSystemData::deserialize(deserializer)?;
+        if !data.tree.get("/").map_or(false, |root| root.metadata.is_dir()) {
+            return Err(serde::de::Error::custom("file system has no root directory"));
+        }
+        let mut file_system = FileSystem::new();
+        for (path, node) in &data.tree {
+            if path != "/" {
+                file_system.index.insert(path, &node.name, &node.metadata, &node.content);
+            }
+        }
+        file_system.tree = data.tree;
+        // Remounting is done as root, the mounts were allowed when they were made.
+        for (dir, source) in data.mounts {
+            let mounted = match &source {
+                MountSource::Archive => file_system.mount_archive(&dir),
+                MountSource::Memory => file_system.mount(&dir, Box::new(vfs::MemoryBackend::new())),
+                // localStorage only exists in the browser, elsewhere load fails and these are
+                // left detached.
+                MountSource::LocalStorage{prefix} => vfs::LocalStorageBackend::load(prefix.clone())
+                    .and_then(|backend| file_system.mount(&dir, Box::new(backend))),
+                _ => Err(FsError::NotLoaded(dir.clone())),
+            };
+            if mounted.is_err() {
+                file_system.detached_mounts.push((dir, source));
+            }
+        }
+        file_system.user = data.user;
+        Ok(file_system)
+    }
 }
 
 impl FileSystem {
     // Creates a new, empty FileSystem
     pub fn new() -> FileSystem {
+        Self::with_clock(SharedClock::default())
+    }
+
+    // Creates a new, empty FileSystem that stamps times from the given clock.
+    // It starts out acting as root so the caller can lay out system files.
+    pub fn with_clock(clock: SharedClock) -> FileSystem {
+        let mut root = Metadata::new(FileType::Directory, "/hard-disk.png");
+        root.created = clock.now();
+        root.modified = root.created;
+        root.accessed = root.created;
         FileSystem {
-            tree: BTreeMap::new(),
+            // The root directory isn't indexed, it has no name to search for.
+            tree: BTreeMap::from([("/".to_string(), FileSystemNode {
+                name: String::new(),
+                metadata: root,
+                content: Vec::new(),
+            })]),
+            index: search::SearchIndex::new(),
+            clock,
+            user: Credentials::ROOT,
+            mounts: BTreeMap::new(),
+            detached_mounts: Vec::new(),
+            journal: Vec::new(),
         }
     }
 
-    // Adds a file to the filesystem
-    pub fn add_file(&mut self, path: String, metadata: Metadata) {
+    // Changes who subsequent operations are performed as
+    pub fn set_user(&mut self, user: Credentials) {
+        self.user = user;
+    }
+
+    pub fn user(&self) -> Credentials {
+        self.user
+    }
+
+    // Adds a file to the filesystem, owned by the current user and stamped with the current time.
+    // Needs write access to the parent directory.
+    pub fn add_file(&mut self, path: String, mut metadata: Metadata) -> Result<(), FsError> {
+        let path = normalize_path(&path);
+        if self.node_metadata(&path).is_some() {
+            return Err(FsError::AlreadyExists(path));
+        }
+        self.check_writable_fs(&path)?;
+        self.check_parent_writable(&path)?;
+        let now = self.clock.now();
+        metadata.created = now;
+        metadata.modified = now;
+        metadata.accessed = now;
+        metadata.owner = self.user.uid;
+        metadata.group = self.user.gid;
+        if let Some((mount_point, inner)) = self.mount_inner(&path) {
+            return self.mounts.get_mut(&mount_point).unwrap().create(&inner, metadata);
+        }
         let file_name = path.split('/').last().unwrap_or_default().to_string();
         let file_node = FileSystemNode {
             name: file_name,
             metadata,
+            content: Vec::new(),
         };
+        self.index.insert(&path, &file_node.name, &file_node.metadata, &file_node.content);
+        self.journal.push(events::FsChange::Created{path: path.clone(), metadata: file_node.metadata.clone()});
         self.tree.insert(path, file_node);
+        Ok(())
+    }
+
+    // add_file then write_file
+    pub fn create_file(&mut self, path: String, metadata: Metadata, content: Vec<u8>) -> Result<(), FsError> {
+        self.add_file(path.clone(), metadata)?;
+        self.write_file(&path, content)
+    }
+
+    // A path for name inside dir that isn't taken yet, "notes.txt" becomes "notes 2.txt" and so on
+    pub fn unique_path(&self, dir: &str, name: &str) -> String {
+        let dir = normalize_path(dir);
+        let dir = dir.trim_end_matches('/');
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+            _ => (name, String::new()),
+        };
+        let mut path = format!("{dir}/{name}");
+        let mut n = 2;
+        while self.node_metadata(&path).is_some() {
+            path = format!("{dir}/{stem} {n}{extension}");
+            n += 1;
+        }
+        path
+    }
+
+    // Adds an empty directory
+    pub fn create_dir(&mut self, path: String) -> Result<(), FsError> {
+        self.add_file(path, Metadata::new(FileType::Directory, "/folder.png"))
+    }
+
+    // Creates a link at link_path pointing to target. The target doesn't have to exist.
+    pub fn symlink(&mut self, target: &str, link_path: String) -> Result<(), FsError> {
+        // The icon is only shown when the link is dangling, see dir_entries.
+        let mut metadata = Metadata::new(FileType::SymbolicLink, "/text.png");
+        metadata.link_target = Some(target.to_string());
+        self.add_file(link_path, metadata)
+    }
+
+    // Sets the mode bits, only the owner or root may
+    pub fn chmod(&mut self, path: &str, mode: u16) -> Result<(), FsError> {
+        let path = self.resolve(path)?;
+        self.check_traverse(&path)?;
+        self.check_writable_fs(&path)?;
+        let user = self.user;
+        let metadata = self.node_metadata_mut(&path).ok_or_else(|| FsError::NotFound(path.clone()))?;
+        if !user.is_root() && user.uid != metadata.owner {
+            return Err(FsError::PermissionDenied(path));
+        }
+        metadata.mode = mode & 0o777;
+        self.metadata_changed(&path)
+    }
+
+    // Gives a node to another user and group, only root may
+    pub fn chown(&mut self, path: &str, owner: u32, group: u32) -> Result<(), FsError> {
+        let path = self.resolve(path)?;
+        if !self.user.is_root() {
+            return Err(FsError::PermissionDenied(path));
+        }
+        self.check_writable_fs(&path)?;
+        let metadata = self.node_metadata_mut(&path).ok_or_else(|| FsError::NotFound(path.clone()))?;
+        metadata.owner = owner;
+        metadata.group = group;
+        self.metadata_changed(&path)
+    }
+
+    // Checks access to the node at an already resolved path, including search (x) access
+    // to every directory above it.
+    fn check_access(&self, path: &str, access: Access) -> Result<(), FsError> {
+        self.check_traverse(path)?;
+        let metadata = self.node_metadata(path).ok_or_else(|| FsError::NotFound(path.to_string()))?;
+        if allowed(&self.user, metadata, access) {
+            Ok(())
+        } else {
+            Err(FsError::PermissionDenied(path.to_string()))
+        }
+    }
+
+    fn check_traverse(&self, path: &str) -> Result<(), FsError> {
+        let mut dir = parent_path(path);
+        loop {
+            // A mounted archive is a file, entering it only needs read access.
+            let is_archive = self.mounts.get(dir).map_or(false, |backend| backend.source() == MountSource::Archive);
+            let access = if is_archive { Access::Read } else { Access::Execute };
+            if let Some(metadata) = self.node_metadata(dir) {
+                if !allowed(&self.user, metadata, access) {
+                    return Err(FsError::PermissionDenied(dir.to_string()));
+                }
+            }
+            if dir == "/" {
+                return Ok(());
+            }
+            dir = parent_path(dir);
+        }
     }
 
-    // Retrieves a file's metadata from the filesystem
-    pub fn get_file_metadata(&self, path: &str) -> Option<&Metadata> {
-        self.tree.get(path).map(|node| &node.metadata)
+    // Creating or removing an entry is a write to the directory that holds it.
+    fn check_parent_writable(&self, path: &str) -> Result<(), FsError> {
+        let parent = self.resolve(parent_path(path))?;
+        match self.node_metadata(&parent) {
+            Some(metadata) if !metadata.is_dir() => Err(FsError::NotADirectory(parent)),
+            Some(_) => self.check_access(&parent, Access::Write),
+            None => Err(FsError::NotFound(parent)),
+        }
+    }
+
+    // Nothing inside a read-only mount, like an archive, can change, not even as root.
+    fn check_writable_fs(&self, path: &str) -> Result<(), FsError> {
+        match self.mount_at(path) {
+            Some((_, backend, inner)) if inner != "/" && backend.read_only() => Err(FsError::ReadOnly(path.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    // Metadata of a node in the tree or in a mount, at an already resolved path.
+    // A mount point itself keeps the metadata of the node it is mounted over.
+    fn node_metadata(&self, path: &str) -> Option<&Metadata> {
+        if let Some(node) = self.tree.get(path) {
+            return Some(&node.metadata);
+        }
+        let (_, backend, inner) = self.mount_at(path)?;
+        backend.metadata(&inner)
+    }
+
+    fn node_metadata_mut(&mut self, path: &str) -> Option<&mut Metadata> {
+        if self.tree.contains_key(path) {
+            return self.tree.get_mut(path).map(|node| &mut node.metadata);
+        }
+        let (mount_point, inner) = self.mount_inner(path)?;
+        self.mounts.get_mut(&mount_point)?.metadata_mut(&inner)
+    }
+
+    // Lets the backend a path is in store a change made through node_metadata_mut.
+    fn metadata_changed(&mut self, path: &str) -> Result<(), FsError> {
+        if self.tree.contains_key(path) {
+            return Ok(());
+        }
+        match self.mount_inner(path) {
+            Some((mount_point, inner)) => self.mounts.get_mut(&mount_point).unwrap().metadata_changed(&inner),
+            None => Ok(()),
+        }
+    }
+
+    // The mount a resolved path is in (or is), its mount point and the path inside it
+    fn mount_at(&self, path: &str) -> Option<(&str, &dyn Backend, String)> {
+        let mut mount_point = path;
+        loop {
+            if let Some((mount_path, backend)) = self.mounts.get_key_value(mount_point) {
+                let inner = normalize_path(&path[mount_point.len()..]);
+                return Some((mount_path.as_str(), backend.as_ref(), inner));
+            }
+            if mount_point == "/" {
+                return None;
+            }
+            mount_point = parent_path(mount_point);
+        }
+    }
+
+    // Like mount_at but only for paths strictly inside a mount, with owned strings so the
+    // backend can be borrowed mutably afterwards.
+    fn mount_inner(&self, path: &str) -> Option<(String, String)> {
+        match self.mount_at(path) {
+            Some((mount_point, _, inner)) if inner != "/" => Some((mount_point.to_string(), inner)),
+            _ => None,
+        }
+    }
+
+    // Serves everything under dir from backend. dir has to be a directory the user can
+    // write to and nothing may already be mounted there, what was under it is hidden until
+    // it's unmounted. Paths cross into the mount like into any other directory.
+    pub fn mount(&mut self, dir: &str, backend: Box<dyn Backend>) -> Result<(), FsError> {
+        let dir = self.resolve(dir)?;
+        match self.node_metadata(&dir) {
+            Some(metadata) if !metadata.is_dir() => return Err(FsError::NotADirectory(dir)),
+            Some(_) => self.check_access(&dir, Access::Write)?,
+            None => return Err(FsError::NotFound(dir)),
+        }
+        if dir == "/" || self.mounts.contains_key(&dir) {
+            return Err(FsError::AlreadyExists(dir));
+        }
+        self.mounts.insert(dir, backend);
+        Ok(())
+    }
+
+    // Mounts a source that doesn't need the network, remote ones go through vfs::mount_remote.
+    pub fn mount_source(&mut self, dir: &str, source: MountSource) -> Result<(), FsError> {
+        match source {
+            MountSource::Memory => self.mount(dir, Box::new(vfs::MemoryBackend::new())),
+            MountSource::LocalStorage{prefix} => self.mount(dir, Box::new(vfs::LocalStorageBackend::load(prefix)?)),
+            MountSource::Archive => self.mount_archive(dir),
+            source => Err(FsError::Io(format!("{source} has to be mounted with vfs::mount_remote"))),
+        }
+    }
+
+    // Drops the backend mounted at dir, anything not yet flushed to a remote store is lost.
+    pub fn unmount(&mut self, dir: &str) -> Result<(), FsError> {
+        let dir = self.resolve(dir)?;
+        let metadata = self.node_metadata(&dir).ok_or_else(|| FsError::NotFound(dir.clone()))?;
+        if !self.user.is_root() && self.user.uid != metadata.owner {
+            return Err(FsError::PermissionDenied(dir));
+        }
+        // Mounts inside this one would be left without a way to reach them.
+        let nested = format!("{}/", dir.trim_end_matches('/'));
+        self.mounts.retain(|mount_point, _| !mount_point.starts_with(&nested));
+        self.mounts.remove(&dir).map(|_| ()).ok_or(FsError::NotFound(dir))
+    }
+
+    // Mounts from a snapshot still to be made, remote ones with vfs::mount_remote.
+    pub fn take_detached_mounts(&mut self) -> Vec<(String, MountSource)> {
+        std::mem::take(&mut self.detached_mounts)
+    }
+
+    // Every mount point and what is mounted there.
+    pub fn mounts(&self) -> Vec<(String, MountSource)> {
+        self.mounts.iter()
+            .map(|(mount_point, backend)| (mount_point.clone(), backend.source()))
+            .collect()
+    }
+
+    // Files at or under paths in remote mounts whose content hasn't been fetched, as
+    // (mount point, store, path inside the mount).
+    pub fn unfetched(&self, paths: &[String]) -> Vec<(String, Rc<dyn remote_store::RemoteStore>, String)> {
+        let mut wanted = Vec::new();
+        for path in paths {
+            let Ok(path) = self.resolve(path) else { continue };
+            let under = format!("{}/", path.trim_end_matches('/'));
+            let mut roots = self.mount_inner(&path).into_iter().collect::<Vec<_>>();
+            roots.extend(self.mounts.keys()
+                .filter(|mount_point| **mount_point == path || mount_point.starts_with(&under))
+                .map(|mount_point| (mount_point.clone(), "/".to_string())));
+            for (mount_point, inner) in roots {
+                let backend = &self.mounts[&mount_point];
+                let Some(store) = backend.remote_store() else { continue };
+                let mut stack = vec![inner];
+                while let Some(inner) = stack.pop() {
+                    match backend.list(&inner) {
+                        Ok(children) => stack.extend(children.into_iter()
+                            .map(|name| format!("{}/{name}", inner.trim_end_matches('/')))),
+                        Err(_) if !backend.is_fetched(&inner) => wanted.push((mount_point.clone(), store.clone(), inner)),
+                        Err(_) => {},
+                    }
+                }
+            }
+        }
+        wanted
+    }
+
+    // Stores content fetched for a file in a remote mount.
+    pub fn fill_remote(&mut self, mount_point: &str, inner: &str, content: Vec<u8>) {
+        if let Some(backend) = self.mounts.get_mut(mount_point) {
+            backend.fill(inner, content);
+        }
+    }
+
+    // Changes to remote mounts that still have to be sent to their stores, see vfs::flush.
+    pub fn take_pending(&mut self) -> Vec<(Rc<dyn remote_store::RemoteStore>, Vec<vfs::Change>)> {
+        self.mounts.values_mut()
+            .filter_map(|backend| backend.take_pending())
+            .collect()
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.mounts.values().any(|backend| backend.has_pending())
+    }
+
+    // Opens a zip, tar or tar.gz file as a read-only directory at its own path
+    pub fn mount_archive(&mut self, path: &str) -> Result<(), FsError> {
+        let path = self.resolve(path)?;
+        let kind = archive::ArchiveKind::from_name(&path)
+            .ok_or_else(|| FsError::Malformed(format!("{path}: not an archive")))?;
+        self.check_access(&path, Access::Read)?;
+        let node = self.tree.get(&path).ok_or_else(|| FsError::NotFound(path.clone()))?;
+        let mounted = archive::MountedArchive::mount(kind, &node.content, &node.metadata)?;
+        self.mounts.insert(path, Box::new(mounted));
+        Ok(())
+    }
+
+    pub fn is_mounted(&self, path: &str) -> bool {
+        self.resolve(path).map_or(false, |path| self.mounts.contains_key(&path))
+    }
+
+    // Copies src, and everything under it when it's a directory, into dest_dir under a name
+    // that isn't taken yet. Links are copied as links. Returns the path of the copy.
+    pub fn copy(&mut self, src: &str, dest_dir: &str) -> Result<String, FsError> {
+        let name = src.trim_end_matches('/').split('/').last().unwrap_or_default().to_string();
+        let dest = self.unique_path(dest_dir, &name);
+        let mut stack = vec![(src.to_string(), dest.clone())];
+        while let Some((src, dest)) = stack.pop() {
+            let mut metadata = self.lstat(&src)?.clone();
+            // Copies belong to whoever made them, and must stay writable by them so the copy
+            // of a read-only directory (like one in an archive) can be filled in.
+            metadata.mode |= 0o200;
+            if metadata.is_sym() {
+                self.symlink(metadata.link_target.as_deref().unwrap_or_default(), dest)?;
+            } else if metadata.is_dir() {
+                self.add_file(dest.clone(), metadata)?;
+                for child in self.list_dir(&src)? {
+                    let child_dest = format!("{dest}/{}", child.split('/').last().unwrap_or_default());
+                    stack.push((child, child_dest));
+                }
+            } else {
+                let content = self.read_file(&src)?.into_owned();
+                self.create_file(dest, metadata, content)?;
+            }
+        }
+        Ok(dest)
+    }
+
+    // The target a link points at, without following it
+    pub fn read_link(&self, path: &str) -> Result<&str, FsError> {
+        self.lstat(path)?.link_target.as_deref()
+            .ok_or_else(|| FsError::NotFound(path.to_string()))
+    }
+
+    // Follows every link in path, returning the path of the node it names.
+    // The returned path may not exist if the last link is dangling.
+    pub fn resolve(&self, path: &str) -> Result<String, FsError> {
+        self.resolve_path(path, true)
+    }
+
+    // Like resolve but a link in the last component is left alone, for lstat-like operations.
+    fn resolve_parent(&self, path: &str) -> Result<String, FsError> {
+        self.resolve_path(path, false)
+    }
+
+    fn resolve_path(&self, path: &str, follow_last: bool) -> Result<String, FsError> {
+        let mut path = normalize_path(path);
+        for _ in 0..=MAX_LINK_DEPTH {
+            let Some((link_path, rest)) = self.first_link(&path, follow_last) else {
+                return Ok(path);
+            };
+            let target = self.node_metadata(&link_path)
+                .and_then(|metadata| metadata.link_target.clone())
+                .unwrap_or_default();
+            let target = if target.starts_with('/') {
+                target
+            } else {
+                format!("{}/{}", parent_path(&link_path), target)
+            };
+            path = normalize_path(&format!("{target}/{rest}"));
+        }
+        Err(FsError::LinkDepthExceeded(path))
+    }
+
+    // The first ancestor of path (or path itself when follow_last) that is a link,
+    // and the remainder of path after it.
+    fn first_link(&self, path: &str, follow_last: bool) -> Option<(String, String)> {
+        let mut prefix = String::new();
+        let components = path.split('/').filter(|c| !c.is_empty()).collect::<Vec<&str>>();
+        for (i, component) in components.iter().enumerate() {
+            prefix.push('/');
+            prefix.push_str(component);
+            let is_last = i == components.len() - 1;
+            if is_last && !follow_last {
+                return None;
+            }
+            if self.node_metadata(&prefix).map_or(false, |metadata| metadata.is_sym()) {
+                return Some((prefix, components[i + 1..].join("/")));
+            }
+        }
+        None
+    }
+
+    // Replaces a file's content, following links
+    pub fn write_file(&mut self, path: &str, content: Vec<u8>) -> Result<(), FsError> {
+        let path = self.resolve(path)?;
+        self.check_writable_fs(&path)?;
+        self.check_access(&path, Access::Write)?;
+        let now = self.clock.now();
+        if let Some((mount_point, inner)) = self.mount_inner(&path) {
+            return self.mounts.get_mut(&mount_point).unwrap().write(&inner, content, now);
+        }
+        // An archive's listing would no longer match the new content.
+        if self.mounts.get(&path).map_or(false, |backend| backend.source() == MountSource::Archive) {
+            self.mounts.remove(&path);
+        }
+        let node = self.tree.get_mut(&path).ok_or_else(|| FsError::NotFound(path.clone()))?;
+        node.content = content;
+        node.metadata.modified = now;
+        node.metadata.accessed = now;
+        self.index.insert(&path, &node.name, &node.metadata, &node.content);
+        self.journal.push(events::FsChange::Written{path, content: node.content.clone()});
+        Ok(())
+    }
+
+    // Retrieves a file's content from the filesystem, following links, and updates its accessed time.
+    // Files in mounts come from their backend, see stamp_accessed for read-only ones.
+    pub fn read_file(&mut self, path: &str) -> Result<Cow<'_, [u8]>, FsError> {
+        let path = self.resolve(path)?;
+        self.check_access(&path, Access::Read)?;
+        let now = self.clock.now();
+        if let Some((mount_point, inner)) = self.mount_inner(&path) {
+            self.stamp_accessed(&mount_point, &inner, now);
+            return self.mounts[&mount_point].read(&inner);
+        }
+        let node = self.tree.get_mut(&path).ok_or(FsError::NotFound(path))?;
+        node.metadata.accessed = now;
+        Ok(Cow::Borrowed(node.content.as_slice()))
+    }
+
+    // Resolves path and updates the accessed time of what it names, returning the resolved path
+    pub fn open(&mut self, path: &str) -> Result<String, FsError> {
+        let path = self.resolve(path)?;
+        self.check_access(&path, Access::Read)?;
+        let now = self.clock.now();
+        match self.mount_inner(&path) {
+            Some((mount_point, inner)) => self.stamp_accessed(&mount_point, &inner, now),
+            None => if let Some(node) = self.tree.get_mut(&path) {
+                node.metadata.accessed = now;
+            },
+        }
+        Ok(path)
+    }
+
+    // Archive entries have no accessed time of their own, reading one stamps the archive.
+    fn stamp_accessed(&mut self, mount_point: &str, inner: &str, now: i64) {
+        let metadata = match self.mounts.get_mut(mount_point).and_then(|backend| backend.metadata_mut(inner)) {
+            Some(metadata) => metadata,
+            None => match self.tree.get_mut(mount_point) {
+                Some(node) => &mut node.metadata,
+                None => return,
+            },
+        };
+        metadata.accessed = now;
+    }
+
+    // Paths of the direct children of a directory, "/" lists the top level.
+    // Listing through a link gives paths under the link, not under its target.
+    pub fn list_dir(&self, path: &str) -> Result<Vec<String>, FsError> {
+        if path == RECENTS_PATH {
+            return Ok(self.recents());
+        }
+        let resolved = self.resolve(path)?;
+        let shown = if path.ends_with('/') { path.to_string() } else { format!("{path}/") };
+        if let Some((_, backend, inner)) = self.mount_at(&resolved) {
+            self.check_access(&resolved, Access::Read)?;
+            return Ok(backend.list(&inner)?.into_iter()
+                .map(|name| format!("{shown}{name}"))
+                .collect());
+        }
+        match self.tree.get(&resolved) {
+            Some(node) if !node.metadata.is_dir() => return Err(FsError::NotADirectory(path.to_string())),
+            Some(_) => self.check_access(&resolved, Access::Read)?,
+            None => return Err(FsError::NotFound(path.to_string())),
+        }
+        let prefix = if resolved.ends_with('/') { resolved.clone() } else { format!("{resolved}/") };
+        Ok(self.tree.range(prefix.clone()..)
+            .take_while(|(child,_)| child.starts_with(&prefix))
+            .filter(|(child,_)| child.len() > prefix.len() && !child[prefix.len()..].contains('/'))
+            .map(|(child,_)| format!("{shown}{}", &child[prefix.len()..]))
+            .collect())
+    }
+
+    // list_dir plus what is needed to draw each child
+    pub fn dir_entries(&self, path: &str) -> Result<Vec<DirEntry>, FsError> {
+        Ok(self.list_dir(path)?.into_iter()
+            .filter_map(|child| {
+                let link = self.lstat(&child).ok()?;
+                let target = self.get_file_metadata(&child).ok();
+                Some(DirEntry{
+                    name: child.split('/').last().unwrap_or_default().to_string(),
+                    img_src: target.unwrap_or(link).img_src.clone(),
+                    file_type: link.file_type,
+                    dangling: link.is_sym() && target.is_none(),
+                    path: child,
+                })
+            })
+            .collect())
+    }
+
+    // Files the current user can read, ordered by most recently accessed first
+    pub fn recents(&self) -> Vec<String> {
+        let mut files = self.tree.iter()
+            .filter(|(path,node)| node.metadata.is_file() && self.check_access(path, Access::Read).is_ok())
+            .collect::<Vec<_>>();
+        files.sort_by(|(_,a),(_,b)| b.metadata.accessed.cmp(&a.metadata.accessed));
+        files.into_iter()
+            .take(RECENTS_LEN)
+            .map(|(path,_)| path.clone())
+            .collect()
+    }
+
+    // Searches names, paths and text contents, see search::SearchQuery
+    // Hits in directories the current user can't read are left out.
+    pub fn search(&self, query: &search::SearchQuery) -> Vec<search::SearchHit> {
+        let mut hits = self.index.search(query);
+        hits.retain(|hit| self.check_access(parent_path(&hit.path), Access::Read).is_ok());
+        hits
+    }
+
+    // Retrieves a file's metadata from the filesystem, following links
+    pub fn get_file_metadata(&self, path: &str) -> Result<&Metadata, FsError> {
+        let resolved = self.resolve(path)?;
+        self.check_traverse(&resolved)?;
+        self.node_metadata(&resolved)
+            .ok_or_else(|| FsError::NotFound(path.to_string()))
+    }
+
+    // Retrieves a file's metadata without following a link in the last component
+    pub fn lstat(&self, path: &str) -> Result<&Metadata, FsError> {
+        let resolved = self.resolve_parent(path)?;
+        self.check_traverse(&resolved)?;
+        self.node_metadata(&resolved)
+            .ok_or_else(|| FsError::NotFound(path.to_string()))
     }
 
     // Lists all the files in the filesystem
@@ -114,8 +914,507 @@ impl FileSystem {
         }
     }
 
-    // Removes a file from the filesystem
-    pub fn remove_file(&mut self, path: &str) {
-        self.tree.remove(path);
+    // Removes a file from the filesystem, removing a link rather than what it points at.
+    // Needs write access to the parent directory. Like rmdir, directories have to be emptied
+    // first so nothing under them is left behind. Removing a mount point unmounts it.
+    pub fn remove_file(&mut self, path: &str) -> Result<(), FsError> {
+        let path = self.resolve_parent(path)?;
+        self.check_writable_fs(&path)?;
+        if let Some((mount_point, inner)) = self.mount_inner(&path) {
+            self.check_parent_writable(&path)?;
+            return self.mounts.get_mut(&mount_point).unwrap().remove(&inner);
+        }
+        if path == "/" || !self.tree.contains_key(&path) {
+            return Err(FsError::NotFound(path));
+        }
+        self.check_parent_writable(&path)?;
+        let under = format!("{path}/");
+        let has_children = self.tree.range(under.clone()..).next().map_or(false, |(child, _)| child.starts_with(&under))
+            || self.mounts.keys().any(|mount_point| mount_point.starts_with(&under));
+        if has_children {
+            return Err(FsError::NotEmpty(path));
+        }
+        self.tree.remove(&path);
+        self.index.remove(&path);
+        self.mounts.remove(&path);
+        self.journal.push(events::FsChange::Removed{path});
+        Ok(())
+    }
+
+    // Moves a file, link or directory with everything under it to a new path, links aren't
+    // followed. Needs write access to both parent directories. Entries inside mounts can't be
+    // moved, mounts under a moved directory move with it.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), FsError> {
+        let from = self.resolve_parent(from)?;
+        let to = self.resolve_parent(to)?;
+        for path in [&from, &to] {
+            if self.mount_inner(path).is_some() {
+                return Err(FsError::ReadOnly(path.clone()));
+            }
+        }
+        if from == "/" || !self.tree.contains_key(&from) {
+            return Err(FsError::NotFound(from));
+        }
+        if self.node_metadata(&to).is_some() {
+            return Err(FsError::AlreadyExists(to));
+        }
+        if to.starts_with(&format!("{from}/")) {
+            return Err(FsError::Io(format!("{to}: Invalid argument")));
+        }
+        self.check_parent_writable(&from)?;
+        self.check_parent_writable(&to)?;
+        self.move_tree(&from, &to);
+        self.journal.push(events::FsChange::Renamed{from, to});
+        Ok(())
     }
-}
\ No newline at end of file
+
+    fn move_tree(&mut self, from: &str, to: &str) {
+        let under = format!("{from}/");
+        let moved = self.tree.range(from.to_string()..)
+            .take_while(|(path, _)| path.starts_with(from))
+            .filter(|(path, _)| *path == from || path.starts_with(&under))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        for path in moved {
+            let mut node = self.tree.remove(&path).unwrap();
+            self.index.remove(&path);
+            let new_path = format!("{to}{}", &path[from.len()..]);
+            if path == from {
+                node.name = to.split('/').last().unwrap_or_default().to_string();
+            }
+            self.index.insert(&new_path, &node.name, &node.metadata, &node.content);
+            self.tree.insert(new_path, node);
+        }
+        let mounts = self.mounts.keys()
+            .filter(|mount_point| *mount_point == from || mount_point.starts_with(&under))
+            .cloned()
+            .collect::<Vec<_>>();
+        for mount_point in mounts {
+            let backend = self.mounts.remove(&mount_point).unwrap();
+            self.mounts.insert(format!("{to}{}", &mount_point[from.len()..]), backend);
+        }
+    }
+
+    // Changes made since the last call, see events::provide_events.
+    pub fn take_journal(&mut self) -> Vec<events::FsChange> {
+        std::mem::take(&mut self.journal)
+    }
+
+    pub fn has_journal(&self) -> bool {
+        !self.journal.is_empty()
+    }
+
+    // Makes a change a tab published, as the current user and with the same checks as making it
+    // here. Returns the changes as journaled here, with this FileSystem's times and owners.
+    pub fn apply_checked(&mut self, change: events::FsChange) -> Result<Vec<events::FsChange>, FsError> {
+        let journal = std::mem::take(&mut self.journal);
+        let result = match change {
+            events::FsChange::Created{path, mut metadata} => {
+                metadata.mode &= 0o777;
+                self.add_file(path, metadata)
+            },
+            events::FsChange::Written{path, content} => self.write_file(&path, content),
+            events::FsChange::Removed{path} => self.remove_file(&path),
+            events::FsChange::Renamed{from, to} => self.rename(&from, &to),
+        };
+        let applied = std::mem::replace(&mut self.journal, journal);
+        result.map(|_| applied)
+    }
+
+    // Checks a file system a tab sent back against this one, which the server kept, as if the
+    // differences were made by this one's user. The tab's user is reset to it, mounts the user
+    // couldn't have made are dropped and any other change the user couldn't have made is
+    // refused.
+    pub fn vet(&self, client: &mut FileSystem) -> Result<(), FsError> {
+        let user = self.user;
+        client.user = user;
+        for (path, node) in &client.tree {
+            let Some(old_node) = self.tree.get(path) else {
+                if !user.is_root() && node.metadata.owner != user.uid {
+                    return Err(FsError::PermissionDenied(path.clone()));
+                }
+                // Against the client's tree since the parent may be new too. Changes to an
+                // existing parent are checked on their own.
+                client.check_parent_writable(path)?;
+                continue;
+            };
+            let (new, old) = (&node.metadata, &old_node.metadata);
+            let chowned = new.owner != old.owner || new.group != old.group;
+            let chmodded = new.mode != old.mode && user.uid != old.owner;
+            let written = node.content != old_node.content || new.link_target != old.link_target || new.file_type != old.file_type;
+            if !user.is_root() && (chowned || chmodded || (written && !allowed(&user, old, Access::Write))) {
+                return Err(FsError::PermissionDenied(path.clone()));
+            }
+        }
+        for path in self.tree.keys().filter(|path| !client.tree.contains_key(*path)) {
+            self.check_parent_writable(path)?;
+        }
+        let known = self.mounts().into_iter().chain(self.detached_mounts.iter().cloned()).collect::<Vec<_>>();
+        let allowed_mount = |client: &FileSystem, dir: &str, source: &MountSource| {
+            known.iter().any(|(known_dir, known_source)| known_dir == dir && known_source == source)
+                || (dir != "/" && client.node_metadata(dir).map_or(false, Metadata::is_dir) && client.check_access(dir, Access::Write).is_ok())
+        };
+        let dropped = client.mounts().into_iter()
+            .filter(|(dir, source)| !allowed_mount(client, dir, source))
+            .collect::<Vec<_>>();
+        for (dir, source) in dropped {
+            log::warn!("dropped {source} on {dir}, {} couldn't have mounted it", user.uid);
+            client.mounts.remove(&dir);
+        }
+        let detached = std::mem::take(&mut client.detached_mounts);
+        client.detached_mounts = detached.into_iter()
+            .filter(|(dir, source)| allowed_mount(client, dir, source))
+            .collect();
+        Ok(())
+    }
+
+    // Replays a change another tab of the same user made. The server checked it when it was
+    // published, see apply_checked, so it's applied to the tree directly and isn't journaled
+    // again.
+    pub fn apply_change(&mut self, change: events::FsChange) {
+        match change {
+            events::FsChange::Created{path, metadata} => {
+                if self.tree.contains_key(&path) || !self.tree.contains_key(parent_path(&path)) {
+                    return;
+                }
+                let name = path.split('/').last().unwrap_or_default().to_string();
+                self.index.insert(&path, &name, &metadata, &[]);
+                self.tree.insert(path, FileSystemNode{name, metadata, content: Vec::new()});
+            },
+            events::FsChange::Written{path, content} => {
+                if let Some(node) = self.tree.get_mut(&path) {
+                    node.content = content;
+                    node.metadata.modified = self.clock.now();
+                    self.index.insert(&path, &node.name, &node.metadata, &node.content);
+                }
+            },
+            events::FsChange::Removed{path} => {
+                self.tree.remove(&path);
+                self.index.remove(&path);
+                self.mounts.remove(&path);
+            },
+            events::FsChange::Renamed{from, to} => {
+                if self.tree.contains_key(&from) && !self.tree.contains_key(&to) {
+                    self.move_tree(&from, &to);
+                }
+            },
+        }
+    }
+}
+
+// Collapses repeated slashes, "." and ".." so equal paths are equal strings.
+pub fn normalize_path(path: &str) -> String {
+    let mut components : Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => { components.pop(); },
+            component => components.push(component),
+        }
+    }
+    format!("/{}", components.join("/"))
+}
+
+// "/a/b" -> "/a", "/a" -> "/"
+pub fn parent_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(idx) => &path[..idx],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file() -> Metadata {
+        Metadata::new(FileType::File, "/text.png")
+    }
+
+    // /etc belongs to root, /home/user to DEFAULT_USER. Acting as DEFAULT_USER.
+    fn file_system() -> FileSystem {
+        file_system_with_clock(SharedClock::default())
+    }
+
+    fn file_system_with_clock(clock: SharedClock) -> FileSystem {
+        let mut fs = FileSystem::with_clock(clock);
+        fs.create_dir("/etc".to_string()).unwrap();
+        fs.create_file("/etc/hosts".to_string(), file(), b"localhost".to_vec()).unwrap();
+        fs.create_dir("/home".to_string()).unwrap();
+        fs.create_dir("/home/user".to_string()).unwrap();
+        fs.chown("/home/user", Credentials::DEFAULT_USER.uid, Credentials::DEFAULT_USER.gid).unwrap();
+        fs.set_user(Credentials::DEFAULT_USER);
+        fs
+    }
+
+    const OTHER_USER: Credentials = Credentials{uid:1001,gid:1001};
+
+    // 2024-01-01 00:00 UTC.
+    const NEW_YEAR: i64 = 1_704_067_200_000;
+
+    fn stopped_clock() -> (FileSystem, clock::MockClock) {
+        let clock = clock::MockClock::new(NEW_YEAR);
+        (file_system_with_clock(SharedClock::new(clock.clone())), clock)
+    }
+
+    #[test]
+    fn others_can_read_but_not_change_files() {
+        let mut fs = file_system();
+        assert_eq!(fs.read_file("/etc/hosts").unwrap().as_ref(), b"localhost");
+        assert_eq!(fs.write_file("/etc/hosts", Vec::new()), Err(FsError::PermissionDenied("/etc/hosts".to_string())));
+        assert_eq!(fs.create_dir("/etc/mine".to_string()), Err(FsError::PermissionDenied("/etc".to_string())));
+        assert_eq!(fs.remove_file("/etc/hosts"), Err(FsError::PermissionDenied("/etc".to_string())));
+        assert_eq!(fs.rename("/etc/hosts", "/home/user/hosts"), Err(FsError::PermissionDenied("/etc".to_string())));
+        fs.set_user(Credentials::ROOT);
+        fs.write_file("/etc/hosts", b"changed".to_vec()).unwrap();
+    }
+
+    #[test]
+    fn group_bits_apply_to_the_group() {
+        let mut fs = file_system();
+        fs.create_file("/home/user/shared".to_string(), file().with_mode(0o640), b"ours".to_vec()).unwrap();
+        fs.chmod("/home/user/shared", 0o640).unwrap();
+        fs.set_user(Credentials{uid:1002,gid:Credentials::DEFAULT_USER.gid});
+        assert_eq!(fs.read_file("/home/user/shared").unwrap().as_ref(), b"ours");
+        assert!(fs.write_file("/home/user/shared", Vec::new()).is_err());
+        fs.set_user(OTHER_USER);
+        assert_eq!(fs.read_file("/home/user/shared"), Err(FsError::PermissionDenied("/home/user/shared".to_string())));
+    }
+
+    #[test]
+    fn private_directories_cant_be_entered() {
+        let mut fs = file_system();
+        fs.create_file("/home/user/notes.txt".to_string(), file(), b"secret".to_vec()).unwrap();
+        fs.chmod("/home/user", 0o700).unwrap();
+        fs.set_user(OTHER_USER);
+        let denied = FsError::PermissionDenied("/home/user".to_string());
+        assert_eq!(fs.read_file("/home/user/notes.txt").unwrap_err(), denied);
+        assert_eq!(fs.list_dir("/home/user").unwrap_err(), denied);
+        assert_eq!(fs.get_file_metadata("/home/user/notes.txt").unwrap_err(), denied);
+        assert_eq!(fs.recents(), vec!["/etc/hosts".to_string()]);
+    }
+
+    #[test]
+    fn only_owners_chmod_and_only_root_chowns() {
+        let mut fs = file_system();
+        fs.create_file("/home/user/notes.txt".to_string(), file(), Vec::new()).unwrap();
+        fs.chmod("/home/user/notes.txt", 0o1600).unwrap();
+        assert_eq!(fs.get_file_metadata("/home/user/notes.txt").unwrap().mode, 0o600);
+        assert_eq!(fs.chown("/home/user/notes.txt", OTHER_USER.uid, OTHER_USER.gid), Err(FsError::PermissionDenied("/home/user/notes.txt".to_string())));
+        assert_eq!(fs.chmod("/etc/hosts", 0o666), Err(FsError::PermissionDenied("/etc/hosts".to_string())));
+        fs.set_user(OTHER_USER);
+        assert_eq!(fs.chmod("/home/user/notes.txt", 0o666), Err(FsError::PermissionDenied("/home/user/notes.txt".to_string())));
+        fs.set_user(Credentials::ROOT);
+        fs.chown("/home/user/notes.txt", OTHER_USER.uid, OTHER_USER.gid).unwrap();
+        assert_eq!(fs.get_file_metadata("/home/user/notes.txt").unwrap().owner, OTHER_USER.uid);
+    }
+
+    #[test]
+    fn directories_are_removed_once_empty() {
+        let mut fs = file_system();
+        fs.create_dir("/home/user/docs".to_string()).unwrap();
+        fs.create_file("/home/user/docs/notes.txt".to_string(), file(), Vec::new()).unwrap();
+        assert_eq!(fs.remove_file("/home/user/docs"), Err(FsError::NotEmpty("/home/user/docs".to_string())));
+        assert!(fs.get_file_metadata("/home/user/docs/notes.txt").is_ok());
+        fs.remove_file("/home/user/docs/notes.txt").unwrap();
+        fs.remove_file("/home/user/docs").unwrap();
+        assert!(fs.list_dir("/home/user").unwrap().is_empty());
+
+        fs.create_dir("/home/user/mnt".to_string()).unwrap();
+        fs.create_dir("/home/user/mnt/disk".to_string()).unwrap();
+        fs.mount_source("/home/user/mnt/disk", MountSource::Memory).unwrap();
+        assert_eq!(fs.remove_file("/home/user/mnt"), Err(FsError::NotEmpty("/home/user/mnt".to_string())));
+        // Removing the mount point itself unmounts it.
+        fs.remove_file("/home/user/mnt/disk").unwrap();
+        assert!(fs.mounts().is_empty());
+        fs.remove_file("/home/user/mnt").unwrap();
+    }
+
+    #[test]
+    fn published_changes_are_checked() {
+        let mut fs = file_system();
+        fs.take_journal();
+        let change = events::FsChange::Written{path:"/etc/hosts".to_string(), content:Vec::new()};
+        assert_eq!(fs.apply_checked(change), Err(FsError::PermissionDenied("/etc/hosts".to_string())));
+        let change = events::FsChange::Removed{path:"/home".to_string()};
+        assert_eq!(fs.apply_checked(change), Err(FsError::PermissionDenied("/".to_string())));
+        let mut metadata = file();
+        metadata.owner = 0;
+        metadata.mode = 0o4777;
+        let change = events::FsChange::Created{path:"/home/user/new".to_string(), metadata};
+        let applied = fs.apply_checked(change).unwrap();
+        let created = fs.lstat("/home/user/new").unwrap();
+        assert_eq!((created.owner, created.mode), (Credentials::DEFAULT_USER.uid, 0o777));
+        assert_eq!(applied.len(), 1);
+        assert!(!fs.has_journal());
+    }
+
+    #[test]
+    fn vet_allows_what_the_user_could_do() {
+        let kept = file_system();
+        let mut client = kept.clone();
+        client.create_file("/home/user/notes.txt".to_string(), file(), b"hi".to_vec()).unwrap();
+        client.chmod("/home/user", 0o700).unwrap();
+        client.set_user(Credentials::ROOT);
+        assert_eq!(kept.vet(&mut client), Ok(()));
+        assert_eq!(client.user(), Credentials::DEFAULT_USER);
+    }
+
+    #[test]
+    fn vet_refuses_what_only_root_could_do() {
+        let kept = file_system();
+        let mut client = kept.clone();
+        client.set_user(Credentials::ROOT);
+        client.chown("/home/user", 0, 0).unwrap();
+        assert_eq!(kept.vet(&mut client), Err(FsError::PermissionDenied("/home/user".to_string())));
+
+        let mut client = kept.clone();
+        client.set_user(Credentials::ROOT);
+        client.write_file("/etc/hosts", b"evil".to_vec()).unwrap();
+        assert_eq!(kept.vet(&mut client), Err(FsError::PermissionDenied("/etc/hosts".to_string())));
+
+        let mut client = kept.clone();
+        client.set_user(Credentials::ROOT);
+        client.create_file("/etc/passwd".to_string(), file(), Vec::new()).unwrap();
+        assert_eq!(kept.vet(&mut client), Err(FsError::PermissionDenied("/etc/passwd".to_string())));
+
+        let mut client = kept.clone();
+        client.set_user(Credentials::ROOT);
+        client.remove_file("/etc/hosts").unwrap();
+        assert_eq!(kept.vet(&mut client), Err(FsError::PermissionDenied("/etc".to_string())));
+    }
+
+    #[test]
+    fn vet_refuses_new_files_in_directories_the_user_cant_write() {
+        let mut kept = file_system();
+        kept.set_user(Credentials::ROOT);
+        kept.create_dir("/home/other".to_string()).unwrap();
+        kept.chown("/home/other", OTHER_USER.uid, OTHER_USER.gid).unwrap();
+        kept.set_user(Credentials::DEFAULT_USER);
+        kept.create_file("/home/user/notes.txt".to_string(), file(), b"hi".to_vec()).unwrap();
+
+        // Planted in a root owned directory, owned by the user so the owner check passes.
+        let mut client = kept.clone();
+        client.set_user(Credentials::ROOT);
+        client.create_file("/etc/cron".to_string(), file(), Vec::new()).unwrap();
+        client.chown("/etc/cron", Credentials::DEFAULT_USER.uid, Credentials::DEFAULT_USER.gid).unwrap();
+        assert_eq!(kept.vet(&mut client), Err(FsError::PermissionDenied("/etc".to_string())));
+
+        let mut client = kept.clone();
+        client.set_user(Credentials::ROOT);
+        client.create_dir("/home/other/mine".to_string()).unwrap();
+        client.chown("/home/other/mine", Credentials::DEFAULT_USER.uid, Credentials::DEFAULT_USER.gid).unwrap();
+        assert_eq!(kept.vet(&mut client), Err(FsError::PermissionDenied("/home/other".to_string())));
+
+        // A rename is a removal the user may make and a creation they may not.
+        let mut client = kept.clone();
+        client.set_user(Credentials::ROOT);
+        client.rename("/home/user/notes.txt", "/etc/notes.txt").unwrap();
+        assert_eq!(kept.vet(&mut client), Err(FsError::PermissionDenied("/etc".to_string())));
+
+        // New directories of the user's own can be filled.
+        let mut client = kept.clone();
+        client.create_dir("/home/user/a".to_string()).unwrap();
+        client.create_file("/home/user/a/b.txt".to_string(), file(), Vec::new()).unwrap();
+        assert_eq!(kept.vet(&mut client), Ok(()));
+    }
+
+    #[test]
+    fn vet_drops_mounts_the_user_couldnt_make() {
+        let kept = file_system();
+        let mut client = kept.clone();
+        client.mount_source("/home/user", MountSource::Memory).unwrap();
+        client.set_user(Credentials::ROOT);
+        client.mount_source("/etc", MountSource::Memory).unwrap();
+        assert_eq!(kept.vet(&mut client), Ok(()));
+        assert_eq!(client.mounts(), vec![("/home/user".to_string(), MountSource::Memory)]);
+    }
+
+    #[test]
+    fn times_come_from_the_clock() {
+        let (mut fs, clock) = stopped_clock();
+        let times = |fs: &FileSystem| {
+            let metadata = fs.get_file_metadata("/home/user/a.txt").unwrap();
+            (metadata.created, metadata.modified, metadata.accessed)
+        };
+        fs.create_file("/home/user/a.txt".to_string(), file(), b"a".to_vec()).unwrap();
+        assert_eq!(times(&fs), (NEW_YEAR, NEW_YEAR, NEW_YEAR));
+        clock.advance(1000);
+        fs.read_file("/home/user/a.txt").unwrap();
+        assert_eq!(times(&fs), (NEW_YEAR, NEW_YEAR, NEW_YEAR + 1000));
+        clock.advance(1000);
+        fs.write_file("/home/user/a.txt", b"b".to_vec()).unwrap();
+        assert_eq!(times(&fs), (NEW_YEAR, NEW_YEAR + 2000, NEW_YEAR + 2000));
+        clock.advance(1000);
+        fs.open("/home/user/a.txt").unwrap();
+        assert_eq!(times(&fs), (NEW_YEAR, NEW_YEAR + 2000, NEW_YEAR + 3000));
+    }
+
+    #[test]
+    fn recents_are_ordered_by_access_time() {
+        let (mut fs, clock) = stopped_clock();
+        fs.create_file("/home/user/a.txt".to_string(), file(), Vec::new()).unwrap();
+        fs.create_file("/home/user/b.txt".to_string(), file(), Vec::new()).unwrap();
+        clock.advance(1);
+        fs.read_file("/home/user/a.txt").unwrap();
+        clock.advance(1);
+        fs.read_file("/home/user/b.txt").unwrap();
+        assert_eq!(fs.recents(), vec!["/home/user/b.txt", "/home/user/a.txt", "/etc/hosts"]);
+        clock.advance(1);
+        fs.open("/home/user/a.txt").unwrap();
+        assert_eq!(fs.recents(), vec!["/home/user/a.txt", "/home/user/b.txt", "/etc/hosts"]);
+    }
+
+    #[test]
+    fn search_dates_use_the_clock() {
+        let (mut fs, clock) = stopped_clock();
+        let found = |fs: &FileSystem, query: &str| {
+            fs.search(&search::SearchQuery::parse(query)).into_iter().map(|hit| hit.path).collect::<Vec<_>>()
+        };
+        fs.create_file("/home/user/notes-old.txt".to_string(), file(), b"old".to_vec()).unwrap();
+        clock.advance(10 * 24 * 60 * 60 * 1000);
+        fs.create_file("/home/user/notes-new.txt".to_string(), file(), b"new".to_vec()).unwrap();
+        assert_eq!(found(&fs, "notes after:2024-01-05"), vec!["/home/user/notes-new.txt"]);
+        assert_eq!(found(&fs, "notes before:2024-01-01"), vec!["/home/user/notes-old.txt"]);
+        assert_eq!(found(&fs, "notes"), vec!["/home/user/notes-new.txt", "/home/user/notes-old.txt"]);
+        // Writing moves the file out of the old day.
+        fs.write_file("/home/user/notes-old.txt", b"rewritten".to_vec()).unwrap();
+        assert_eq!(found(&fs, "notes before:2024-01-01"), Vec::<String>::new());
+        assert_eq!(found(&fs, "notes after:2024-01-05").len(), 2);
+    }
+
+    #[test]
+    fn link_cycles_are_refused() {
+        let mut fs = file_system();
+        fs.symlink("/home/user/b", "/home/user/a".to_string()).unwrap();
+        fs.symlink("/home/user/a", "/home/user/b".to_string()).unwrap();
+        fs.symlink("self", "/home/user/self".to_string()).unwrap();
+        fs.symlink("/home/user/deeper/x", "/home/user/deeper".to_string()).unwrap();
+        for path in ["/home/user/a", "/home/user/self", "/home/user/deeper", "/home/user/a/file"] {
+            assert!(matches!(fs.resolve(path), Err(FsError::LinkDepthExceeded(_))), "{path}");
+            assert!(matches!(fs.read_file(path), Err(FsError::LinkDepthExceeded(_))), "{path}");
+        }
+        // The links themselves are still there to look at and remove.
+        assert_eq!(fs.read_link("/home/user/a"), Ok("/home/user/b"));
+        let entries = fs.dir_entries("/home/user").unwrap();
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().all(|entry| entry.dangling && entry.file_type == FileType::SymbolicLink));
+        fs.remove_file("/home/user/a").unwrap();
+        assert_eq!(fs.resolve("/home/user/b"), Ok("/home/user/a".to_string()));
+    }
+
+    #[test]
+    fn link_chains_resolve_up_to_the_limit() {
+        let mut fs = file_system();
+        fs.create_file("/home/user/file".to_string(), file(), b"end".to_vec()).unwrap();
+        fs.symlink("/home/user/file", "/home/user/l0".to_string()).unwrap();
+        for i in 1..=MAX_LINK_DEPTH {
+            fs.symlink(&format!("l{}", i - 1), format!("/home/user/l{i}")).unwrap();
+        }
+        let last = format!("/home/user/l{}", MAX_LINK_DEPTH - 1);
+        assert_eq!(fs.resolve(&last), Ok("/home/user/file".to_string()));
+        assert_eq!(fs.read_file(&last).unwrap().as_ref(), b"end");
+        let past = format!("/home/user/l{MAX_LINK_DEPTH}");
+        assert!(matches!(fs.resolve(&past), Err(FsError::LinkDepthExceeded(_))));
+    }
+}
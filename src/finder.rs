@@ -0,0 +1,166 @@
+use super::*;
+use file_system::{DirEntry,FileType,FsError,SystemRuntime,DESKTOP_PATH,parent_path};
+use vfs::MountSource;
+
+// The Finder app, see desktop.toml.
+pub const FINDER_PATH: &'static str = "/finder";
+
+// Kept as the window's app state, so it reopens at the same folder.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+struct FinderState{
+    dir:String,
+}
+
+impl Default for FinderState{
+    fn default() -> Self {
+        Self{dir:DESKTOP_PATH.to_string()}
+    }
+}
+
+// Opens a new Finder window showing dir.
+pub fn open_folder(system:RwSignal<SystemRuntime>, dir:&str) {
+    let title = dir.rsplit('/').find(|name| !name.is_empty()).unwrap_or("/").to_string();
+    let state = serde_json::to_value(FinderState{dir:dir.to_string()}).unwrap_or_default();
+    system.update(|system| {
+        let id = system.windows_mut().open(FINDER_PATH, title);
+        system.windows_mut().set_state(id, state);
+        system.running_app_paths.insert(FINDER_PATH.to_string());
+    });
+}
+
+// Opens what's at path: folders and archives in a Finder window, anything else with
+// SystemRuntime::run_app.
+pub fn open(system:RwSignal<SystemRuntime>, path:&str) {
+    match as_folder(system, path) {
+        Some(true) => open_folder(system, path),
+        Some(false) => system.update(|system| system.run_app(path)),
+        None => {},
+    }
+}
+
+// Whether path can be shown as a folder: directories, and archives once they're mounted over
+// themselves. None when an archive couldn't be mounted, which is reported.
+fn as_folder(system:RwSignal<SystemRuntime>, path:&str) -> Option<bool> {
+    match mount_archive(system, path) {
+        Ok(is_folder) => Some(is_folder),
+        Err(err) => {
+            let name = path.rsplit('/').next().unwrap_or_default();
+            if let Some(notifications) = use_context::<RwSignal<notifications::Notifications>>() {
+                notifications::notify(notifications, notifications::Notification::new(notifications::Severity::Error, format!("Couldn't open {name}"))
+                    .with_body(err.to_string()));
+            }
+            None
+        },
+    }
+}
+
+// Mounts an archive at its own path like `mount archive.zip` does, if it isn't already.
+// Ok(true) for directories too, Ok(false) for anything else.
+fn mount_archive(system:RwSignal<SystemRuntime>, path:&str) -> Result<bool,FsError> {
+    let (is_dir, is_archive, mounted) = system.with_untracked(|system| {
+        let file_system = system.file_system();
+        let Ok(metadata) = file_system.get_file_metadata(path) else { return (false, false, false) };
+        let is_archive = metadata.is_file()
+            && metadata.mime_type.as_deref().or_else(|| mime::from_extension(path)).map_or(false, mime::is_archive)
+            && archive::ArchiveKind::from_name(path).is_some();
+        (metadata.is_dir(), is_archive, file_system.is_mounted(path))
+    });
+    if is_archive && !mounted {
+        system.try_update(|system| system.mount(path, MountSource::Archive)).unwrap_or(Ok(()))?;
+    }
+    Ok(is_dir || is_archive)
+}
+
+// The content of a Finder window, see windows::WindowContent. Double clicking a folder or an
+// archive goes into it, anything else is opened. Files dropped on it are imported into the folder shown.
+#[component]
+pub fn Finder() -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let window = expect_context::<windows::WindowId>();
+    let imports = expect_context::<RwSignal<import::Imports>>();
+    let dir = create_rw_signal(session_restore::app_state::<FinderState>(system, window.0).dir);
+    create_effect(move |first:Option<()>| {
+        let dir = dir();
+        if first.is_some() {
+            session_restore::save_app_state(system, window.0, &FinderState{dir});
+        }
+    });
+    let entries = move || system.with(|system| system.dir_entries(&dir()));
+    view!{
+        <div class="h-full flex flex-col">
+            <div class="flex items-center gap-2 px-2 py-1 bg-field bg-opacity-50">
+                <button class="px-2 rounded-theme hover:bg-highlight disabled:text-muted"
+                disabled=move || dir() == "/"
+                on:click=move |_| dir.update(|dir| *dir = parent_path(dir).to_string())>"‹"</button>
+                <span class="text-sm truncate">{dir}</span>
+            </div>
+            <div class="flex-1 grid grid-cols-[repeat(auto-fill,minmax(6rem,1fr))] content-start gap-4 p-4 overflow-y-auto"
+            on:dragover=move |ev| ev.prevent_default()
+            on:drop=move |ev| {
+                ev.prevent_default();
+                import::import_drop(&ev, dir.get_untracked(), system, imports);
+            }>
+                <For
+                each=entries
+                key=|entry| (entry.path.clone(),entry.dangling)
+                children=move |entry| view!{<FinderItem entry dir/>}
+                />
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn FinderItem(entry:DirEntry, dir:RwSignal<String>) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let DirEntry{path,name,img_src,file_type,dangling} = entry;
+    view!{
+        <button class="flex flex-col items-center rounded-theme hover:bg-highlight"
+        title=dangling.then(|| "The original item can't be found.")
+        on:dblclick=move |_| match file_type {
+            FileType::Directory => dir.set(path.clone()),
+            _ => match as_folder(system, &path) {
+                Some(true) => dir.set(path.clone()),
+                Some(false) => system.update(|system| system.run_app(&path)),
+                None => {},
+            },
+        }>
+            <img src=img_src class="w-16" class=("opacity-50",dangling)/>
+            <p class="text-sm text-center break-all" class=("italic",dangling)>{name}</p>
+        </button>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_system::{FileSystem,Metadata};
+
+    fn file() -> Metadata {
+        Metadata::new(FileType::File, "/text.png")
+    }
+
+    #[test]
+    fn archives_open_as_folders() {
+        let runtime = create_runtime();
+        let mut file_system = FileSystem::new();
+        let mut writer = zip::ZipWriter::new();
+        writer.add_file("notes.txt", 0, 0o644, b"hello").unwrap();
+        file_system.create_dir("/docs".to_string()).unwrap();
+        file_system.create_file("/docs/a.zip".to_string(), file(), writer.finish()).unwrap();
+        file_system.create_file("/docs/notes.txt".to_string(), file(), b"plain".to_vec()).unwrap();
+        file_system.create_file("/docs/broken.zip".to_string(), file(), b"not a zip".to_vec()).unwrap();
+        let system = create_rw_signal(SystemRuntime::new(file_system, Vec::new()));
+
+        assert_eq!(as_folder(system, "/docs"), Some(true));
+        assert_eq!(as_folder(system, "/docs/notes.txt"), Some(false));
+        assert_eq!(as_folder(system, "/docs/a.zip"), Some(true));
+        assert!(system.with_untracked(|system| system.file_system().is_mounted("/docs/a.zip")));
+        assert_eq!(system.with_untracked(|system| system.file_system().list_dir("/docs/a.zip")), Ok(vec!["/docs/a.zip/notes.txt".to_string()]));
+        // Opening it again uses the mount that's there.
+        assert_eq!(as_folder(system, "/docs/a.zip"), Some(true));
+        assert_eq!(as_folder(system, "/docs/broken.zip"), None);
+        assert!(!system.with_untracked(|system| system.file_system().is_mounted("/docs/broken.zip")));
+        runtime.dispose();
+    }
+}
@@ -0,0 +1,210 @@
+use super::*;
+use file_system::{SystemRuntime,Metadata,FileType};
+use std::cell::RefCell;
+use gloo::events::EventListener;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{File,FileSystemEntry,FileSystemDirectoryEntry,FileSystemFileEntry};
+
+// Files are read in slices of this many bytes so large files show progress as they load.
+const CHUNK_SIZE: f64 = 1024.0 * 1024.0;
+
+// One file being copied in from the host machine.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ImportProgress{
+    // Where the file will end up, unique while the import runs.
+    pub path:String,
+    pub loaded:u64,
+    pub total:u64,
+}
+
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Imports(pub Vec<ImportProgress>);
+
+// Imports whatever was dropped from the host OS into dir. Folders are imported with everything
+// in them. Entries have to be taken from the DataTransfer before the handler returns.
+pub fn import_drop(ev:&web_sys::DragEvent, dir:String, system:RwSignal<SystemRuntime>, imports:RwSignal<Imports>) {
+    let Some(data) = ev.data_transfer() else { return };
+    let items = data.items();
+    let mut entries = Vec::new();
+    let mut files = Vec::new();
+    for i in 0..items.length() {
+        let Some(item) = items.get(i) else { continue };
+        if item.kind() != "file" {
+            continue;
+        }
+        match item.webkit_get_as_entry() {
+            Ok(Some(entry)) => entries.push(entry),
+            _ => if let Ok(Some(file)) = item.get_as_file() {
+                files.push(file);
+            },
+        }
+    }
+    spawn_local(async move {
+        for entry in entries {
+            import_entry(entry, dir.clone(), system, imports).await;
+        }
+        for file in files {
+            import_file(file, dir.clone(), system, imports).await;
+        }
+    });
+}
+
+thread_local! {
+    // The listener of the last picker opened. Opening another drops it, so a cancelled pick,
+    // which never fires change, doesn't keep its closure for good.
+    static PICKER: RefCell<Option<EventListener>> = RefCell::new(None);
+}
+
+// Opens the browser's file picker, accept being the input's accept attribute, and passes what
+// was chosen to on_pick.
+pub fn pick(accept:Option<&str>, multiple:bool, on_pick:impl FnOnce(Vec<File>) + 'static) {
+    let Ok(input) = document().create_element("input") else { return };
+    let input = input.unchecked_into::<web_sys::HtmlInputElement>();
+    input.set_type("file");
+    input.set_multiple(multiple);
+    if let Some(accept) = accept {
+        input.set_accept(accept);
+    }
+    let picker = input.clone();
+    let listener = EventListener::once(&input, "change", move |_| {
+        let Some(list) = picker.files() else { return };
+        on_pick((0..list.length()).filter_map(|i| list.get(i)).collect());
+    });
+    PICKER.with(|kept| kept.replace(Some(listener)));
+    input.click();
+}
+
+// Opens the browser's file picker and imports the chosen files into dir.
+pub fn pick_files(dir:String, system:RwSignal<SystemRuntime>, imports:RwSignal<Imports>) {
+    pick(None, true, move |files| spawn_local(async move {
+        for file in files {
+            import_file(file, dir.clone(), system, imports).await;
+        }
+    }));
+}
+
+// Walks a dropped folder without recursion, creating directories as it goes.
+async fn import_entry(entry:FileSystemEntry, dir:String, system:RwSignal<SystemRuntime>, imports:RwSignal<Imports>) {
+    let mut stack = vec![(entry,dir)];
+    while let Some((entry,dir)) = stack.pop() {
+        if entry.is_directory() {
+            let entry = entry.unchecked_into::<FileSystemDirectoryEntry>();
+            let created = system.try_update(|system| {
+                let file_system = system.file_system_mut();
+                let path = file_system.unique_path(&dir, &entry.name());
+                file_system.create_dir(path.clone()).map(|_| path)
+            });
+            let path = match created {
+                Some(Ok(path)) => path,
+                Some(Err(err)) => {
+                    log::error!("import of {} failed: {err}", entry.name());
+                    continue;
+                },
+                None => continue,
+            };
+            match read_dir_entries(&entry).await {
+                Ok(children) => stack.extend(children.into_iter().map(|child| (child,path.clone()))),
+                Err(err) => log::error!("reading {} failed: {err:?}", entry.name()),
+            }
+        } else if entry.is_file() {
+            let entry = entry.unchecked_into::<FileSystemFileEntry>();
+            match entry_file(&entry).await {
+                Ok(file) => import_file(file, dir, system, imports).await,
+                Err(err) => log::error!("reading {} failed: {err:?}", entry.name()),
+            }
+        }
+    }
+}
+
+async fn import_file(file:File, dir:String, system:RwSignal<SystemRuntime>, imports:RwSignal<Imports>) {
+    let name = file.name();
+    let Some(path) = system.try_with_untracked(|system| system.file_system().unique_path(&dir, &name)) else { return };
+    let total = file.size() as u64;
+    imports.update(|imports| imports.0.push(ImportProgress{path:path.clone(),loaded:0,total}));
+    let result = read_with_progress(&file, |loaded| imports.update(|imports| {
+        if let Some(progress) = imports.0.iter_mut().find(|progress| progress.path == path) {
+            progress.loaded = loaded;
+        }
+    })).await;
+    imports.update(|imports| imports.0.retain(|progress| progress.path != path));
+    let content = match result {
+        Ok(content) => content,
+        Err(err) => {
+            log::error!("reading {name} failed: {err:?}");
+            return;
+        },
+    };
+    let mime = mime::detect(&name, &content, &file.type_());
+    let mut metadata = Metadata::new(FileType::File, mime::icon(&mime));
+    metadata.mime_type = Some(mime);
+    system.update(|system| {
+        if let Err(err) = system.file_system_mut().create_file(path, metadata, content) {
+            log::error!("import of {name} failed: {err}");
+        }
+    });
+}
+
+async fn read_with_progress(file:&File, mut on_progress:impl FnMut(u64)) -> Result<Vec<u8>,JsValue> {
+    let total = file.size();
+    let mut content = Vec::with_capacity(total as usize);
+    let mut start = 0.0;
+    while start < total {
+        let end = (start + CHUNK_SIZE).min(total);
+        let chunk = file.slice_with_f64_and_f64(start, end)?;
+        let buffer = JsFuture::from(chunk.array_buffer()).await?;
+        content.extend(js_sys::Uint8Array::new(&buffer).to_vec());
+        start = end;
+        on_progress(start as u64);
+    }
+    Ok(content)
+}
+
+// readEntries hands out children in batches and signals the end with an empty one.
+async fn read_dir_entries(dir:&FileSystemDirectoryEntry) -> Result<Vec<FileSystemEntry>,JsValue> {
+    let reader = dir.create_reader();
+    let mut entries = Vec::new();
+    loop {
+        let batch = JsFuture::from(js_sys::Promise::new(&mut |resolve,reject| {
+            if let Err(err) = reader.read_entries_with_callback_and_callback(&resolve, &reject) {
+                _ = reject.call1(&JsValue::NULL, &err);
+            }
+        })).await?;
+        let batch = batch.unchecked_into::<js_sys::Array>();
+        if batch.length() == 0 {
+            return Ok(entries);
+        }
+        entries.extend(batch.iter().map(|entry| entry.unchecked_into::<FileSystemEntry>()));
+    }
+}
+
+async fn entry_file(entry:&FileSystemFileEntry) -> Result<File,JsValue> {
+    let file = JsFuture::from(js_sys::Promise::new(&mut |resolve,reject| {
+        entry.file_with_callback_and_callback(&resolve, &reject);
+    })).await?;
+    Ok(file.unchecked_into::<File>())
+}
+
+#[component]
+pub fn ImportProgressList() -> impl IntoView {
+    let imports = expect_context::<RwSignal<Imports>>();
+    view!{
+        <div class="fixed bottom-24 right-4 flex flex-col gap-1 z-30">
+        <For
+        each=move || imports().0
+        key=|progress| (progress.path.clone(),progress.loaded)
+        children=move |progress| {
+            let percent = if progress.total == 0 { 100 } else { progress.loaded * 100 / progress.total };
+            view!{
+                <div class="w-64 bg-surface bg-opacity-50 backdrop-blur-theme rounded-theme p-2 text-sm">
+                <p class="truncate">{progress.path.split('/').last().unwrap_or_default().to_string()}</p>
+                <div class="h-1 rounded bg-selected">
+                    <div class="h-1 rounded bg-content" style=format!("width:{percent}%")></div>
+                </div>
+                </div>
+            }
+        }
+        />
+        </div>
+    }
+}
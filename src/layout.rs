@@ -0,0 +1,323 @@
+// Window geometry. Everything here is plain math on rects in px, so it works the same on the
+// server and in the browser.
+
+#[derive(Debug,Clone,Copy,PartialEq,Default,serde::Serialize,serde::Deserialize)]
+pub struct Rect{
+    pub x:f64,
+    pub y:f64,
+    pub width:f64,
+    pub height:f64,
+}
+
+impl Rect{
+    pub const fn new(x:f64, y:f64, width:f64, height:f64) -> Self {
+        Self{x,y,width,height}
+    }
+
+    // Shrinks the rect by gap on every side.
+    pub fn inset(&self, gap:f64) -> Self {
+        Self::new(self.x + gap, self.y + gap, (self.width - 2.0 * gap).max(0.0), (self.height - 2.0 * gap).max(0.0))
+    }
+
+    // The largest rect with this one's aspect ratio that fits in area, centered in it.
+    pub fn fit_into(&self, area:Rect) -> Self {
+        if self.width <= 0.0 || self.height <= 0.0 {
+            return area;
+        }
+        let scale = (area.width / self.width).min(area.height / self.height).min(1.0);
+        let (width, height) = (self.width * scale, self.height * scale);
+        Self::new(area.x + (area.width - width) / 2.0, area.y + (area.height - height) / 2.0, width, height)
+    }
+
+    // Keeps at least min px of the rect inside area so it can always be grabbed again.
+    pub fn keep_reachable(&self, area:Rect, min:f64) -> Self {
+        let x = self.x.clamp(area.x - self.width + min, area.x + area.width - min);
+        let y = self.y.clamp(area.y, area.y + area.height - min);
+        Self::new(x, y, self.width, self.height)
+    }
+
+    pub fn style(&self) -> String {
+        format!("left:{}px;top:{}px;width:{}px;height:{}px;", self.x, self.y, self.width, self.height)
+    }
+}
+
+// count cells of a grid as square as possible filling area, row by row.
+pub fn grid(count:usize, area:Rect, gap:f64) -> Vec<Rect> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let columns = (count as f64).sqrt().ceil() as usize;
+    let rows = (count + columns - 1) / columns;
+    let width = area.width / columns as f64;
+    let height = area.height / rows as f64;
+    (0..count)
+        .map(|i| Rect::new(area.x + (i % columns) as f64 * width, area.y + (i / columns) as f64 * height, width, height).inset(gap / 2.0))
+        .collect()
+}
+
+// Where each window goes in the overview: a grid cell each, scaled down to fit it but never
+// scaled up.
+pub fn overview(windows:&[Rect], area:Rect, gap:f64) -> Vec<Rect> {
+    windows.iter()
+        .zip(grid(windows.len(), area, gap))
+        .map(|(window, cell)| window.fit_into(cell))
+        .collect()
+}
+
+// Where a window can be snapped to, as a part of the desktop area.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,serde::Serialize,serde::Deserialize)]
+#[serde(rename_all="kebab-case")]
+pub enum Snap{
+    Maximized,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Snap{
+    pub fn rect(&self, area:Rect) -> Rect {
+        let (width, height) = (area.width / 2.0, area.height / 2.0);
+        let (middle, center) = (area.x + width, area.y + height);
+        match self {
+            Self::Maximized => area,
+            Self::Left => Rect::new(area.x, area.y, width, area.height),
+            Self::Right => Rect::new(middle, area.y, width, area.height),
+            Self::TopLeft => Rect::new(area.x, area.y, width, height),
+            Self::TopRight => Rect::new(middle, area.y, width, height),
+            Self::BottomLeft => Rect::new(area.x, center, width, height),
+            Self::BottomRight => Rect::new(middle, center, width, height),
+        }
+    }
+}
+
+// How much further than within from an edge the pointer can be along the other edge and still
+// hit a corner.
+const CORNER_FACTOR: f64 = 4.0;
+
+// What a window dragged by the pointer at (x,y) snaps to when it's dropped: maximized at the
+// top edge, a half at the left and right edges and a quarter in the corners. within is how
+// close to an edge counts as at it. The bottom edge is left to the dock.
+pub fn snap_at(x:f64, y:f64, area:Rect, within:f64) -> Option<Snap> {
+    let (left, right) = (x - area.x, area.x + area.width - x);
+    let (top, bottom) = (y - area.y, area.y + area.height - y);
+    let edge = |distance:f64| distance <= within;
+    let corner = |a:f64, b:f64| (edge(a) && b <= within * CORNER_FACTOR) || (edge(b) && a <= within * CORNER_FACTOR);
+    if corner(left, top) {
+        Some(Snap::TopLeft)
+    } else if corner(right, top) {
+        Some(Snap::TopRight)
+    } else if corner(left, bottom) {
+        Some(Snap::BottomLeft)
+    } else if corner(right, bottom) {
+        Some(Snap::BottomRight)
+    } else if edge(left) {
+        Some(Snap::Left)
+    } else if edge(right) {
+        Some(Snap::Right)
+    } else if edge(top) {
+        Some(Snap::Maximized)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,serde::Serialize,serde::Deserialize)]
+#[serde(rename_all="kebab-case")]
+pub enum Tiling{
+    // Windows stay where they're put.
+    #[default]
+    Off,
+    // Side by side, all the same width.
+    Columns,
+    // The first window takes MASTER_RATIO of the width, the others share the rest top to bottom.
+    MasterStack,
+}
+
+impl Tiling{
+    pub const ALL: [Self;3] = [Self::Off,Self::Columns,Self::MasterStack];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Columns => "Columns",
+            Self::MasterStack => "Master and Stack",
+        }
+    }
+}
+
+pub const MASTER_RATIO: f64 = 0.6;
+
+// Rects for count windows tiled over area, gap apart and gap from its edges. Tiling::Off has
+// no layout, so it gives none.
+pub fn tile(tiling:Tiling, count:usize, area:Rect, gap:f64) -> Vec<Rect> {
+    let area = area.inset(gap / 2.0);
+    let cells = match (tiling, count) {
+        (Tiling::Off, _) | (_, 0) => Vec::new(),
+        (Tiling::MasterStack, 1) => vec![area],
+        (Tiling::Columns, _) => {
+            let width = area.width / count as f64;
+            (0..count).map(|i| Rect::new(area.x + i as f64 * width, area.y, width, area.height)).collect()
+        },
+        (Tiling::MasterStack, _) => {
+            let master = area.width * MASTER_RATIO;
+            let height = area.height / (count - 1) as f64;
+            std::iter::once(Rect::new(area.x, area.y, master, area.height))
+                .chain((0..count - 1).map(|i| Rect::new(area.x + master, area.y + i as f64 * height, area.width - master, height)))
+                .collect()
+        },
+    };
+    cells.into_iter().map(|cell| cell.inset(gap / 2.0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAP: f64 = 8.0;
+    // Odd sizes included, halves then fall between pixels.
+    const AREAS: [Rect;4] = [
+        Rect::new(0.0, 0.0, 1920.0, 1080.0),
+        Rect::new(0.0, 28.0, 1366.0, 740.0),
+        Rect::new(10.0, 28.0, 1001.0, 767.0),
+        Rect::new(0.0, 0.0, 375.0, 667.0),
+    ];
+
+    fn right(rect:Rect) -> f64 {
+        rect.x + rect.width
+    }
+
+    fn bottom(rect:Rect) -> f64 {
+        rect.y + rect.height
+    }
+
+    fn close(a:f64, b:f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn overlap(a:Rect, b:Rect) -> bool {
+        a.x < right(b) - 1e-9 && b.x < right(a) - 1e-9 && a.y < bottom(b) - 1e-9 && b.y < bottom(a) - 1e-9
+    }
+
+    #[test]
+    fn snaps_split_the_area() {
+        for area in AREAS {
+            assert_eq!(Snap::Maximized.rect(area), area);
+            let (left, right_half) = (Snap::Left.rect(area), Snap::Right.rect(area));
+            assert!(close(left.width + right_half.width, area.width));
+            assert!(close(right(left), right_half.x));
+            assert!(close(right(right_half), right(area)));
+            assert_eq!((left.y, left.height), (area.y, area.height));
+
+            let quarters = [Snap::TopLeft, Snap::TopRight, Snap::BottomLeft, Snap::BottomRight].map(|snap| snap.rect(area));
+            for (i, a) in quarters.iter().enumerate() {
+                assert!(close(a.width, area.width / 2.0) && close(a.height, area.height / 2.0));
+                assert!(a.x >= area.x && a.y >= area.y && right(*a) <= right(area) + 1e-9 && bottom(*a) <= bottom(area) + 1e-9);
+                assert!(quarters[i + 1..].iter().all(|b| !overlap(*a, *b)));
+            }
+            assert_eq!(quarters[0].x, left.x);
+            assert!(close(bottom(quarters[3]), bottom(area)));
+        }
+    }
+
+    #[test]
+    fn snap_at_finds_edges_and_corners() {
+        let area = AREAS[1];
+        let within = 8.0;
+        let (middle, center) = (area.x + area.width / 2.0, area.y + area.height / 2.0);
+        assert_eq!(snap_at(middle, area.y, area, within), Some(Snap::Maximized));
+        assert_eq!(snap_at(area.x + 2.0, center, area, within), Some(Snap::Left));
+        assert_eq!(snap_at(right(area) - 2.0, center, area, within), Some(Snap::Right));
+        assert_eq!(snap_at(area.x, area.y, area, within), Some(Snap::TopLeft));
+        // Corners reach further along the edge than the edges themselves.
+        assert_eq!(snap_at(area.x + 20.0, area.y + 1.0, area, within), Some(Snap::TopLeft));
+        assert_eq!(snap_at(right(area), area.y + 20.0, area, within), Some(Snap::TopRight));
+        assert_eq!(snap_at(area.x + 1.0, bottom(area) - 20.0, area, within), Some(Snap::BottomLeft));
+        assert_eq!(snap_at(right(area) - 1.0, bottom(area), area, within), Some(Snap::BottomRight));
+        // The bottom edge is left to the dock.
+        assert_eq!(snap_at(middle, bottom(area), area, within), None);
+        assert_eq!(snap_at(middle, center, area, within), None);
+    }
+
+    #[test]
+    fn nothing_is_tiled_without_windows_or_tiling() {
+        for area in AREAS {
+            for tiling in Tiling::ALL {
+                assert!(tile(tiling, 0, area, GAP).is_empty());
+            }
+            assert!(tile(Tiling::Off, 3, area, GAP).is_empty());
+        }
+    }
+
+    #[test]
+    fn one_window_fills_the_area() {
+        for area in AREAS {
+            let inside = area.inset(GAP);
+            assert_eq!(tile(Tiling::Columns, 1, area, GAP), vec![inside]);
+            assert_eq!(tile(Tiling::MasterStack, 1, area, GAP), vec![inside]);
+        }
+    }
+
+    // Tiles are inside the area, gap from its edges and at least gap from each other.
+    fn check_tiles(tiles:&[Rect], area:Rect) {
+        let inside = area.inset(GAP);
+        for (i, a) in tiles.iter().enumerate() {
+            assert!(a.width > 0.0 && a.height > 0.0, "{a:?}");
+            assert!(a.x >= inside.x - 1e-9 && a.y >= inside.y - 1e-9, "{a:?} in {area:?}");
+            assert!(right(*a) <= right(inside) + 1e-9 && bottom(*a) <= bottom(inside) + 1e-9, "{a:?} in {area:?}");
+            for b in &tiles[i + 1..] {
+                assert!(!overlap(a.inset(-GAP / 2.0 + 1e-6), b.inset(-GAP / 2.0 + 1e-6)), "{a:?} and {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn columns_share_the_width() {
+        for area in AREAS {
+            for count in 2..=5 {
+                let tiles = tile(Tiling::Columns, count, area, GAP);
+                assert_eq!(tiles.len(), count);
+                check_tiles(&tiles, area);
+                assert!(tiles.iter().all(|tile| close(tile.width, tiles[0].width) && tile.height == area.height - 2.0 * GAP));
+                assert!(tiles.windows(2).all(|pair| close(pair[1].x - right(pair[0]), GAP)));
+                assert!(close(right(tiles[count - 1]), right(area) - GAP));
+            }
+        }
+    }
+
+    #[test]
+    fn master_stack_keeps_the_first_window_large() {
+        for area in AREAS {
+            for count in 2..=5 {
+                let tiles = tile(Tiling::MasterStack, count, area, GAP);
+                assert_eq!(tiles.len(), count);
+                check_tiles(&tiles, area);
+                let (master, stack) = (tiles[0], &tiles[1..]);
+                assert!(close(master.height, area.height - 2.0 * GAP));
+                assert!(master.width > stack[0].width);
+                assert!(stack.iter().all(|tile| close(tile.x, right(master) + GAP) && close(right(*tile), right(area) - GAP)));
+                assert!(stack.windows(2).all(|pair| close(pair[1].y - bottom(pair[0]), GAP)));
+                assert!(close(bottom(stack[stack.len() - 1]), bottom(area) - GAP));
+            }
+        }
+    }
+
+    #[test]
+    fn overview_fits_windows_without_growing_them() {
+        let area = AREAS[0];
+        let windows = [Rect::new(0.0, 0.0, 400.0, 300.0), Rect::new(50.0, 50.0, 1800.0, 1000.0), Rect::new(0.0, 0.0, 801.0, 601.0)];
+        let placed = overview(&windows, area, GAP);
+        assert_eq!(placed.len(), windows.len());
+        assert_eq!((placed[0].width, placed[0].height), (400.0, 300.0));
+        for (window, rect) in windows.iter().zip(&placed) {
+            assert!(close(rect.width / rect.height, window.width / window.height));
+            assert!(rect.width <= window.width && rect.height <= window.height);
+        }
+        for (i, a) in placed.iter().enumerate() {
+            assert!(placed[i + 1..].iter().all(|b| !overlap(*a, *b)));
+        }
+        assert!(overview(&[], area, GAP).is_empty());
+    }
+}
@@ -9,37 +9,64 @@ use leptos_meta::*;
 use leptos_router::*;
 pub mod fallback;
 pub mod error_template;
-pub mod client_state;
 #[cfg(feature="ssr")]
 pub mod backend_utils;
 #[cfg(feature="ssr")]
 pub mod server_state;
-pub mod user_msg;
+#[cfg(feature="ssr")]
+pub mod session;
 pub mod topbar;
 pub mod taskbar;
 pub mod desktop;
+pub mod finder;
 pub mod file_system;
+pub mod search;
+pub mod clock;
+pub mod permissions;
+pub mod mime;
+pub mod import;
+pub mod zip;
+pub mod export;
+pub mod archive;
+pub mod vfs;
+pub mod remote_store;
+pub mod shell;
+pub mod snapshot;
+pub mod config;
+pub mod events;
+pub mod accounts;
+pub mod login;
+pub mod power;
+pub mod settings;
+pub mod theme;
+pub mod notifications;
+pub mod dialogs;
+pub mod menu_extras;
+pub mod preferences;
+pub mod layout;
+pub mod windows;
+pub mod workspaces;
+pub mod session_restore;
+pub mod routes;
+pub mod mobile;
 use file_system::SystemRuntime;
 #[component]
 pub fn App() -> impl IntoView {
     provide_meta_context();
- 
     view! {
         <Stylesheet id="leptos" href="/pkg/site_os.css"/>
-        <Stylesheet id="googlefont" href="https://fonts.googleapis.com/css?family=Lunasima"/>
         <Link rel="shortcut icon" type_="image/ico" href="/favicon.ico"/>
         <Meta name="description" content="SiteOs is a site that looks like an os."/>
         <Title text="site_os"/>
         //<DisableRightClick/>
         <Router>
             <main>
-            <Body attr:style="
-            font-family: 'Lunasima';
-            color:white;
-            background-image: url(bg.png);
-            background-repeat: repeat;"/>
                 <Routes>
                     <Route path="" view=|| view!{<Desktop/>}/>
+                    // Deep links, see routes::Launch.
+                    <Route path="/app/:id" view=|| view!{<Desktop/>}/>
+                    <Route path="/open/*path" view=|| view!{<Desktop/>}/>
+                    <Route path="/desktop/:workspace" view=|| view!{<Desktop/>}/>
                 </Routes>
             </main>
         </Router>
@@ -50,92 +77,111 @@ pub fn App() -> impl IntoView {
 
 #[component]
 pub fn Desktop() -> impl IntoView {
+    let Some(mut runtime) = request_runtime() else {
+        let config = desktop_config();
+        let mut appearance = settings::Appearance::default();
+        appearance.theme = theme::builtin_name(config.theme).to_string();
+        let theme = theme::Theme::builtin(&appearance.theme).unwrap_or_default();
+        return view!{
+            <AppearanceBody theme appearance wallpaper_url=Some(config.wallpaper)/>
+            <login::LoginScreen/>
+        }.into_view();
+    };
+    // Wallpapers in the FileSystem only get a url in the browser, see settings::provide_settings.
+    let appearance = runtime.settings().appearance.clone();
+    let theme = theme::load_or_default(runtime.file_system_mut(), &appearance.theme);
+    let wallpaper_url = match runtime.wallpaper() {
+        settings::Wallpaper::Public(url) => Some(url.clone()),
+        settings::Wallpaper::File(_) => None,
+    };
+    // Nothing of the desktop goes into the page until the session is unlocked, the lock page
+    // reloads once it is.
+    if request_locked() {
+        return view!{
+            <AppearanceBody theme appearance wallpaper_url/>
+            <power::LockedPage/>
+        }.into_view();
+    }
+    let apps = desktop_config().apps;
+    let launch = routes::requested_launch(&apps);
     view!{
-        <OperatingSystemProvider>
+        <AppearanceBody theme appearance wallpaper_url/>
+        <OperatingSystemProvider runtime launch apps>
         <topbar::TopBar/>
+        <search::Spotlight/>
         <desktop::Desktop/>
+        <windows::WindowLayer/>
         <taskbar::TaskBar/>
+        <notifications::NotificationCenter/>
+        <dialogs::DialogLayer/>
+        <power::PowerOverlay/>
         </OperatingSystemProvider>
+    }.into_view()
+}
+
+// The theme's custom properties, font and wallpaper as the page is first rendered. The client
+// keeps them up to date from then on, see settings::provide_settings.
+#[component]
+fn AppearanceBody(theme:theme::Theme, appearance:settings::Appearance, wallpaper_url:Option<String>) -> impl IntoView {
+    let style = settings::body_style(&theme, &appearance, wallpaper_url.as_deref());
+    view!{
+        {theme.font.url.map(|url| view!{<Stylesheet id=theme::FONT_STYLESHEET_ID href=url/>})}
+        <Body attr:style=style attr:data-theme=appearance.theme/>
     }
 }
 
+fn request_locked() -> bool {
+    #[cfg(feature="ssr")]
+    if let (Some(session), Some(state)) = (session::current_session(), session::server_state()) {
+        return state.is_locked(&session);
+    }
+    false
+}
 
+// The runtime the desktop is rendered with, None until the visitor logs in.
+// Only the server renders non-island components, the client build falls back to the default.
+fn request_runtime() -> Option<SystemRuntime> {
+    #[cfg(feature="ssr")]
+    if let (Some(session), Some(state)) = (session::current_session(), session::server_state()) {
+        return state.runtime(&session);
+    }
+    Some(initial_runtime())
+}
+
+// The desktop described by the built in desktop.toml, for when there's no server to ask.
+pub fn initial_runtime() -> SystemRuntime {
+    config::DesktopConfig::default().runtime().expect("desktop.toml to lay out a file system")
+}
+
+// The config the server was started with.
+fn desktop_config() -> config::DesktopConfig {
+    #[cfg(feature="ssr")]
+    if let Some(state) = session::server_state() {
+        return state.config().clone();
+    }
+    config::DesktopConfig::default()
+}
 
+// Built by the server and serialized into the page with the island, so the hydrated state is
+// the state the HTML was rendered from.
 #[island]
-pub fn OperatingSystemProvider(children:Children) -> impl IntoView {
-    use file_system::*;
-    provide_context::<RwSignal<SystemRuntime>>(create_rw_signal(SystemRuntime::new(
-        {
-            let mut file_system = FileSystem::new();
-            file_system.add_file("/finder".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/folder.png".to_string(),
-            });
-            file_system.add_file("/browser".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/browser.png".to_string(),
-            });
-            file_system.add_file("/calendar".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/calendar.png".to_string(),
-            });
-            file_system.add_file("/calculator".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/calculator.png".to_string(),
-            });
-             file_system.add_file("/text".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/text.png".to_string(),
-            });
-            file_system.add_file("/csv".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/csv-file.png".to_string(),
-            });
-            file_system.add_file("/picture".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/picture.png".to_string(),
-            });
-            file_system.add_file("/terminal".to_string(),Metadata{
-                accessed:0,
-                created:0,
-                modified:0,
-                file_type:FileType::File,
-                img_src:"/terminal.png".to_string(),
-            });
-            file_system
-        },
-        vec![
-            "/finder".to_string(),
-            "/browser".to_string(),
-            "/calendar".to_string(),
-            "/calculator".to_string(),
-            "/text".to_string(),
-            "/csv".to_string(),
-            "/picture".to_string(),
-            "/terminal".to_string(),
-            ]
-    )));
+pub fn OperatingSystemProvider(runtime:SystemRuntime, launch:Option<routes::Launch>, apps:Vec<config::AppConfig>, children:Children) -> impl IntoView {
+    provide_context::<RwSignal<search::SpotlightOpen>>(create_rw_signal(search::SpotlightOpen(false)));
+    provide_context::<RwSignal<import::Imports>>(create_rw_signal(import::Imports::default()));
+    provide_context::<RwSignal<desktop::Selection>>(create_rw_signal(desktop::Selection::default()));
+    let system = create_rw_signal(runtime);
+    provide_context::<RwSignal<SystemRuntime>>(system);
+    vfs::provide_sync(system);
+    let notifications = notifications::provide_notifications();
+    dialogs::provide_dialogs();
+    menu_extras::provide_menu_extras();
+    windows::provide_overview();
+    session_restore::provide_session_restore(system);
+    events::provide_events(system, notifications);
+    settings::provide_settings(system);
+    power::provide_power(system);
+    mobile::provide_mobile_layout(system, apps.clone());
+    routes::provide_routes(system, launch, apps);
 
     children()
 }
@@ -0,0 +1,74 @@
+use super::*;
+
+// Shown instead of the desktop until the visitor logs in, registers or continues as a guest.
+// The page is reloaded afterwards so the server renders the account's desktop.
+#[island]
+pub fn LoginScreen() -> impl IntoView {
+    let username = create_rw_signal(String::new());
+    let password = create_rw_signal(String::new());
+    let error = create_rw_signal(None::<String>);
+    let pending = create_rw_signal(false);
+    let submit = move |action:LoginAction| {
+        pending.set(true);
+        error.set(None);
+        let (username,password) = (username.get_untracked(),password.get_untracked());
+        spawn_local(async move {
+            let result = match action {
+                LoginAction::Login => accounts::login(username, password).await,
+                LoginAction::Register => accounts::register(username, password).await,
+                LoginAction::Guest => accounts::login_as_guest().await,
+            };
+            match result {
+                Ok(_) => _ = window().location().reload(),
+                Err(err) => {
+                    error.set(Some(server_error_message(err)));
+                    pending.set(false);
+                },
+            }
+        });
+    };
+    view!{
+        <div class="fixed inset-0 flex items-center justify-center backdrop-blur-theme bg-backdrop bg-opacity-40">
+        <form class="w-72 flex flex-col gap-2 bg-surface bg-opacity-50 rounded-theme p-4"
+        on:submit=move |ev| {
+            ev.prevent_default();
+            submit(LoginAction::Login);
+        }>
+            <topbar::TopLeftEye/>
+            <input class="rounded-theme px-2 py-1 bg-field outline-none" placeholder="Username"
+            autocomplete="username"
+            prop:value=username
+            on:input=move |ev| username.set(event_target_value(&ev))/>
+            <input class="rounded-theme px-2 py-1 bg-field outline-none" placeholder="Password" type="password"
+            autocomplete="current-password"
+            prop:value=password
+            on:input=move |ev| password.set(event_target_value(&ev))/>
+            {move || error().map(|error| view!{<p class="text-sm text-danger">{error}</p>})}
+            <div class="flex gap-2">
+                <button type="submit" class="flex-1 rounded-theme bg-selected hover:bg-highlight"
+                disabled=pending>"Log In"</button>
+                <button type="button" class="flex-1 rounded-theme bg-selected hover:bg-highlight"
+                disabled=pending
+                on:click=move |_| submit(LoginAction::Register)>"Register"</button>
+            </div>
+            <button type="button" class="text-sm underline" disabled=pending
+            on:click=move |_| submit(LoginAction::Guest)>"Continue as Guest"</button>
+        </form>
+        </div>
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum LoginAction{
+    Login,
+    Register,
+    Guest,
+}
+
+// The message a server function failed with, without the variant's prefix.
+pub fn server_error_message(err:ServerFnError) -> String {
+    match err {
+        ServerFnError::ServerError(message) => message,
+        err => err.to_string(),
+    }
+}
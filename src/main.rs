@@ -1,6 +1,6 @@
 #[cfg(feature = "ssr")]
 mod ssr_imports {
-    pub use axum::{routing::get, Router};
+    pub use axum::{routing::{get, post}, Router};
     pub use site_os::fallback::file_and_error_handler;
     pub use leptos::*;
     pub use leptos_axum::{generate_route_list, LeptosRoutes};
@@ -19,13 +19,34 @@ async fn main() {
     let leptos_options = conf.leptos_options;
     let addr = leptos_options.site_addr;
     let routes = generate_route_list(App);
+    let desktop_config = match site_os::config::DesktopConfig::load()
+        .and_then(|config| config.runtime().map(|_| config)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        },
+    };
 
     // build our application with a route
     let app = Router::new()
         .route("/favicon.ico", get(file_and_error_handler))
+        .route("/api/*fn_name", post(leptos_axum::handle_server_fns))
+        .route(site_os::events::EVENTS_URL, get(site_os::events::event_stream))
         .leptos_routes(&leptos_options, routes, App)
         .fallback(file_and_error_handler)
-        .layer(axum::Extension(site_os::server_state::ServerState::default()))
+        // Every request gets its session once, before any handler or component reads it.
+        .layer(axum::middleware::from_fn(site_os::session::with_session))
+        .layer(tower_cookies::CookieManagerLayer::new())
+        .layer(axum::Extension(site_os::server_state::ServerState::new(
+            desktop_config,
+            std::sync::Arc::new(site_os::accounts::MemoryUserStore::default()),
+        )))
+        // Keeps session cookies out of logs.
+        .layer(tower_http::sensitive_headers::SetSensitiveHeadersLayer::new([
+            http::header::COOKIE,
+            http::header::SET_COOKIE,
+        ]))
         .with_state(leptos_options);
 
     // run our app with hyper
@@ -0,0 +1,223 @@
+use super::*;
+use std::rc::Rc;
+use chrono::{Datelike,Months,NaiveDate};
+
+// How often the topbar clock is redrawn.
+const CLOCK_TICK_MS: u32 = 1000;
+
+// An icon an app adds to the right of the topbar, with a dropdown opened by clicking it.
+//
+//     menu_extras::register(extras, MenuExtra::new("battery", "🔋", "Battery", || view!{<p>"100%"</p>}.into_view()));
+#[derive(Clone)]
+pub struct MenuExtra{
+    // Registering another extra with the same id replaces this one.
+    pub id:String,
+    // A url starting with / (served from public/), or text like an emoji.
+    pub icon:String,
+    pub title:String,
+    pub content:Rc<dyn Fn() -> View>,
+}
+
+impl MenuExtra{
+    pub fn new(id:impl Into<String>, icon:impl Into<String>, title:impl Into<String>, content:impl Fn() -> View + 'static) -> Self {
+        Self{id:id.into(),icon:icon.into(),title:title.into(),content:Rc::new(content)}
+    }
+}
+
+impl std::fmt::Debug for MenuExtra{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MenuExtra").field("id",&self.id).field("icon",&self.icon).finish_non_exhaustive()
+    }
+}
+
+// Extras in the order they were registered.
+#[derive(Debug,Clone,Default)]
+pub struct MenuExtras(pub Vec<MenuExtra>);
+
+// Called once by OperatingSystemProvider.
+pub fn provide_menu_extras() -> RwSignal<MenuExtras> {
+    let extras = create_rw_signal(MenuExtras::default());
+    provide_context(extras);
+    extras
+}
+
+pub fn register(extras:RwSignal<MenuExtras>, extra:MenuExtra) {
+    extras.update(|extras| match extras.0.iter_mut().find(|registered| registered.id == extra.id) {
+        Some(registered) => *registered = extra,
+        None => extras.0.push(extra),
+    });
+}
+
+pub fn unregister(extras:RwSignal<MenuExtras>, id:&str) {
+    extras.update(|extras| extras.0.retain(|extra| extra.id != id));
+}
+
+// Which dropdown of the status area is showing.
+#[derive(Debug,Clone,PartialEq)]
+enum StatusMenu{
+    None,
+    Extra(String),
+    Network,
+    Calendar,
+}
+
+// The right of the topbar: apps' extras, the network indicator and the clock.
+#[island]
+pub fn StatusArea() -> impl IntoView {
+    let extras = expect_context::<RwSignal<MenuExtras>>();
+    let open = create_rw_signal(StatusMenu::None);
+    let area = create_node_ref::<leptos::html::Div>();
+    // Clicking anywhere outside closes the open dropdown.
+    let handle = window_event_listener(ev::click, move |ev| {
+        let Some(area) = area.get_untracked() else { return };
+        let target = event_target::<web_sys::Node>(&ev);
+        if !area.contains(Some(&target)) {
+            open.set(StatusMenu::None);
+        }
+    });
+    on_cleanup(move || handle.remove());
+    let toggle = move |menu:StatusMenu| open.update(|open| *open = if *open == menu { StatusMenu::None } else { menu });
+    view!{
+        <div class="flex" _ref=area>
+            <For
+            each=move || extras.with(|extras| extras.0.iter().map(|extra| extra.id.clone()).collect::<Vec<String>>())
+            key=|id| id.clone()
+            children=move |id| {
+                let Some(extra) = extras.with_untracked(|extras| extras.0.iter().find(|extra| extra.id == id).cloned()) else {
+                    return view!{}.into_view();
+                };
+                let menu = StatusMenu::Extra(id);
+                let is_open = {
+                    let menu = menu.clone();
+                    move || open.with(|open| *open == menu)
+                };
+                let content = extra.content.clone();
+                view!{
+                    <StatusItem title=extra.title.clone() is_open=Signal::derive(is_open.clone()) on_click=move |_| toggle(menu.clone())>
+                        <ExtraIcon icon=extra.icon.clone()/>
+                    </StatusItem>
+                    {move || is_open().then(|| view!{<StatusDropDown>{content()}</StatusDropDown>})}
+                }.into_view()
+            }
+            />
+            <NetworkStatus open toggle=Callback::new(toggle)/>
+            <Clock open toggle=Callback::new(toggle)/>
+        </div>
+    }
+}
+
+#[component]
+fn StatusItem(title:String, #[prop(into)] is_open:Signal<bool>, #[prop(into)] on_click:Callback<()>, children:Children) -> impl IntoView {
+    view!{
+        <button class="pl-3 pr-3 rounded-theme" title=title
+        class=("bg-highlight", is_open)
+        on:click=move |_| on_click.call(())>
+            {children()}
+        </button>
+    }
+}
+
+#[component]
+fn StatusDropDown(children:Children) -> impl IntoView {
+    view!{
+        <div class="absolute top-7 right-2 z-40 min-w-[12rem] p-2 rounded-theme bg-surface bg-opacity-50 backdrop-blur-theme">
+            {children()}
+        </div>
+    }
+}
+
+#[component]
+fn ExtraIcon(icon:String) -> impl IntoView {
+    if icon.starts_with('/') {
+        view!{<img class="h-4 inline" src=icon/>}.into_view()
+    } else {
+        icon.into_view()
+    }
+}
+
+// Whether the browser is online, from navigator.onLine and its online/offline events.
+#[component]
+fn NetworkStatus(open:RwSignal<StatusMenu>, toggle:Callback<StatusMenu>) -> impl IntoView {
+    let online = create_rw_signal(true);
+    create_effect(move |_| online.set(window().navigator().on_line()));
+    let handles = [
+        window_event_listener(ev::online, move |_| online.set(true)),
+        window_event_listener(ev::offline, move |_| online.set(false)),
+    ];
+    on_cleanup(move || handles.into_iter().for_each(|handle| handle.remove()));
+    let is_open = move || open.with(|open| *open == StatusMenu::Network);
+    view!{
+        <StatusItem title="Network".to_string() is_open=Signal::derive(is_open) on_click=move |_| toggle.call(StatusMenu::Network)>
+            <span class=("text-danger", move || !online())>{move || if online() { "●" } else { "○" }}</span>
+        </StatusItem>
+        {move || is_open().then(|| view!{
+            <StatusDropDown>
+                <p>{move || if online() { "Connected" } else { "Offline" }}</p>
+                <p class="text-sm text-muted">{move || if online() {
+                    "Changes are synced with the server."
+                } else {
+                    "Changes are kept here and synced when the connection is back."
+                }}</p>
+            </StatusDropDown>
+        })}
+    }
+}
+
+// The time in the format chosen in System Preferences, opening a calendar.
+#[component]
+fn Clock(open:RwSignal<StatusMenu>, toggle:Callback<StatusMenu>) -> impl IntoView {
+    let settings = expect_context::<settings::SettingsStore>();
+    let format = settings.select(|settings| settings.date_time.clone());
+    let now = create_rw_signal(chrono::Utc::now().timestamp_millis());
+    let interval = store_value(None::<gloo::timers::callback::Interval>);
+    // Timers only exist in the browser. Dropping the Interval on cleanup stops it.
+    create_effect(move |_| {
+        interval.set_value(Some(gloo::timers::callback::Interval::new(CLOCK_TICK_MS, move || {
+            now.set(chrono::Utc::now().timestamp_millis());
+        })));
+    });
+    on_cleanup(move || interval.set_value(None));
+    let is_open = move || open.with(|open| *open == StatusMenu::Calendar);
+    view!{
+        <StatusItem title="Calendar".to_string() is_open=Signal::derive(is_open) on_click=move |_| toggle.call(StatusMenu::Calendar)>
+            {move || format.with(|format| format.format(now()))}
+        </StatusItem>
+        {move || is_open().then(|| view!{<StatusDropDown><Calendar/></StatusDropDown>})}
+    }
+}
+
+// A month at a time, starting with today's.
+#[component]
+fn Calendar() -> impl IntoView {
+    let today = chrono::Local::now().date_naive();
+    let month = create_rw_signal(today.with_day(1).unwrap_or(today));
+    let days = move || month_grid(month());
+    view!{
+        <div class="flex flex-col gap-2 text-sm">
+            <div class="flex justify-between items-center">
+                <button class="px-2 rounded-theme hover:bg-highlight"
+                on:click=move |_| month.update(|month| *month = month.checked_sub_months(Months::new(1)).unwrap_or(*month))>"‹"</button>
+                <span class="font-bold">{move || month().format("%B %Y").to_string()}</span>
+                <button class="px-2 rounded-theme hover:bg-highlight"
+                on:click=move |_| month.update(|month| *month = month.checked_add_months(Months::new(1)).unwrap_or(*month))>"›"</button>
+            </div>
+            <div class="grid grid-cols-7 gap-1 text-center">
+                {["S","M","T","W","T","F","S"].into_iter().map(|day| view!{<span class="text-muted">{day}</span>}).collect_view()}
+                {move || days().into_iter().map(|day| match day {
+                    Some(day) => view!{
+                        <span class="rounded-full" class=("bg-selected", day == today)>{day.day()}</span>
+                    }.into_view(),
+                    None => view!{<span/>}.into_view(),
+                }).collect_view()}
+            </div>
+        </div>
+    }
+}
+
+// The days of the month starting at first, with None before the first so it lands on its
+// weekday in a Sunday first week.
+fn month_grid(first:NaiveDate) -> Vec<Option<NaiveDate>> {
+    let blanks = first.weekday().num_days_from_sunday() as usize;
+    let days = first.iter_days().take_while(|day| day.month() == first.month());
+    std::iter::repeat(None).take(blanks).chain(days.map(Some)).collect()
+}
@@ -0,0 +1,66 @@
+// Mime types for files coming from outside the FileSystem.
+
+pub const OCTET_STREAM: &'static str = "application/octet-stream";
+
+// Picks a mime type from the content's magic bytes, then the file extension, then what the
+// browser reported, in that order. Browsers report "" for most extensions they don't know.
+pub fn detect(name:&str, content:&[u8], reported:&str) -> String {
+    sniff(content)
+        .or_else(|| from_extension(name))
+        .map(|mime| mime.to_string())
+        .unwrap_or_else(|| if reported.is_empty() { OCTET_STREAM.to_string() } else { reported.to_string() })
+}
+
+pub fn sniff(content:&[u8]) -> Option<&'static str> {
+    const SIGNATURES: [(&[u8],&str);8] = [
+        (b"\x89PNG\r\n\x1a\n","image/png"),
+        (b"\xff\xd8\xff","image/jpeg"),
+        (b"GIF87a","image/gif"),
+        (b"GIF89a","image/gif"),
+        (b"%PDF-","application/pdf"),
+        (b"PK\x03\x04","application/zip"),
+        (b"\x1f\x8b","application/gzip"),
+        (b"<svg","image/svg+xml"),
+    ];
+    SIGNATURES.iter()
+        .find(|(magic,_)| content.starts_with(magic))
+        .map(|(_,mime)| *mime)
+}
+
+pub fn from_extension(name:&str) -> Option<&'static str> {
+    let (_,extension) = name.rsplit_once('.')?;
+    Some(match extension.to_lowercase().as_str() {
+        "txt" | "md" | "rs" | "toml" | "log" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "tar" => "application/x-tar",
+        "gz" | "tgz" => "application/gzip",
+        _ => return None,
+    })
+}
+
+// Types the Finder opens as folders, see archive::MountedArchive.
+pub fn is_archive(mime:&str) -> bool {
+    matches!(mime, "application/zip" | "application/x-tar" | "application/gzip")
+}
+
+// The icon in public/ the desktop and dock use for a mime type.
+pub fn icon(mime:&str) -> &'static str {
+    if mime == "text/csv" {
+        "/csv-file.png"
+    } else if mime.starts_with("image/") {
+        "/picture.png"
+    } else {
+        "/text.png"
+    }
+}
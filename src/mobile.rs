@@ -0,0 +1,292 @@
+use super::*;
+use file_system::SystemRuntime;
+use config::AppConfig;
+use gloo::timers::callback::Timeout;
+
+// Viewports at most this wide, in CSS px, get the touch layout: a home screen of apps in place
+// of the desktop, full screen windows and a status bar in place of the menu bar.
+pub const MAX_WIDTH: f64 = 768.0;
+// How long a finger has to rest for a long press, which stands in for a right click.
+const LONG_PRESS_MS: u32 = 500;
+// How far a finger can wander before it's a drag rather than a press or a tap, in px.
+const SLOP: f64 = 10.0;
+// The back gesture is a swipe to the right starting this close to the left edge...
+const BACK_EDGE: f64 = 24.0;
+// ...going at least this far.
+const BACK_DISTANCE: f64 = 96.0;
+
+// Whether the touch layout is in use. The server can't know the viewport, so pages are
+// rendered with the desktop layout and switch once they're in the browser.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct MobileLayout(pub bool);
+
+// The apps of the desktop config, what the home screen shows.
+#[derive(Debug,Clone,PartialEq)]
+pub struct HomeScreenApps(pub Vec<AppConfig>);
+
+// Provides the MobileLayout, switching it as the viewport is resized, and listens for the
+// touch gestures. Called once by OperatingSystemProvider.
+pub fn provide_mobile_layout(system:RwSignal<SystemRuntime>, apps:Vec<AppConfig>) -> RwSignal<MobileLayout> {
+    let layout = create_rw_signal(MobileLayout(false));
+    provide_context(layout);
+    provide_context(HomeScreenApps(apps));
+    let update = move || {
+        let mobile = MobileLayout(windows::desktop_area().width <= MAX_WIDTH);
+        if layout.get_untracked() != mobile {
+            layout.set(mobile);
+        }
+    };
+    create_effect(move |_| update());
+    let resize = window_event_listener(ev::resize, move |_| update());
+    on_cleanup(move || resize.remove());
+    listen_for_long_press();
+    listen_for_back_gesture(system, layout);
+    layout
+}
+
+// Leaves the focused app for the one used before it, or for the home screen once there's none.
+// Apps keep running, their windows are only minimized.
+pub fn go_back(system:RwSignal<SystemRuntime>) {
+    system.update(|system| {
+        let Some(id) = system.windows().focused().map(|window| window.id) else { return };
+        system.windows_mut().minimize(id);
+    });
+}
+
+// A finger resting on the page.
+struct Press{
+    target:web_sys::EventTarget,
+    start:(f64,f64),
+    // Dropping it cancels the long press.
+    timer:Option<Timeout>,
+    // Whether a contextmenu event went out for it, by the timer or by the browser.
+    fired:bool,
+}
+
+// Turns a one finger long press into a contextmenu event on what's pressed, like a right click.
+// The click the browser sends when the finger is lifted is swallowed. Browsers that already
+// send contextmenu for long presses only get theirs.
+fn listen_for_long_press() {
+    let press = store_value(None::<Press>);
+    let touchstart = window_event_listener(ev::touchstart, move |ev| {
+        let touches = ev.touches();
+        let (Some(touch), Some(target)) = (touches.get(0).filter(|_| touches.length() == 1), ev.target()) else {
+            press.set_value(None);
+            return;
+        };
+        let start = (touch.client_x() as f64, touch.client_y() as f64);
+        // Only sets fired, the Timeout can't be dropped while it runs.
+        let timer = Timeout::new(LONG_PRESS_MS, move || {
+            let pressed = press.try_update_value(|press| press.as_mut()
+                .filter(|press| !press.fired)
+                .map(|press| {
+                    press.fired = true;
+                    (press.target.clone(), press.start)
+                }))
+                .flatten();
+            let Some((target, (x, y))) = pressed else { return };
+            let mut init = web_sys::MouseEventInit::new();
+            init.bubbles(true).cancelable(true).client_x(x as i32).client_y(y as i32);
+            if let Ok(event) = web_sys::MouseEvent::new_with_mouse_event_init_dict("contextmenu", &init) {
+                _ = target.dispatch_event(&event);
+            }
+        });
+        press.set_value(Some(Press{target,start,timer:Some(timer),fired:false}));
+    });
+    let touchmove = window_event_listener(ev::touchmove, move |ev| {
+        let Some(touch) = ev.touches().get(0) else { return };
+        let (x, y) = (touch.client_x() as f64, touch.client_y() as f64);
+        press.update_value(|pressed| {
+            if pressed.as_ref().is_some_and(|press| !press.fired && (x - press.start.0).hypot(y - press.start.1) > SLOP) {
+                *pressed = None;
+            }
+        });
+    });
+    let touchend = window_event_listener(ev::touchend, move |ev| {
+        if press.try_update_value(Option::take).flatten().is_some_and(|press| press.fired) {
+            ev.prevent_default();
+        }
+    });
+    let touchcancel = window_event_listener(ev::touchcancel, move |_| press.set_value(None));
+    let contextmenu = window_event_listener(ev::contextmenu, move |_| press.update_value(|press| {
+        if let Some(press) = press.as_mut() {
+            press.fired = true;
+            press.timer = None;
+        }
+    }));
+    on_cleanup(move || [touchstart, touchmove, touchend, touchcancel, contextmenu].into_iter().for_each(|handle| handle.remove()));
+}
+
+// In the touch layout, a one finger swipe in from the left edge goes back, see go_back.
+fn listen_for_back_gesture(system:RwSignal<SystemRuntime>, layout:RwSignal<MobileLayout>) {
+    let start = store_value(None::<f64>);
+    let touchstart = window_event_listener(ev::touchstart, move |ev| {
+        let touches = ev.touches();
+        start.set_value((layout.get_untracked().0 && touches.length() == 1)
+            .then(|| touches.get(0).map(|touch| touch.client_x() as f64))
+            .flatten()
+            .filter(|x| *x <= BACK_EDGE));
+    });
+    let touchend = window_event_listener(ev::touchend, move |ev| {
+        let Some(start) = start.try_update_value(Option::take).flatten() else { return };
+        let Some(touch) = ev.changed_touches().get(0) else { return };
+        if touch.client_x() as f64 - start >= BACK_DISTANCE {
+            go_back(system);
+        }
+    });
+    on_cleanup(move || [touchstart, touchend].into_iter().for_each(|handle| handle.remove()));
+}
+
+// The apps in home screen order: the ones the user arranged first, then the rest in config
+// order.
+pub fn home_screen_order(apps:&[AppConfig], order:&[String]) -> Vec<AppConfig> {
+    let arranged = order.iter().filter_map(|path| apps.iter().find(|app| &app.path == path));
+    let rest = apps.iter().filter(|app| !order.contains(&app.path));
+    arranged.chain(rest).cloned().collect()
+}
+
+// An icon of the home screen being dragged.
+#[derive(Debug,Clone,PartialEq)]
+struct IconDrag{
+    path:String,
+    start:(f64,f64),
+    offset:(f64,f64),
+    // Once it went further than SLOP it's a drag and no longer a tap.
+    moved:bool,
+}
+
+// The touch layout's desktop: a grid of the apps. Tapping an app opens it, long pressing one
+// shows its windows and dragging one onto another moves it there.
+#[component]
+pub fn HomeScreen() -> impl IntoView {
+    let settings = expect_context::<settings::SettingsStore>();
+    let HomeScreenApps(apps) = expect_context::<HomeScreenApps>();
+    let order = settings.select(|settings| settings.home_screen.clone());
+    let apps = create_memo(move |_| order.with(|order| home_screen_order(&apps, order)));
+    let drag = create_rw_signal(None::<IconDrag>);
+    // The app the dragged one would be dropped on.
+    let over = create_rw_signal(None::<String>);
+    let drop_on = move |target:String| {
+        let Some(IconDrag{path,..}) = drag.get_untracked() else { return };
+        let mut paths = apps.get_untracked().into_iter().map(|app| app.path).collect::<Vec<String>>();
+        let (Some(from), Some(to)) = (paths.iter().position(|p| *p == path), paths.iter().position(|p| *p == target)) else { return };
+        let moved = paths.remove(from);
+        paths.insert(to, moved);
+        settings.update(|settings| settings.home_screen = paths);
+    };
+    view!{
+        <div class="grid grid-cols-4 gap-6 p-6 pt-10 justify-items-center">
+            <For
+            each=apps
+            key=|app| app.path.clone()
+            children=move |app| view!{<HomeScreenIcon app drag over drop_on/>}
+            />
+        </div>
+    }
+}
+
+#[component]
+fn HomeScreenIcon(app:AppConfig, drag:RwSignal<Option<IconDrag>>, over:RwSignal<Option<String>>, #[prop(into)] drop_on:Callback<String>) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let overview = expect_context::<RwSignal<windows::Overview>>();
+    let AppConfig{id,path,icon} = app;
+    let dragged = {
+        let path = path.clone();
+        create_memo(move |_| drag.with(|drag| drag.as_ref().filter(|drag| drag.path == path).map(|drag| drag.offset)))
+    };
+    let targeted = {
+        let path = path.clone();
+        move || over.with(|over| over.as_ref() == Some(&path))
+    };
+    let style = move || dragged().map(|(x, y)| format!("transform:translate({x}px,{y}px);z-index:10;")).unwrap_or_default();
+    // The app under the pointer other than the dragged one.
+    let app_at = {
+        let path = path.clone();
+        move |x:f64, y:f64| document().elements_from_point(x as f32, y as f32).iter()
+            .filter_map(|element| element.dyn_into::<web_sys::Element>().ok())
+            .filter_map(|element| element.closest("[data-home-app]").ok().flatten())
+            .filter_map(|element| element.get_attribute("data-home-app"))
+            .find(|other| *other != path)
+    };
+    let down_path = path.clone();
+    let tap_path = path.clone();
+    let press_path = path.clone();
+    view!{
+        <button class="relative w-16 flex flex-col items-center gap-1 rounded-theme touch-none select-none"
+        class=("ring-2", targeted.clone())
+        class=("ring-highlight", targeted)
+        style=style
+        data-home-app=path.clone()
+        on:pointerdown=move |ev| {
+            if let Some(target) = ev.current_target().and_then(|target| target.dyn_into::<web_sys::Element>().ok()) {
+                _ = target.set_pointer_capture(ev.pointer_id());
+            }
+            let start = (ev.client_x() as f64, ev.client_y() as f64);
+            drag.set(Some(IconDrag{path:down_path.clone(),start,offset:(0.0, 0.0),moved:false}));
+        }
+        on:pointermove=move |ev| {
+            let (x, y) = (ev.client_x() as f64, ev.client_y() as f64);
+            let Some(start) = drag.with_untracked(|drag| drag.as_ref().map(|drag| drag.start)) else { return };
+            let offset = (x - start.0, y - start.1);
+            let moved = drag.with_untracked(|drag| drag.as_ref().is_some_and(|drag| drag.moved)) || offset.0.hypot(offset.1) > SLOP;
+            if moved {
+                drag.update(|drag| if let Some(drag) = drag.as_mut() {
+                    drag.offset = offset;
+                    drag.moved = true;
+                });
+                over.set(app_at(x, y));
+            }
+        }
+        on:pointerup=move |_| {
+            if let Some(target) = over.get_untracked() {
+                drop_on.call(target);
+            }
+            over.set(None);
+            // Left for the click to see whether this was a tap.
+            drag.update(|drag| if let Some(drag) = drag.as_mut() {
+                drag.offset = (0.0, 0.0);
+            });
+        }
+        on:pointercancel=move |_| {
+            over.set(None);
+            drag.set(None);
+        }
+        on:click=move |_| {
+            let tapped = drag.get_untracked().map_or(true, |drag| !drag.moved);
+            drag.set(None);
+            if tapped {
+                system.update(|system| system.run_app(&tap_path));
+            }
+        }
+        on:contextmenu=move |ev| {
+            ev.prevent_default();
+            drag.set(None);
+            let has_windows = system.with_untracked(|system| system.windows().app_windows(&press_path).next().is_some());
+            if has_windows {
+                windows::show_app_windows(overview, press_path.clone());
+            }
+        }>
+            <img class="w-14 h-14 pointer-events-none" src=icon/>
+            <span class="text-xs truncate w-full text-center">{id}</span>
+        </button>
+    }
+}
+
+// The left of the status bar the topbar collapses into: a way back while an app is open and the
+// name of the app in front.
+#[component]
+pub fn StatusBarTitle() -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let title = create_memo(move |_| system.with(|system| system.windows().focused().map(|window| window.title.clone())));
+    view!{
+        <div class="flex items-center min-w-0">
+        <button class="pl-3 pr-3 rounded-theme" title="Back"
+        class=("hidden", move || title().is_none())
+        on:click=move |ev| {
+            ev.stop_propagation();
+            go_back(system);
+        }>"‹"</button>
+        <span class="pl-3 text-sm truncate">{move || title().unwrap_or_default()}</span>
+        </div>
+    }
+}
@@ -0,0 +1,326 @@
+use super::*;
+use std::collections::{BTreeMap,VecDeque};
+
+// Notifications kept for the notification center, oldest are dropped first.
+const HISTORY_LEN: usize = 100;
+// How long a toast stays up unless the notification sets its own timeout.
+pub const TOAST_TIMEOUT_MS: u32 = 5000;
+// Source of notifications that don't come from an app.
+pub const SYSTEM_SOURCE: &'static str = "System";
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Severity{
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity{
+    // The theme color a toast of this severity is marked with.
+    fn border_class(&self) -> &'static str {
+        match self {
+            Self::Info => "border-selected",
+            Self::Success => "border-success",
+            Self::Warning => "border-warning",
+            Self::Error => "border-danger",
+        }
+    }
+}
+
+// A button on a notification. Clicking it runs the callback and dismisses the notification.
+#[derive(Clone)]
+pub struct NotificationAction{
+    pub label:String,
+    pub on_click:Callback<()>,
+}
+
+impl std::fmt::Debug for NotificationAction{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationAction").field("label",&self.label).finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct Notification{
+    // Set by notify.
+    pub id:u64,
+    // The app that sent it, notifications are grouped by this.
+    pub source:String,
+    pub severity:Severity,
+    pub title:String,
+    pub body:String,
+    pub actions:Vec<NotificationAction>,
+    // None keeps the toast up until it's dismissed.
+    pub timeout_ms:Option<u32>,
+    // Milliseconds since the unix epoch, set by notify.
+    pub time:i64,
+    pub read:bool,
+}
+
+impl Notification{
+    // Errors stay up until dismissed, everything else times out after TOAST_TIMEOUT_MS.
+    pub fn new(severity:Severity, title:impl Into<String>) -> Self {
+        Self{
+            id:0,
+            source:SYSTEM_SOURCE.to_string(),
+            severity,
+            title:title.into(),
+            body:String::new(),
+            actions:Vec::new(),
+            timeout_ms:(severity != Severity::Error).then_some(TOAST_TIMEOUT_MS),
+            time:0,
+            read:false,
+        }
+    }
+
+    pub fn with_body(mut self, body:impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn with_source(mut self, source:impl Into<String>) -> Self {
+        self.source = source.into();
+        self
+    }
+
+    pub fn with_action(mut self, label:impl Into<String>, on_click:impl Fn(()) + 'static) -> Self {
+        self.actions.push(NotificationAction{label:label.into(),on_click:Callback::new(on_click)});
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout_ms:Option<u32>) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct Notifications{
+    next_id:u64,
+    // Newest last.
+    history:VecDeque<Notification>,
+    // Ids of the notifications showing as toasts.
+    toasts:Vec<u64>,
+    // Whether the notification center panel is showing.
+    pub center_open:bool,
+}
+
+impl Notifications{
+    pub fn history(&self) -> impl DoubleEndedIterator<Item=&Notification> {
+        self.history.iter()
+    }
+
+    pub fn unread(&self) -> usize {
+        self.history.iter().filter(|notification| !notification.read).count()
+    }
+
+    pub fn get(&self, id:u64) -> Option<&Notification> {
+        self.history.iter().find(|notification| notification.id == id)
+    }
+
+    // The newest toast of each source with how many others from that source are up, sources
+    // with the newest toast first.
+    pub fn toast_groups(&self) -> Vec<(Notification,usize)> {
+        let mut groups : BTreeMap<&str,(u64,usize)> = BTreeMap::new();
+        for id in &self.toasts {
+            let Some(notification) = self.get(*id) else { continue };
+            let group = groups.entry(notification.source.as_str()).or_insert((*id,0));
+            group.0 = group.0.max(*id);
+            group.1 += 1;
+        }
+        let mut groups = groups.into_values()
+            .filter_map(|(id,count)| self.get(id).map(|notification| (notification.clone(),count - 1)))
+            .collect::<Vec<_>>();
+        groups.sort_by(|(a,_),(b,_)| b.id.cmp(&a.id));
+        groups
+    }
+
+    // History grouped by source, newest notification and newest group first.
+    pub fn groups(&self) -> Vec<(String,Vec<Notification>)> {
+        let mut groups : BTreeMap<String,Vec<Notification>> = BTreeMap::new();
+        for notification in self.history.iter().rev() {
+            groups.entry(notification.source.clone()).or_default().push(notification.clone());
+        }
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort_by(|(_,a),(_,b)| b[0].id.cmp(&a[0].id));
+        groups
+    }
+
+    fn push(&mut self, mut notification:Notification, time:i64) -> u64 {
+        self.next_id += 1;
+        notification.id = self.next_id;
+        notification.time = time;
+        // Seen right away while the center is open.
+        notification.read = self.center_open;
+        if self.history.len() == HISTORY_LEN {
+            if let Some(dropped) = self.history.pop_front() {
+                self.toasts.retain(|id| *id != dropped.id);
+            }
+        }
+        self.history.push_back(notification);
+        if !self.center_open {
+            self.toasts.push(self.next_id);
+        }
+        self.next_id
+    }
+
+    // Hides a notification's toast, it stays in the history.
+    pub fn dismiss(&mut self, id:u64) {
+        self.toasts.retain(|toast| *toast != id);
+    }
+
+    // Hides the toasts of a source, like dismiss for a whole group.
+    pub fn dismiss_source(&mut self, source:&str) {
+        let history = &self.history;
+        self.toasts.retain(|id| history.iter().find(|notification| notification.id == *id)
+            .map_or(false, |notification| notification.source != source));
+    }
+
+    pub fn remove(&mut self, id:u64) {
+        self.dismiss(id);
+        self.history.retain(|notification| notification.id != id);
+    }
+
+    pub fn clear_source(&mut self, source:&str) {
+        self.dismiss_source(source);
+        self.history.retain(|notification| notification.source != source);
+    }
+
+    pub fn clear(&mut self) {
+        self.toasts.clear();
+        self.history.clear();
+    }
+
+    // Opening the center shows everything the toasts did, so they're hidden and marked read.
+    pub fn toggle_center(&mut self) {
+        self.center_open = !self.center_open;
+        if self.center_open {
+            self.toasts.clear();
+            self.history.iter_mut().for_each(|notification| notification.read = true);
+        }
+    }
+}
+
+// Called once by OperatingSystemProvider.
+pub fn provide_notifications() -> RwSignal<Notifications> {
+    let notifications = create_rw_signal(Notifications::default());
+    provide_context(notifications);
+    notifications
+}
+
+// Shows a toast and adds the notification to the history. The toast goes away after the
+// notification's timeout.
+pub fn notify(notifications:RwSignal<Notifications>, notification:Notification) -> u64 {
+    let timeout_ms = notification.timeout_ms;
+    let now = chrono::Utc::now().timestamp_millis();
+    let id = notifications.try_update(|notifications| notifications.push(notification, now)).unwrap_or_default();
+    if let Some(timeout_ms) = timeout_ms {
+        gloo::timers::callback::Timeout::new(timeout_ms, move || {
+            notifications.update(|notifications| notifications.dismiss(id));
+        }).forget();
+    }
+    id
+}
+
+// Toasts in the top right corner, and the notification center over them when it's open.
+#[island]
+pub fn NotificationCenter() -> impl IntoView {
+    let notifications = expect_context::<RwSignal<Notifications>>();
+    let center_open = create_memo(move |_| notifications.with(|notifications| notifications.center_open));
+    view!{
+        <div class="fixed top-8 right-2 w-80 flex flex-col gap-2 z-40">
+            <For
+            each=move || notifications.with(Notifications::toast_groups)
+            key=|(notification,more)| (notification.id,*more)
+            children=move |(notification,more)| view!{<NotificationCard notification more toast=true/>}
+            />
+        </div>
+        <div class="fixed top-6 right-0 bottom-0 w-96 z-40 flex flex-col gap-3 p-3 overflow-y-auto \
+            bg-surface bg-opacity-50 backdrop-blur-theme"
+            class=("hidden", move || !center_open())>
+            <div class="flex justify-between">
+                <span class="font-bold">"Notifications"</span>
+                <button class="text-sm text-muted" on:click=move |_| notifications.update(Notifications::clear)>"Clear All"</button>
+            </div>
+            <For
+            each=move || notifications.with(Notifications::groups)
+            key=|(source,group)| (source.clone(),group.iter().map(|notification| notification.id).collect::<Vec<u64>>())
+            children=move |(source,group)| {
+                let clear_source = source.clone();
+                view!{
+                    <div class="flex flex-col gap-1">
+                        <div class="flex justify-between text-sm text-muted">
+                            <span>{source}</span>
+                            <button on:click=move |_| notifications.update(|notifications| notifications.clear_source(&clear_source))>"Clear"</button>
+                        </div>
+                        {group.into_iter().map(|notification| view!{<NotificationCard notification more=0 toast=false/>}).collect_view()}
+                    </div>
+                }
+            }
+            />
+            {move || notifications.with(|notifications| notifications.history().next().is_none())
+                .then(|| view!{<p class="text-sm text-muted text-center">"No Notifications"</p>})}
+        </div>
+    }
+}
+
+// A toast, or an entry in the center. more counts the source's other toasts behind it.
+#[component]
+fn NotificationCard(notification:Notification, more:usize, toast:bool) -> impl IntoView {
+    let notifications = expect_context::<RwSignal<Notifications>>();
+    let Notification{id,source,severity,title,body,actions,time,..} = notification;
+    let close_source = source.clone();
+    let close = move |_| notifications.update(|notifications| if toast {
+        notifications.dismiss_source(&close_source);
+    } else {
+        notifications.remove(id);
+    });
+    view!{
+        <div class=format!("flex flex-col gap-1 p-2 text-sm border-l-4 rounded-theme-lg bg-surface bg-opacity-50 backdrop-blur-theme {}", severity.border_class())>
+            <div class="flex justify-between gap-2 text-xs text-muted">
+                <span>{source}</span>
+                <span>{format_time(time)}</span>
+                <button title="Close" on:click=close>"✕"</button>
+            </div>
+            <p class="font-bold">{title}</p>
+            {(!body.is_empty()).then(|| view!{<p>{body}</p>})}
+            {(!actions.is_empty()).then(|| view!{
+                <div class="flex gap-2">
+                {actions.into_iter().map(|NotificationAction{label,on_click}| view!{
+                    <button class="flex-1 rounded-theme bg-control hover:bg-highlight"
+                    on:click=move |_| {
+                        on_click.call(());
+                        notifications.update(|notifications| notifications.dismiss(id));
+                    }>{label}</button>
+                }).collect_view()}
+                </div>
+            })}
+            {(more > 0).then(|| view!{<p class="text-xs text-muted">{format!("{more} more")}</p>})}
+        </div>
+    }
+}
+
+// The time the notification was sent, in the format chosen in System Preferences.
+fn format_time(time:i64) -> String {
+    use_context::<settings::SettingsStore>()
+        .map(|settings| settings.with_untracked(|settings| settings.date_time.format(time)))
+        .unwrap_or_default()
+}
+
+// The bell in the topbar, with the number of unread notifications.
+#[island]
+pub fn NotificationButton() -> impl IntoView {
+    let notifications = expect_context::<RwSignal<Notifications>>();
+    let unread = create_memo(move |_| notifications.with(Notifications::unread));
+    view!{
+        <button class="relative pl-3 pr-3 rounded-theme" title="Notification Center"
+        class=("bg-highlight", move || notifications.with(|notifications| notifications.center_open))
+        on:click=move |_| notifications.update(Notifications::toggle_center)>
+            "🔔"
+            {move || (unread() > 0).then(|| view!{
+                <span class="absolute top-0 right-0 text-xs rounded-full bg-danger pl-1 pr-1">{unread()}</span>
+            })}
+        </button>
+    }
+}
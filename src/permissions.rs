@@ -0,0 +1,70 @@
+use super::*;
+use file_system::Metadata;
+
+// Who a FileSystem operation is performed as, like a unix process's uid and gid.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Credentials{
+    pub uid:u32,
+    pub gid:u32,
+}
+
+impl Credentials{
+    pub const ROOT: Credentials = Credentials{uid:0,gid:0};
+    // Guests, see accounts::Account::guest.
+    pub const DEFAULT_USER: Credentials = Credentials{uid:1000,gid:1000};
+
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Access{
+    Read,
+    Write,
+    Execute,
+}
+
+impl Access{
+    fn bit(&self) -> u16 {
+        match self {
+            Self::Read => 0o4,
+            Self::Write => 0o2,
+            Self::Execute => 0o1,
+        }
+    }
+}
+
+pub const DEFAULT_DIR_MODE: u16 = 0o755;
+pub const DEFAULT_FILE_MODE: u16 = 0o644;
+pub const SYMLINK_MODE: u16 = 0o777;
+// Built-in apps, readable and runnable by everyone but changeable by no one but root.
+pub const SYSTEM_APP_MODE: u16 = 0o555;
+
+// Whether creds may access a node with the given metadata. Root may do anything.
+pub fn allowed(creds:&Credentials, metadata:&Metadata, access:Access) -> bool {
+    if creds.is_root() {
+        return true;
+    }
+    let shift = if creds.uid == metadata.owner {
+        6
+    } else if creds.gid == metadata.group {
+        3
+    } else {
+        0
+    };
+    (metadata.mode >> shift) & access.bit() != 0
+}
+
+// Renders mode bits the way ls -l does, e.g. "drwxr-xr-x".
+pub fn mode_string(metadata:&Metadata) -> String {
+    let mut out = String::with_capacity(10);
+    out.push(if metadata.is_dir() { 'd' } else if metadata.is_sym() { 'l' } else { '-' });
+    for shift in [6,3,0] {
+        let bits = metadata.mode >> shift;
+        out.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        out.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    out
+}
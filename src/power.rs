@@ -0,0 +1,205 @@
+use super::*;
+use file_system::SystemRuntime;
+
+// How long the boot screen's progress bar takes to fill.
+const BOOT_MS: u32 = 1500;
+// Input-free time before the screensaver starts.
+pub const IDLE_TIMEOUT_MS: u32 = 5 * 60 * 1000;
+
+#[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize)]
+pub enum PowerState{
+    // The boot screen is showing, see boot.
+    Booting,
+    Running,
+    // Blurred behind a password prompt until unlock succeeds.
+    Locked,
+    Screensaver,
+    // Logged out and saved, only a power button is left.
+    ShutDown,
+}
+
+// Starts in Booting. A locked session isn't rendered with the desktop at all, see LockedPage.
+// Called once by OperatingSystemProvider.
+pub fn provide_power(system:RwSignal<SystemRuntime>) {
+    let power = create_rw_signal(PowerState::Booting);
+    provide_context(power);
+    create_effect(move |_| boot(system, power));
+    watch_idle(power);
+}
+
+// The boot sequence: apps are quit unless their windows are to be reopened, detached mounts
+// come back and the boot screen shows for BOOT_MS.
+pub fn boot(system:RwSignal<SystemRuntime>, power:RwSignal<PowerState>) {
+    power.set(PowerState::Booting);
+    system.update(|system| if !system.settings().windows.reopen_windows {
+        system.quit_all_apps();
+    });
+    snapshot::remount_detached(system);
+    gloo::timers::callback::Timeout::new(BOOT_MS, move || {
+        if power.get_untracked() == PowerState::Booting {
+            power.set(PowerState::Running);
+        }
+    }).forget();
+}
+
+pub fn lock(power:RwSignal<PowerState>) {
+    power.set(PowerState::Locked);
+    spawn_local(async move {
+        if let Err(err) = lock_session().await {
+            log::error!("couldn't lock the session on the server: {err}");
+        }
+    });
+}
+
+// Flushes remote mounts, hands the runtime to the server to keep, logs out and reloads to
+// the login screen.
+pub fn log_out(system:RwSignal<SystemRuntime>) {
+    spawn_local(async move {
+        save_and_log_out(system).await;
+        _ = window().location().reload();
+    });
+}
+
+pub fn shut_down(system:RwSignal<SystemRuntime>, power:RwSignal<PowerState>) {
+    spawn_local(async move {
+        save_and_log_out(system).await;
+        power.set(PowerState::ShutDown);
+    });
+}
+
+async fn save_and_log_out(system:RwSignal<SystemRuntime>) {
+    vfs::flush(system).await;
+    let snapshot = system.with_untracked(snapshot::to_json)
+        .map_err(|err| log::error!("couldn't save the desktop: {err}"))
+        .ok();
+    if let Err(err) = accounts::logout(snapshot).await {
+        log::error!("logout failed: {err}");
+    }
+}
+
+// Starts the screensaver after IDLE_TIMEOUT_MS without input, any input stops it.
+fn watch_idle(power:RwSignal<PowerState>) {
+    let timer = store_value(None::<gloo::timers::callback::Timeout>);
+    let reset = move || {
+        if power.get_untracked() == PowerState::Screensaver {
+            power.set(PowerState::Running);
+        }
+        let timeout = gloo::timers::callback::Timeout::new(IDLE_TIMEOUT_MS, move || {
+            if power.get_untracked() == PowerState::Running {
+                power.set(PowerState::Screensaver);
+            }
+        });
+        // Dropping the previous Timeout cancels it.
+        timer.set_value(Some(timeout));
+    };
+    // Timers only exist in the browser.
+    create_effect(move |_| reset());
+    let handles = [
+        window_event_listener(ev::mousemove, move |_| reset()),
+        window_event_listener(ev::keydown, move |_| reset()),
+        window_event_listener(ev::pointerdown, move |_| reset()),
+    ];
+    on_cleanup(move || handles.into_iter().for_each(|handle| handle.remove()));
+}
+
+// Until unlock succeeds the server refuses the session's changes and sends it no events, see
+// accounts::unlocked_account.
+#[server(LockSession, "/api")]
+pub async fn lock_session() -> Result<(),ServerFnError> {
+    let (session, state) = accounts::session_state()?;
+    state.lock(&session);
+    Ok(())
+}
+
+// Guests have no password, unlocking only needs them to be the session's guest.
+#[server(Unlock, "/api")]
+pub async fn unlock(password:String) -> Result<(),ServerFnError> {
+    let (session, state) = accounts::session_state()?;
+    let account = state.account(&session).ok_or_else(|| ServerFnError::ServerError("Not logged in.".to_string()))?;
+    if !account.guest {
+        let user = state.users().find(&account.username).await
+            .ok_or_else(|| ServerFnError::ServerError("Account not found.".to_string()))?;
+        if !accounts::verify_password(&password, &user.password_hash) {
+            return Err(ServerFnError::ServerError("Wrong password.".to_string()));
+        }
+    }
+    state.unlock(&session);
+    Ok(())
+}
+
+// Everything drawn over the desktop for the states other than Running.
+#[island]
+pub fn PowerOverlay() -> impl IntoView {
+    let power = expect_context::<RwSignal<PowerState>>();
+    move || match power() {
+        PowerState::Running => view!{}.into_view(),
+        PowerState::Booting => view!{<BootScreen/>}.into_view(),
+        PowerState::Locked => view!{<LockScreen on_unlocked=move || power.set(PowerState::Running)/>}.into_view(),
+        PowerState::Screensaver => view!{
+            <div class="fixed inset-0 z-50 bg-black flex items-center justify-center">
+                <div class="w-24 animate-pulse"><topbar::TopLeftEye/></div>
+            </div>
+        }.into_view(),
+        PowerState::ShutDown => view!{
+            <div class="fixed inset-0 z-50 bg-black flex items-center justify-center">
+                <button class="rounded-full border border-content w-12 h-12"
+                title="Start"
+                on:click=move |_| _ = window().location().reload()>"⏻"</button>
+            </div>
+        }.into_view(),
+    }
+}
+
+#[component]
+fn BootScreen() -> impl IntoView {
+    // Set a frame after mounting so the width transitions from 0.
+    let filled = create_rw_signal(false);
+    create_effect(move |_| request_animation_frame(move || filled.set(true)));
+    let progress_style = move || format!("transition: width {BOOT_MS}ms linear; width:{}%", if filled() { 100 } else { 0 });
+    view!{
+        <div class="fixed inset-0 z-50 bg-black flex flex-col gap-6 items-center justify-center">
+            <div class="w-24"><topbar::TopLeftEye/></div>
+            <div class="w-48 h-1 rounded bg-surface overflow-hidden">
+                <div class="h-1 bg-content" style=progress_style></div>
+            </div>
+        </div>
+    }
+}
+
+// What a locked session's page shows instead of the desktop. The page has no runtime to go back
+// to, so it's loaded again once unlocked.
+#[island]
+pub fn LockedPage() -> impl IntoView {
+    view!{<LockScreen on_unlocked=|| _ = window().location().reload()/>}
+}
+
+#[component]
+fn LockScreen<F:Fn() + Copy + 'static>(on_unlocked:F) -> impl IntoView {
+    let password = create_rw_signal(String::new());
+    let error = create_rw_signal(None::<String>);
+    view!{
+        <div class="fixed inset-0 z-50 backdrop-blur-theme bg-backdrop bg-opacity-40 flex items-center justify-center">
+        <form class="w-64 flex flex-col gap-2"
+        on:submit=move |ev| {
+            ev.prevent_default();
+            let entered = password.get_untracked();
+            spawn_local(async move {
+                match unlock(entered).await {
+                    Ok(()) => {
+                        password.set(String::new());
+                        on_unlocked();
+                    },
+                    Err(err) => error.set(Some(login::server_error_message(err))),
+                }
+            });
+        }>
+            <div class="w-16 self-center"><topbar::TopLeftEye/></div>
+            <input class="rounded-theme px-2 py-1 bg-field outline-none" placeholder="Password" type="password"
+            autocomplete="current-password"
+            prop:value=password
+            on:input=move |ev| password.set(event_target_value(&ev))/>
+            {move || error().map(|error| view!{<p class="text-sm text-danger">{error}</p>})}
+        </form>
+        </div>
+    }
+}
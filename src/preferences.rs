@@ -0,0 +1,360 @@
+use super::*;
+use file_system::SystemRuntime;
+use dialogs::{Dialogs,Modality};
+use settings::{Accent,DockPosition,SettingsStore,ShortcutAction,Shortcuts,Wallpaper,MIN_DOCK_SIZE,MAX_DOCK_SIZE};
+
+// The System Preferences app, see desktop.toml.
+pub const PREFERENCES_PATH: &'static str = "/preferences";
+// Where pictures added from the Appearance pane are imported to.
+pub const PICTURES_PATH: &'static str = "/Pictures";
+// Wallpapers served from public/.
+const BUILT_IN_WALLPAPERS: [&'static str;1] = ["/bg.png"];
+
+// Kept as the window's app state, so it opens at the same pane next time.
+#[derive(Debug,Clone,Copy,PartialEq,Default,Serialize,Deserialize)]
+enum Pane{
+    #[default]
+    Appearance,
+    Dock,
+    Windows,
+    DateTime,
+    Shortcuts,
+}
+
+impl Pane{
+    const ALL: [Self;5] = [Self::Appearance,Self::Dock,Self::Windows,Self::DateTime,Self::Shortcuts];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Appearance => "Appearance",
+            Self::Dock => "Dock",
+            Self::Windows => "Windows",
+            Self::DateTime => "Date & Time",
+            Self::Shortcuts => "Keyboard Shortcuts",
+        }
+    }
+}
+
+pub fn open(system:RwSignal<SystemRuntime>) {
+    system.update(|system| system.run_app(PREFERENCES_PATH));
+}
+
+// The content of the System Preferences window, see windows::WindowContent.
+#[component]
+pub fn Preferences() -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let window = expect_context::<windows::WindowId>();
+    let pane = create_rw_signal(session_restore::app_state::<Pane>(system, window.0));
+    create_effect(move |first:Option<()>| {
+        let pane = pane();
+        if first.is_some() {
+            session_restore::save_app_state(system, window.0, &pane);
+        }
+    });
+    view!{
+        <div class="h-full flex">
+            <div class="w-44 flex flex-col gap-1 p-2 bg-field bg-opacity-50">
+                {Pane::ALL.into_iter().map(|item| view!{
+                    <button class="text-left px-2 rounded-theme"
+                    class=("bg-selected", move || pane() == item)
+                    on:click=move |_| pane.set(item)>{item.label()}</button>
+                }).collect_view()}
+            </div>
+            <div class="flex-1 flex flex-col gap-4 p-4 overflow-y-auto">
+                {move || match pane() {
+                    Pane::Appearance => view!{<AppearancePane/>}.into_view(),
+                    Pane::Dock => view!{<DockPane/>}.into_view(),
+                    Pane::Windows => view!{<WindowsPane/>}.into_view(),
+                    Pane::DateTime => view!{<DateTimePane/>}.into_view(),
+                    Pane::Shortcuts => view!{<ShortcutsPane/>}.into_view(),
+                }}
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn Row(label:&'static str, children:Children) -> impl IntoView {
+    view!{
+        <div class="flex items-center gap-4">
+            <span class="w-32 text-right text-sm">{label}</span>
+            <div class="flex-1 flex items-center gap-2">{children()}</div>
+        </div>
+    }
+}
+
+#[component]
+fn AppearancePane() -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let imports = expect_context::<RwSignal<import::Imports>>();
+    let settings = expect_context::<SettingsStore>();
+    let dialogs = expect_context::<Dialogs>();
+    let appearance = settings.select(|settings| settings.appearance.clone());
+    let pictures = move || system.with(|system| {
+        system.dir_entries(PICTURES_PATH).into_iter()
+            .filter(|entry| system.file_system().get_file_metadata(&entry.path).ok()
+                .and_then(|metadata| metadata.mime_type.as_deref())
+                .map_or(false, |mime| mime.starts_with("image/")))
+            .collect::<Vec<file_system::DirEntry>>()
+    });
+    // Whether wallpapers picked here go on the current workspace only.
+    let this_desktop = create_rw_signal(system.with_untracked(|system| system.windows().current_workspace().wallpaper.is_some()));
+    let shown_wallpaper = create_memo(move |_| system.with(|system| system.wallpaper().clone()));
+    let set_wallpaper = move |wallpaper:Wallpaper| if this_desktop.get_untracked() {
+        system.update(|system| {
+            let id = system.windows().current_workspace().id;
+            system.windows_mut().set_workspace_wallpaper(id, Some(wallpaper));
+        });
+    } else {
+        settings.update(|settings| settings.appearance.wallpaper = wallpaper);
+    };
+    // Going back to all desktops drops the current one's own wallpaper.
+    let set_this_desktop = move |only:bool| {
+        this_desktop.set(only);
+        if !only {
+            system.update(|system| {
+                let id = system.windows().current_workspace().id;
+                system.windows_mut().set_workspace_wallpaper(id, None);
+            });
+        }
+    };
+    // Themes that come with a wallpaper switch to it too.
+    let set_theme = move |name:String| {
+        let wallpaper = system.try_update_untracked(|system| theme::load(system.file_system_mut(), &name))
+            .and_then(|theme| theme.map_err(|err| log::error!("couldn't load theme: {err}")).ok())
+            .and_then(|theme| theme.wallpaper);
+        settings.update(|settings| {
+            if let Some(wallpaper) = wallpaper {
+                settings.appearance.wallpaper = wallpaper;
+            }
+            settings.appearance.theme = name;
+        });
+    };
+    view!{
+        <Row label="Theme">
+            <For
+            each=move || system.with(theme::available)
+            key=|name| name.clone()
+            children=move |name| {
+                let selected = {
+                    let name = name.clone();
+                    move || appearance.with(|appearance| appearance.theme == name)
+                };
+                let label = name.clone();
+                view!{
+                    <button class="px-3 rounded-theme bg-control"
+                    class=("bg-highlight", selected)
+                    on:click=move |_| set_theme(name.clone())>
+                    {label}
+                    </button>
+                }
+            }
+            />
+        </Row>
+        <Row label="Accent color">
+            {Accent::ALL.into_iter().map(|accent| view!{
+                <button class="w-5 h-5 rounded-full" title=format!("{accent:?}")
+                style=format!("background-color:{}", accent.color())
+                class=("ring-2", move || appearance.with(|appearance| appearance.accent == accent))
+                class=("ring-content", move || appearance.with(|appearance| appearance.accent == accent))
+                on:click=move |_| settings.update(|settings| settings.appearance.accent = accent)/>
+            }).collect_view()}
+        </Row>
+        <Row label="Wallpaper for">
+            <button class="px-3 rounded-theme bg-control"
+            class=("bg-highlight", move || !this_desktop())
+            on:click=move |_| set_this_desktop(false)>"All Desktops"</button>
+            <button class="px-3 rounded-theme bg-control"
+            class=("bg-highlight", this_desktop)
+            on:click=move |_| set_this_desktop(true)>"This Desktop"</button>
+        </Row>
+        <Row label="Wallpaper">
+            <div class="flex flex-wrap gap-2">
+            {BUILT_IN_WALLPAPERS.into_iter().map(|url| {
+                let wallpaper = Wallpaper::Public(url.to_string());
+                let selected = {
+                    let wallpaper = wallpaper.clone();
+                    move || shown_wallpaper.with(|shown| *shown == wallpaper)
+                };
+                view!{
+                    <button class="w-16 h-12 rounded-theme bg-cover" title=url
+                    style=format!("background-image:url({url})")
+                    class=("ring-2", selected.clone())
+                    class=("ring-content", selected)
+                    on:click=move |_| set_wallpaper(wallpaper.clone())/>
+                }
+            }).collect_view()}
+            <For
+            each=pictures
+            key=|entry| entry.path.clone()
+            children=move |entry| {
+                let wallpaper = Wallpaper::File(entry.path.clone());
+                let selected = {
+                    let wallpaper = wallpaper.clone();
+                    move || shown_wallpaper.with(|shown| *shown == wallpaper)
+                };
+                view!{
+                    <button class="w-16 flex flex-col items-center rounded-theme text-xs" title=entry.path.clone()
+                    class=("bg-selected", selected)
+                    on:click=move |_| set_wallpaper(wallpaper.clone())>
+                        <img class="w-8" src=entry.img_src/>
+                        <span class="truncate w-full">{entry.name}</span>
+                    </button>
+                }
+            }
+            />
+            <button class="w-16 h-12 rounded-theme border border-dashed text-sm"
+            on:click=move |_| import::pick_files(PICTURES_PATH.to_string(), system, imports)>"Add…"</button>
+            <button class="w-16 h-12 rounded-theme border border-dashed text-sm" title="Pick any picture on this computer"
+            on:click=move |_| spawn_local(async move {
+                if let Some(path) = dialogs.open_file(Modality::Window(PREFERENCES_PATH.to_string()), PICTURES_PATH).await {
+                    set_wallpaper(Wallpaper::File(path));
+                }
+            })>"Choose…"</button>
+            </div>
+        </Row>
+    }
+}
+
+#[component]
+fn WindowsPane() -> impl IntoView {
+    let settings = expect_context::<SettingsStore>();
+    let windows = settings.select(|settings| settings.windows);
+    view!{
+        <Row label="Tiling">
+            {layout::Tiling::ALL.into_iter().map(|tiling| view!{
+                <button class="px-3 rounded-theme bg-control"
+                class=("bg-highlight", move || windows.with(|windows| windows.tiling == tiling))
+                on:click=move |_| settings.update(|settings| settings.windows.tiling = tiling)>
+                {tiling.label()}
+                </button>
+            }).collect_view()}
+        </Row>
+        <Row label="">
+            <label class="flex items-center gap-2">
+                <input type="checkbox"
+                prop:checked=move || windows.with(|windows| windows.snap_to_edges)
+                on:change=move |ev| settings.update(|settings| settings.windows.snap_to_edges = event_target_checked(&ev))/>
+                "Snap windows dropped at the edges of the screen"
+            </label>
+        </Row>
+        <Row label="">
+            <label class="flex items-center gap-2">
+                <input type="checkbox"
+                prop:checked=move || windows.with(|windows| windows.reopen_windows)
+                on:change=move |ev| settings.update(|settings| settings.windows.reopen_windows = event_target_checked(&ev))/>
+                "Reopen windows when logging back in"
+            </label>
+        </Row>
+    }
+}
+
+#[component]
+fn DockPane() -> impl IntoView {
+    let settings = expect_context::<SettingsStore>();
+    let dock = settings.select(|settings| settings.dock.clone());
+    view!{
+        <Row label="Position on screen">
+            {[DockPosition::Left,DockPosition::Bottom,DockPosition::Right].into_iter().map(|position| view!{
+                <button class="px-3 rounded-theme bg-control"
+                class=("bg-highlight", move || dock.with(|dock| dock.position == position))
+                on:click=move |_| settings.update(|settings| settings.dock.position = position)>
+                {format!("{position:?}")}
+                </button>
+            }).collect_view()}
+        </Row>
+        <Row label="Size">
+            <input type="range" class="flex-1"
+            min=MIN_DOCK_SIZE max=MAX_DOCK_SIZE
+            prop:value=move || dock.with(|dock| dock.size.to_string())
+            on:input=move |ev| if let Ok(size) = event_target_value(&ev).parse::<u32>() {
+                settings.update(|settings| settings.dock.size = size.clamp(MIN_DOCK_SIZE, MAX_DOCK_SIZE));
+            }/>
+            <span class="w-12 text-sm">{move || format!("{}px", dock.with(|dock| dock.size))}</span>
+        </Row>
+        <Row label="">
+            <label class="flex items-center gap-2">
+                <input type="checkbox"
+                prop:checked=move || dock.with(|dock| dock.autohide)
+                on:change=move |ev| settings.update(|settings| settings.dock.autohide = event_target_checked(&ev))/>
+                "Automatically hide and show the Dock"
+            </label>
+        </Row>
+    }
+}
+
+#[component]
+fn DateTimePane() -> impl IntoView {
+    let settings = expect_context::<SettingsStore>();
+    let format = settings.select(|settings| settings.date_time.clone());
+    let now = chrono::Utc::now().timestamp_millis();
+    let option = move |label:&'static str, get:fn(&settings::DateTimeFormat) -> bool, set:fn(&mut settings::DateTimeFormat, bool)| view!{
+        <label class="flex items-center gap-2">
+            <input type="checkbox"
+            prop:checked=move || format.with(get)
+            on:change=move |ev| settings.update(|settings| set(&mut settings.date_time, event_target_checked(&ev)))/>
+            {label}
+        </label>
+    };
+    view!{
+        <Row label="Preview">
+            <span>{move || format.with(|format| format.format(now))}</span>
+        </Row>
+        <Row label="Time">
+            {option("Use a 24-hour clock", |format| format.hour24, |format, on| format.hour24 = on)}
+            {option("Show seconds", |format| format.show_seconds, |format, on| format.show_seconds = on)}
+        </Row>
+        <Row label="Date">
+            {option("Show the day of the week", |format| format.show_weekday, |format, on| format.show_weekday = on)}
+            {option("Show the date", |format| format.show_date, |format, on| format.show_date = on)}
+        </Row>
+    }
+}
+
+#[component]
+fn ShortcutsPane() -> impl IntoView {
+    let settings = expect_context::<SettingsStore>();
+    let dialogs = expect_context::<Dialogs>();
+    let shortcuts = settings.select(|settings| settings.shortcuts.clone());
+    // The action waiting for its new combo to be typed.
+    let recording = create_rw_signal(None::<ShortcutAction>);
+    view!{
+        {ShortcutAction::ALL.into_iter().map(|action| view!{
+            <Row label=action.label()>
+                <button class="w-40 px-2 rounded-theme bg-control"
+                class=("bg-highlight", move || recording() == Some(action))
+                on:click=move |_| recording.set(Some(action))
+                on:blur=move |_| recording.set(None)
+                // Stopped here so the window's shortcut listeners don't fire while recording.
+                on:keydown=move |ev| {
+                    if recording.get_untracked() != Some(action) {
+                        return;
+                    }
+                    ev.prevent_default();
+                    ev.stop_propagation();
+                    if ev.key() == "Escape" {
+                        recording.set(None);
+                    } else if let Some(combo) = settings::KeyCombo::from_event(&ev) {
+                        settings.update(|settings| { settings.shortcuts.0.insert(action, combo); });
+                        recording.set(None);
+                    }
+                }>
+                {move || if recording() == Some(action) {
+                    "Type a shortcut…".to_string()
+                } else {
+                    shortcuts.with(|shortcuts| shortcuts.combo(action).label())
+                }}
+                </button>
+            </Row>
+        }).collect_view()}
+        <Row label="">
+            <button class="px-3 rounded-theme bg-control hover:bg-selected"
+            on:click=move |_| spawn_local(async move {
+                let modality = Modality::Window(PREFERENCES_PATH.to_string());
+                if dialogs.confirm(modality, "Restore Defaults", "Set every shortcut back to its default?").await {
+                    settings.update(|settings| settings.shortcuts = Shortcuts::default());
+                }
+            })>"Restore Defaults"</button>
+        </Row>
+    }
+}
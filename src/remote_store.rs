@@ -0,0 +1,312 @@
+use super::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use file_system::FsError;
+use vfs::MountSource;
+
+pub type StoreFuture<T> = Pin<Box<dyn Future<Output=Result<T,String>>>>;
+
+// One file or directory as a remote store lists it. Paths are relative to the store's root.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct RemoteObject{
+    pub path:String,
+    pub is_dir:bool,
+    pub size:u64,
+    // Milliseconds since the unix epoch.
+    pub modified:i64,
+}
+
+// Where a vfs::RemoteBackend gets its files from. Everything here goes over the network so it's
+// async, unlike vfs::Backend.
+pub trait RemoteStore{
+    fn list(&self) -> StoreFuture<Vec<RemoteObject>>;
+    fn get(&self, path:&str) -> StoreFuture<Vec<u8>>;
+    fn put(&self, path:&str, content:&[u8]) -> StoreFuture<()>;
+    fn create_dir(&self, path:&str) -> StoreFuture<()>;
+    fn delete(&self, path:&str) -> StoreFuture<()>;
+}
+
+pub fn connect(source:&MountSource) -> Result<Rc<dyn RemoteStore>,FsError> {
+    match source {
+        MountSource::Server{root} => Ok(Rc::new(ServerStore{root:root.clone()})),
+        MountSource::PublicAssets => Ok(Rc::new(PublicAssetsStore)),
+        MountSource::ObjectStorage{bucket_url} => Ok(Rc::new(ObjectStore{bucket_url:bucket_url.trim_end_matches('/').to_string()})),
+        source => Err(FsError::Io(format!("{source} is not a remote source"))),
+    }
+}
+
+// A directory on the site's server, under the logged in account's own directory in
+// SERVER_FILES_DIR.
+pub struct ServerStore{
+    root:String,
+}
+
+impl RemoteStore for ServerStore{
+    fn list(&self) -> StoreFuture<Vec<RemoteObject>> {
+        let root = self.root.clone();
+        Box::pin(async move { list_server_files(root).await.map_err(|err| err.to_string()) })
+    }
+    fn get(&self, path:&str) -> StoreFuture<Vec<u8>> {
+        let (root,path) = (self.root.clone(),path.to_string());
+        Box::pin(async move { read_server_file(root,path).await.map_err(|err| err.to_string()) })
+    }
+    fn put(&self, path:&str, content:&[u8]) -> StoreFuture<()> {
+        let (root,path,content) = (self.root.clone(),path.to_string(),content.to_vec());
+        Box::pin(async move {
+            // The server refuses these anyway, this saves sending them.
+            if content.len() > MAX_SERVER_FILE_SIZE {
+                return Err(too_large(&path));
+            }
+            write_server_file(root,path,content).await.map_err(|err| err.to_string())
+        })
+    }
+    fn create_dir(&self, path:&str) -> StoreFuture<()> {
+        let (root,path) = (self.root.clone(),path.to_string());
+        Box::pin(async move { create_server_dir(root,path).await.map_err(|err| err.to_string()) })
+    }
+    fn delete(&self, path:&str) -> StoreFuture<()> {
+        let (root,path) = (self.root.clone(),path.to_string());
+        Box::pin(async move { remove_server_file(root,path).await.map_err(|err| err.to_string()) })
+    }
+}
+
+// The crate's public/ directory as the server serves it, listed by a server function and
+// read with plain GETs.
+pub struct PublicAssetsStore;
+
+impl RemoteStore for PublicAssetsStore{
+    fn list(&self) -> StoreFuture<Vec<RemoteObject>> {
+        Box::pin(async move { list_public_assets().await.map_err(|err| err.to_string()) })
+    }
+    fn get(&self, path:&str) -> StoreFuture<Vec<u8>> {
+        let url = path.to_string();
+        Box::pin(async move { http_get(&url).await })
+    }
+    fn put(&self, path:&str, _content:&[u8]) -> StoreFuture<()> {
+        let path = path.to_string();
+        Box::pin(async move { Err(format!("{path}: public assets are read-only")) })
+    }
+    fn create_dir(&self, path:&str) -> StoreFuture<()> {
+        self.put(path, &[])
+    }
+    fn delete(&self, path:&str) -> StoreFuture<()> {
+        self.put(path, &[])
+    }
+}
+
+// An S3 compatible bucket over plain http. The bucket has to allow the site's origin to list,
+// read and (for writes) put and delete objects, no request signing is done in the browser.
+pub struct ObjectStore{
+    bucket_url:String,
+}
+
+impl RemoteStore for ObjectStore{
+    fn list(&self) -> StoreFuture<Vec<RemoteObject>> {
+        let url = format!("{}/?list-type=2", self.bucket_url);
+        Box::pin(async move {
+            let body = http_get(&url).await?;
+            Ok(parse_list_objects(&String::from_utf8_lossy(&body)))
+        })
+    }
+    fn get(&self, path:&str) -> StoreFuture<Vec<u8>> {
+        let url = format!("{}{path}", self.bucket_url);
+        Box::pin(async move { http_get(&url).await })
+    }
+    fn put(&self, path:&str, content:&[u8]) -> StoreFuture<()> {
+        let url = format!("{}{path}", self.bucket_url);
+        let body = js_sys::Uint8Array::from(content);
+        Box::pin(async move {
+            let response = gloo::net::http::Request::put(&url).body(body).map_err(|err| err.to_string())?
+                .send().await.map_err(|err| err.to_string())?;
+            check_status(&url, response.status())
+        })
+    }
+    // Buckets have no directories, they exist as long as a key is under them.
+    fn create_dir(&self, _path:&str) -> StoreFuture<()> {
+        Box::pin(async move { Ok(()) })
+    }
+    fn delete(&self, path:&str) -> StoreFuture<()> {
+        let url = format!("{}{path}", self.bucket_url);
+        Box::pin(async move {
+            let response = gloo::net::http::Request::delete(&url).send().await.map_err(|err| err.to_string())?;
+            check_status(&url, response.status())
+        })
+    }
+}
+
+async fn http_get(url:&str) -> Result<Vec<u8>,String> {
+    let response = gloo::net::http::Request::get(url).send().await.map_err(|err| err.to_string())?;
+    check_status(url, response.status())?;
+    response.binary().await.map_err(|err| err.to_string())
+}
+
+fn check_status(url:&str, status:u16) -> Result<(),String> {
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("{url}: http status {status}"))
+    }
+}
+
+// Pulls Key, Size and LastModified out of a ListObjectsV2 response. Good enough for the flat
+// xml S3 returns, not a general xml parser.
+fn parse_list_objects(xml:&str) -> Vec<RemoteObject> {
+    fn tag<'a>(xml:&'a str, name:&str) -> Option<&'a str> {
+        let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+        let end = start + xml[start..].find(&format!("</{name}>"))?;
+        Some(&xml[start..end])
+    }
+    xml.split("<Contents>").skip(1)
+        .filter_map(|contents| {
+            let key = tag(contents, "Key")?;
+            let modified = tag(contents, "LastModified")
+                .and_then(|date| chrono::DateTime::parse_from_rfc3339(date).ok())
+                .map(|date| date.to_utc().timestamp_millis())
+                .unwrap_or_default();
+            Some(RemoteObject{
+                path:format!("/{}", key.trim_end_matches('/')),
+                is_dir:key.ends_with('/'),
+                size:tag(contents, "Size").and_then(|size| size.parse().ok()).unwrap_or_default(),
+                modified,
+            })
+        })
+        .collect()
+}
+
+// Root of the directories ServerStore mounts, relative to where the server runs.
+#[cfg(feature="ssr")]
+pub const SERVER_FILES_DIR: &'static str = "server_files";
+
+// Largest file write_server_file takes.
+pub const MAX_SERVER_FILE_SIZE: usize = 16 * 1024 * 1024;
+
+fn too_large(path:&str) -> String {
+    format!("{path} is larger than the {} MiB the server takes.", MAX_SERVER_FILE_SIZE / (1024 * 1024))
+}
+
+// Maps a store root and a path in it to a real path, never leaving the owner's directory
+// under SERVER_FILES_DIR.
+#[cfg(feature="ssr")]
+fn server_path(owner:&str, root:&str, path:&str) -> std::path::PathBuf {
+    let root = file_system::normalize_path(root);
+    let path = file_system::normalize_path(path);
+    std::path::Path::new(SERVER_FILES_DIR)
+        .join(owner)
+        .join(root.trim_start_matches('/'))
+        .join(path.trim_start_matches('/'))
+}
+
+// server_path for the account the request is logged in as. Each desktop gets a directory of
+// its own, named after its runtime key, so the root a client sends can't reach anyone else's.
+#[cfg(feature="ssr")]
+fn account_path(root:&str, path:&str) -> Result<std::path::PathBuf,ServerFnError> {
+    let (session, state, account) = accounts::unlocked_account()?;
+    let owner = state.runtime_key(&session, &account).replace(|c:char| !c.is_ascii_alphanumeric(), "-");
+    Ok(server_path(&owner, root, path))
+}
+
+#[cfg(feature="ssr")]
+async fn list_dir_recursive(base:std::path::PathBuf) -> std::io::Result<Vec<RemoteObject>> {
+    let mut objects = Vec::new();
+    let mut stack = vec![base.clone()];
+    while let Some(dir) = stack.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            let modified = metadata.modified().ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|since| since.as_millis() as i64)
+                .unwrap_or_default();
+            let relative = entry.path().strip_prefix(&base).unwrap_or(&entry.path()).to_string_lossy().replace('\\', "/");
+            objects.push(RemoteObject{
+                path:format!("/{relative}"),
+                is_dir:metadata.is_dir(),
+                size:metadata.len(),
+                modified,
+            });
+            if metadata.is_dir() {
+                stack.push(entry.path());
+            }
+        }
+    }
+    Ok(objects)
+}
+
+#[server(ListServerFiles, "/api")]
+pub async fn list_server_files(root:String) -> Result<Vec<RemoteObject>,ServerFnError> {
+    let dir = account_path(&root, "/")?;
+    tokio::fs::create_dir_all(&dir).await
+        .map_err(|err| backend_utils::handle_server_err(err, "Couldn't open server folder.".to_string()))?;
+    Ok(list_dir_recursive(dir).await
+        .map_err(|err| backend_utils::handle_server_err(err, "Couldn't list server folder.".to_string()))?)
+}
+
+#[server(ReadServerFile, "/api")]
+pub async fn read_server_file(root:String, path:String) -> Result<Vec<u8>,ServerFnError> {
+    Ok(tokio::fs::read(account_path(&root, &path)?).await
+        .map_err(|err| backend_utils::handle_server_err(err, format!("Couldn't read {path}.")))?)
+}
+
+#[server(WriteServerFile, "/api")]
+pub async fn write_server_file(root:String, path:String, content:Vec<u8>) -> Result<(),ServerFnError> {
+    if content.len() > MAX_SERVER_FILE_SIZE {
+        return Err(ServerFnError::ServerError(too_large(&path)));
+    }
+    Ok(tokio::fs::write(account_path(&root, &path)?, content).await
+        .map_err(|err| backend_utils::handle_server_err(err, format!("Couldn't write {path}.")))?)
+}
+
+#[server(CreateServerDir, "/api")]
+pub async fn create_server_dir(root:String, path:String) -> Result<(),ServerFnError> {
+    Ok(tokio::fs::create_dir_all(account_path(&root, &path)?).await
+        .map_err(|err| backend_utils::handle_server_err(err, format!("Couldn't create {path}.")))?)
+}
+
+#[server(RemoveServerFile, "/api")]
+pub async fn remove_server_file(root:String, path:String) -> Result<(),ServerFnError> {
+    let path_buf = account_path(&root, &path)?;
+    let result = if tokio::fs::metadata(&path_buf).await.map_or(false, |metadata| metadata.is_dir()) {
+        tokio::fs::remove_dir(&path_buf).await
+    } else {
+        tokio::fs::remove_file(&path_buf).await
+    };
+    Ok(result.map_err(|err| backend_utils::handle_server_err(err, format!("Couldn't remove {path}.")))?)
+}
+
+// Lists what cargo-leptos copied from public/ into the site root, leaving out the compiled pkg.
+#[server(ListPublicAssets, "/api")]
+pub async fn list_public_assets() -> Result<Vec<RemoteObject>,ServerFnError> {
+    let site_root = std::env::var("LEPTOS_SITE_ROOT").unwrap_or_else(|_| "target/site".to_string());
+    let objects = list_dir_recursive(std::path::PathBuf::from(site_root)).await
+        .map_err(|err| backend_utils::handle_server_err(err, "Couldn't list public assets.".to_string()))?;
+    Ok(objects.into_iter().filter(|object| !object.path.starts_with("/pkg")).collect())
+}
+
+#[cfg(all(test, feature="ssr"))]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn server_paths_stay_in_the_owners_directory() {
+        let owned = Path::new(SERVER_FILES_DIR).join("user-1001");
+        assert_eq!(server_path("user-1001", "docs", "/a.txt"), owned.join("docs/a.txt"));
+        assert_eq!(server_path("user-1001", "../user-1002", "/a.txt"), owned.join("user-1002/a.txt"));
+        assert_eq!(server_path("user-1001", "docs", "../../../etc/passwd"), owned.join("docs/etc/passwd"));
+        assert_eq!(server_path("user-1001", "/", "/"), owned);
+    }
+
+    #[tokio::test]
+    async fn server_files_need_a_login() {
+        let runtime = create_runtime();
+        let (mut parts, _) = http::Request::new(()).into_parts();
+        parts.extensions.insert(server_state::ServerState::default());
+        parts.extensions.insert(session::Session("anonymous".to_string()));
+        provide_context(parts);
+        assert!(list_server_files("docs".to_string()).await.is_err());
+        assert!(write_server_file("docs".to_string(), "/a.txt".to_string(), b"a".to_vec()).await.is_err());
+        assert!(remove_server_file("docs".to_string(), "/a.txt".to_string()).await.is_err());
+        runtime.dispose();
+    }
+}
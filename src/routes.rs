@@ -0,0 +1,103 @@
+use super::*;
+use file_system::SystemRuntime;
+use config::AppConfig;
+
+// What a deep link asks the desktop to show once it's up:
+//
+//     /app/<app id>         runs the app, by its id in desktop.toml
+//     /open/<file path>     opens the file
+//     /desktop/<number>     switches to that workspace, counting from 1
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub enum Launch{
+    // Path of the app.
+    App(String),
+    File(String),
+    Workspace(usize),
+}
+
+// The Launch of the route the page was loaded at. Only the server renders the components that
+// call this.
+pub fn requested_launch(apps:&[AppConfig]) -> Option<Launch> {
+    let params = use_params_map().get_untracked();
+    if let Some(id) = params.get("id") {
+        let app = apps.iter().find(|app| app.id == percent_decode(id));
+        if app.is_none() {
+            log::warn!("a link asked for the unknown app {id}");
+        }
+        return app.map(|app| Launch::App(app.path.clone()));
+    }
+    if let Some(path) = params.get("path") {
+        return Some(Launch::File(file_system::normalize_path(&format!("/{}", percent_decode(path)))));
+    }
+    params.get("workspace").and_then(|number| number.parse().ok()).map(Launch::Workspace)
+}
+
+// The route showing what's in front: the focused window's app or file, or the workspace when
+// no window is focused. Not percent-encoded.
+pub fn route_for(system:&SystemRuntime, apps:&[AppConfig]) -> String {
+    let windows = system.windows();
+    match windows.focused() {
+        Some(window) => match apps.iter().find(|app| app.path == window.app) {
+            Some(app) => format!("/app/{}", app.id),
+            None => format!("/open{}", window.app),
+        },
+        None => {
+            let current = windows.current_workspace().id;
+            let number = windows.workspaces().iter().position(|workspace| workspace.id == current).unwrap_or_default() + 1;
+            format!("/desktop/{number}")
+        },
+    }
+}
+
+// Decodes %XX escapes, anything that isn't one is kept as is.
+fn percent_decode(text:&str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            },
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Opens what the page's link asked for once booting is done, then keeps the address bar on
+// route_for so it can be copied and shared. Called once by OperatingSystemProvider, after
+// power::provide_power.
+pub fn provide_routes(system:RwSignal<SystemRuntime>, launch:Option<Launch>, apps:Vec<AppConfig>) {
+    let launch = store_value(launch);
+    create_effect(move |_| {
+        let Some(launch) = launch.try_update_value(Option::take).flatten() else { return };
+        system.update(|system| match launch {
+            Launch::App(path) | Launch::File(path) => system.run_app(&path),
+            Launch::Workspace(number) => {
+                // Numbers past the last workspace go to the last one.
+                let workspaces = system.windows().workspaces();
+                let Some(workspace) = workspaces.get(number.saturating_sub(1)).or(workspaces.last()) else { return };
+                let id = workspace.id;
+                system.windows_mut().switch_workspace(id);
+            },
+        });
+    });
+    let route = create_memo(move |_| system.with(|system| route_for(system, &apps)));
+    // Replaced rather than pushed, focusing windows shouldn't fill the back button's history.
+    create_effect(move |_| {
+        let route = String::from(js_sys::encode_uri(&route()));
+        let Ok(history) = window().history() else { return };
+        if window().location().pathname().ok().as_deref() != Some(route.as_str()) {
+            _ = history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&route));
+        }
+    });
+}
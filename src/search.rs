@@ -0,0 +1,445 @@
+use super::*;
+use std::collections::{BTreeMap,BTreeSet};
+use file_system::{FileType,Metadata,SystemRuntime};
+
+// Scores for the different ways a query can match, higher sorts first.
+const EXACT_NAME_SCORE: u32 = 1000;
+const PREFIX_NAME_SCORE: u32 = 750;
+const FUZZY_NAME_SCORE: u32 = 500;
+const FULL_TEXT_SCORE: u32 = 250;
+const MAX_RESULTS: usize = 20;
+const MS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug,Clone,PartialEq)]
+struct IndexedDoc{
+    name:String,
+    terms:BTreeSet<String>,
+    file_type:FileType,
+    modified:i64,
+}
+
+// Inverted index over the FileSystem. The FileSystem calls insert/remove on every mutation
+// so the index never needs a full rebuild.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct SearchIndex{
+    // lowercased file name -> paths with that name
+    names:BTreeMap<String,BTreeSet<String>>,
+    // term from the path or text contents -> paths containing it
+    terms:BTreeMap<String,BTreeSet<String>>,
+    // path -> what we indexed for it, so removal undoes exactly what insert did
+    docs:BTreeMap<String,IndexedDoc>,
+}
+
+impl SearchIndex{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Indexes (or re-indexes) a path. Contents are only tokenized when they are valid utf8.
+    pub fn insert(&mut self, path:&str, name:&str, metadata:&Metadata, content:&[u8]) {
+        self.remove(path);
+        let name = name.to_lowercase();
+        let mut terms = tokenize(path);
+        if let Ok(text) = std::str::from_utf8(content) {
+            terms.extend(tokenize(text));
+        }
+        self.names.entry(name.clone()).or_default().insert(path.to_string());
+        for term in terms.iter() {
+            self.terms.entry(term.clone()).or_default().insert(path.to_string());
+        }
+        self.docs.insert(path.to_string(),IndexedDoc{
+            name,
+            terms,
+            file_type:metadata.file_type,
+            modified:metadata.modified,
+        });
+    }
+
+    pub fn remove(&mut self, path:&str) {
+        let Some(doc) = self.docs.remove(path) else { return };
+        remove_posting(&mut self.names, &doc.name, path);
+        for term in doc.terms.iter() {
+            remove_posting(&mut self.terms, term, path);
+        }
+    }
+
+    pub fn search(&self, query:&SearchQuery) -> Vec<SearchHit> {
+        let text = query.text.trim().to_lowercase();
+        let mut scores : BTreeMap<&str,(u32,MatchKind)> = BTreeMap::new();
+        if text.is_empty() {
+            // Only filters were given, list everything that passes them.
+            for path in self.docs.keys() {
+                bump(&mut scores, path, 0, MatchKind::Filter);
+            }
+        } else {
+            for (name,paths) in self.names.range(text.clone()..) {
+                if !name.starts_with(&text) {
+                    break;
+                }
+                let (score,kind) = if name == &text {
+                    (EXACT_NAME_SCORE,MatchKind::Name)
+                } else {
+                    // Shorter names are closer to what was typed.
+                    (PREFIX_NAME_SCORE - (name.len() - text.len()).min(100) as u32,MatchKind::Prefix)
+                };
+                for path in paths {
+                    bump(&mut scores, path, score, kind);
+                }
+            }
+            for (path,doc) in self.docs.iter() {
+                if let Some(score) = fuzzy_score(&text, &doc.name) {
+                    bump(&mut scores, path, FUZZY_NAME_SCORE.saturating_sub(score), MatchKind::Fuzzy);
+                }
+            }
+            for path in self.full_text(&text) {
+                bump(&mut scores, path, FULL_TEXT_SCORE, MatchKind::Content);
+            }
+        }
+        let mut hits = scores.into_iter()
+            .filter_map(|(path,(score,kind))| {
+                let doc = self.docs.get(path)?;
+                query.matches_filters(doc).then(|| SearchHit{
+                    path:path.to_string(),
+                    name:path.split('/').last().unwrap_or_default().to_string(),
+                    file_type:doc.file_type,
+                    score,
+                    kind,
+                })
+            })
+            .collect::<Vec<SearchHit>>();
+        hits.sort_by(|a,b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+        hits.truncate(MAX_RESULTS);
+        hits
+    }
+
+    // Paths whose terms contain every word of the query, the last word matching as a prefix
+    // so results show up while the user is still typing.
+    fn full_text(&self, text:&str) -> BTreeSet<&str> {
+        let words = words(text).collect::<Vec<String>>();
+        let mut result : Option<BTreeSet<&str>> = None;
+        for (i,word) in words.iter().enumerate() {
+            let is_last = i == words.len() - 1;
+            let mut found = BTreeSet::new();
+            for (term,paths) in self.terms.range(word.clone()..) {
+                if !term.starts_with(word.as_str()) {
+                    break;
+                }
+                if is_last || term == word {
+                    found.extend(paths.iter().map(|p|p.as_str()));
+                }
+            }
+            result = Some(match result {
+                Some(prev) => prev.intersection(&found).cloned().collect(),
+                None => found,
+            });
+        }
+        result.unwrap_or_default()
+    }
+}
+
+// Keeps the best scoring way a path matched.
+fn bump<'a>(scores:&mut BTreeMap<&'a str,(u32,MatchKind)>, path:&'a str, score:u32, kind:MatchKind) {
+    let entry = scores.entry(path).or_insert((score,kind));
+    if entry.0 < score {
+        *entry = (score,kind);
+    }
+}
+
+fn remove_posting(map:&mut BTreeMap<String,BTreeSet<String>>, key:&str, path:&str) {
+    if let Some(paths) = map.get_mut(key) {
+        paths.remove(path);
+        if paths.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+pub fn tokenize(text:&str) -> BTreeSet<String> {
+    words(text).collect()
+}
+
+// Lowercased alphanumeric words in the order they appear.
+fn words(text:&str) -> impl Iterator<Item=String> + '_ {
+    text.split(|c:char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+// Returns a penalty (lower is better) if every char of the query appears in order in the name.
+// Gaps between matched chars cost more than a run of adjacent matches.
+pub fn fuzzy_score(query:&str, name:&str) -> Option<u32> {
+    let mut penalty = 0;
+    let mut last_match : Option<usize> = None;
+    let mut name_chars = name.chars().enumerate();
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let (idx,_) = name_chars.find(|(_,n)| *n == q)?;
+        penalty += match last_match {
+            Some(last) => (idx - last - 1) as u32 * 10,
+            None => idx as u32 * 5,
+        };
+        last_match = Some(idx);
+    }
+    Some(penalty.min(FUZZY_NAME_SCORE - 1))
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum MatchKind{
+    Name,
+    Prefix,
+    Fuzzy,
+    Content,
+    Filter,
+    Recent,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct SearchHit{
+    pub path:String,
+    pub name:String,
+    pub file_type:FileType,
+    pub score:u32,
+    pub kind:MatchKind,
+}
+
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct SearchQuery{
+    pub text:String,
+    pub file_type:Option<FileType>,
+    // Inclusive bounds on Metadata::modified.
+    pub modified_after:Option<i64>,
+    pub modified_before:Option<i64>,
+}
+
+impl SearchQuery{
+    // Parses the Spotlight input. Besides free text it understands
+    // `kind:file|dir|link`, `after:YYYY-MM-DD` and `before:YYYY-MM-DD`.
+    pub fn parse(input:&str) -> Self {
+        let mut query = Self::default();
+        let mut text = Vec::new();
+        for word in input.split_whitespace() {
+            match word.split_once(':') {
+                Some(("kind",kind)) => query.file_type = match kind {
+                    "file" => Some(FileType::File),
+                    "dir" | "folder" => Some(FileType::Directory),
+                    "link" | "alias" => Some(FileType::SymbolicLink),
+                    _ => None,
+                },
+                Some(("after",date)) if parse_date(date).is_some() => query.modified_after = parse_date(date),
+                // before: is inclusive of the whole day given.
+                Some(("before",date)) if parse_date(date).is_some() => query.modified_before = parse_date(date)
+                    .map(|start| start + MS_PER_DAY - 1),
+                _ => text.push(word),
+            }
+        }
+        query.text = text.join(" ");
+        query
+    }
+
+    fn matches_filters(&self, doc:&IndexedDoc) -> bool {
+        self.file_type.map_or(true,|file_type| file_type == doc.file_type)
+            && self.modified_after.map_or(true,|after| doc.modified >= after)
+            && self.modified_before.map_or(true,|before| doc.modified <= before)
+    }
+}
+
+// Start of the given day in milliseconds since the unix epoch, UTC.
+fn parse_date(date:&str) -> Option<i64> {
+    chrono::NaiveDate::parse_from_str(date,"%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0,0,0))
+        .map(|date_time| date_time.and_utc().timestamp_millis())
+}
+
+#[derive(PartialEq,Clone,Copy,Debug)]
+pub struct SpotlightOpen(pub bool);
+
+#[island]
+pub fn Spotlight() -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let open = expect_context::<RwSignal<SpotlightOpen>>();
+    let settings = expect_context::<settings::SettingsStore>();
+    let (input, set_input) = create_signal(String::new());
+    let input_ref = create_node_ref::<leptos::html::Input>();
+    let hits = move || {
+        let input = input();
+        if input.trim().is_empty() {
+            // Before anything is typed show what was used last.
+            return system.with(|system| system.list_dir(file_system::RECENTS_PATH))
+                .into_iter()
+                .map(|path| SearchHit{
+                    name:path.split('/').last().unwrap_or_default().to_string(),
+                    path,
+                    file_type:FileType::File,
+                    score:0,
+                    kind:MatchKind::Recent,
+                })
+                .collect();
+        }
+        system.with(|system| system.search(&SearchQuery::parse(&input)))
+    };
+    let close = move || {
+        open.set(SpotlightOpen(false));
+        set_input(String::new());
+    };
+    // The Spotlight shortcut (Cmd/Ctrl+Space unless changed) toggles the overlay from anywhere,
+    // Escape closes it.
+    let handle = window_event_listener(ev::keydown, move |ev| {
+        if settings.with_untracked(|settings| settings.shortcuts.matches(settings::ShortcutAction::Spotlight, &ev)) {
+            ev.prevent_default();
+            if open.get_untracked().0 {
+                close();
+            } else {
+                open.set(SpotlightOpen(true));
+            }
+        } else if ev.key() == "Escape" && open.get_untracked().0 {
+            close();
+        }
+    });
+    on_cleanup(move || handle.remove());
+    create_effect(move |_| {
+        if open().0 {
+            if let Some(input) = input_ref.get() {
+                _ = input.focus();
+            }
+        }
+    });
+    view!{
+        <div class="fixed top-1/4 left-1/2 translate-x-[-50%] w-[36rem] z-40 flex flex-col \
+            bg-surface bg-opacity-50 backdrop-blur-theme rounded-theme-lg p-2"
+            class=("hidden", move || !open().0)>
+            <input _ref=input_ref type="text"
+            class="w-full bg-transparent outline-none text-xl p-1 placeholder:text-muted"
+            placeholder="Spotlight Search"
+            prop:value=input
+            on:input=move |ev| set_input(event_target_value(&ev))
+            on:keydown=move |ev| {
+                if ev.key() == "Enter" {
+                    if let Some(hit) = hits().first() {
+                        finder::open(system, &hit.path);
+                        close();
+                    }
+                }
+            }
+            />
+            <For
+            each=hits
+            key=|hit| hit.path.clone()
+            children=move |hit| {
+                let path = hit.path.clone();
+                view!{
+                    <button class="flex justify-between rounded-theme pl-2 pr-2 hover:bg-highlight"
+                    on:click=move |_| {
+                        finder::open(system, &path);
+                        close();
+                    }>
+                    <span>{hit.name}</span>
+                    <span class="text-sm text-muted">{hit.path}</span>
+                    </button>
+                }
+            }
+            />
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(file_type:FileType, modified:i64) -> Metadata {
+        let mut metadata = Metadata::new(file_type, "/text.png");
+        metadata.modified = modified;
+        metadata
+    }
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::new();
+        index.insert("/Desktop/notes", "notes", &metadata(FileType::Directory, 10), &[]);
+        index.insert("/Desktop/notes/todo.txt", "todo.txt", &metadata(FileType::File, 20), b"buy milk and eggs");
+        index.insert("/Desktop/notebook.txt", "notebook.txt", &metadata(FileType::File, 30), b"nothing here");
+        index.insert("/Desktop/no_tes.csv", "no_tes.csv", &metadata(FileType::File, 40), b"a,b");
+        index.insert("/Documents/shopping.txt", "shopping.txt", &metadata(FileType::File, 50), b"milk, bread");
+        index
+    }
+
+    fn paths(hits:&[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.path.as_str()).collect()
+    }
+
+    fn query(text:&str) -> SearchQuery {
+        SearchQuery{text:text.to_string(), ..Default::default()}
+    }
+
+    #[test]
+    fn names_rank_above_contents() {
+        let hits = index().search(&query("notes"));
+        assert_eq!(paths(&hits), ["/Desktop/notes", "/Desktop/no_tes.csv", "/Desktop/notes/todo.txt"]);
+        assert_eq!(hits[0].kind, MatchKind::Name);
+        assert_eq!(hits[0].score, EXACT_NAME_SCORE);
+        assert_eq!(hits[1].kind, MatchKind::Fuzzy);
+        // Only matches through its path.
+        assert_eq!(hits[2].kind, MatchKind::Content);
+    }
+
+    #[test]
+    fn shorter_prefix_matches_rank_first() {
+        let hits = index().search(&query("note"));
+        assert_eq!(&paths(&hits)[..2], ["/Desktop/notes", "/Desktop/notebook.txt"]);
+        assert!(hits[..2].iter().all(|hit| hit.kind == MatchKind::Prefix));
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn every_word_has_to_be_in_the_contents() {
+        let index = index();
+        assert_eq!(paths(&index.search(&query("milk"))), ["/Desktop/notes/todo.txt", "/Documents/shopping.txt"]);
+        assert_eq!(paths(&index.search(&query("milk bre"))), ["/Documents/shopping.txt"]);
+        assert!(index.search(&query("milk cheese")).is_empty());
+    }
+
+    #[test]
+    fn filters_narrow_the_hits() {
+        let index = index();
+        let dirs = index.search(&SearchQuery::parse("kind:dir"));
+        assert_eq!(paths(&dirs), ["/Desktop/notes"]);
+        assert_eq!(dirs[0].kind, MatchKind::Filter);
+        let mut recent = query("milk");
+        recent.modified_after = Some(25);
+        assert_eq!(paths(&index.search(&recent)), ["/Documents/shopping.txt"]);
+        recent.modified_before = Some(40);
+        assert!(index.search(&recent).is_empty());
+    }
+
+    #[test]
+    fn removed_and_reindexed_paths_are_forgotten() {
+        let mut index = index();
+        index.remove("/Documents/shopping.txt");
+        assert_eq!(paths(&index.search(&query("milk"))), ["/Desktop/notes/todo.txt"]);
+        index.insert("/Desktop/notes/todo.txt", "todo.txt", &metadata(FileType::File, 60), b"call mum");
+        assert!(index.search(&query("milk")).is_empty());
+        assert_eq!(paths(&index.search(&query("mum"))), ["/Desktop/notes/todo.txt"]);
+        index.remove("/Desktop/notes/todo.txt");
+        index.remove("/Desktop/notes/todo.txt");
+        assert!(index.search(&query("todo")).is_empty());
+        assert!(!index.terms.contains_key("mum"));
+    }
+
+    #[test]
+    fn fuzzy_prefers_adjacent_matches() {
+        // n at the start, then a gap of one before t and one before s.
+        assert_eq!(fuzzy_score("nts", "notes"), Some(20));
+        assert!(fuzzy_score("not", "notes") < fuzzy_score("nts", "notes"));
+        assert_eq!(fuzzy_score("xyz", "notes"), None);
+        assert_eq!(fuzzy_score("son", "notes"), None);
+    }
+
+    #[test]
+    fn queries_parse_filters_out_of_the_text() {
+        let query = SearchQuery::parse("kind:file  milk after:2023-11-14 before:2023-11-15 eggs");
+        assert_eq!(query.text, "milk eggs");
+        assert_eq!(query.file_type, Some(FileType::File));
+        assert_eq!(query.modified_after, Some(1_699_920_000_000));
+        assert_eq!(query.modified_before, Some(1_699_920_000_000 + 2 * MS_PER_DAY - 1));
+        // Not a date, so it's searched for.
+        assert_eq!(SearchQuery::parse("after:tomorrow").text, "after:tomorrow");
+    }
+}
@@ -1,8 +1,169 @@
 use super::*;
+use std::collections::{HashMap,HashSet};
+use std::sync::{Arc,RwLock};
+use file_system::SystemRuntime;
+use accounts::{Account,UserStore};
 
-
-
-#[derive(Debug,Clone,Default)]
+#[derive(Debug,Clone)]
 pub struct ServerState{
+    // What new desktops are built from, loaded at start.
+    config:Arc<config::DesktopConfig>,
+    users:Arc<dyn UserStore>,
+    // Session id -> who it's logged in as.
+    logins:Arc<RwLock<HashMap<String,Account>>>,
+    // Sessions showing the lock screen, so reloading doesn't unlock them.
+    locked:Arc<RwLock<HashSet<String>>>,
+    // Runtime key -> json snapshot of that desktop's runtime. Snapshots rather than runtimes
+    // since a SystemRuntime isn't Send.
+    runtimes:Arc<RwLock<HashMap<String,String>>>,
+    events:events::EventHub,
+}
+
+impl Default for ServerState{
+    fn default() -> Self {
+        Self::new(config::DesktopConfig::default(), Arc::new(accounts::MemoryUserStore::default()))
+    }
 }
 
+impl ServerState{
+    pub fn new(config:config::DesktopConfig, users:Arc<dyn UserStore>) -> Self {
+        Self{
+            config:Arc::new(config),
+            users,
+            logins:Default::default(),
+            locked:Default::default(),
+            runtimes:Default::default(),
+            events:Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &config::DesktopConfig {
+        &self.config
+    }
+
+    pub fn users(&self) -> &dyn UserStore {
+        self.users.as_ref()
+    }
+
+    pub fn events(&self) -> &events::EventHub {
+        &self.events
+    }
+
+    pub fn account(&self, session:&str) -> Option<Account> {
+        self.logins.read().unwrap().get(session).cloned()
+    }
+
+    pub fn login(&self, session:&str, account:Account) {
+        self.logins.write().unwrap().insert(session.to_string(), account);
+    }
+
+    pub fn lock(&self, session:&str) {
+        self.locked.write().unwrap().insert(session.to_string());
+    }
+
+    pub fn unlock(&self, session:&str) {
+        self.locked.write().unwrap().remove(session);
+    }
+
+    pub fn is_locked(&self, session:&str) -> bool {
+        self.locked.read().unwrap().contains(session)
+    }
+
+    // Guests' desktops only live as long as their session.
+    pub fn logout(&self, session:&str) {
+        self.unlock(session);
+        if let Some(account) = self.logins.write().unwrap().remove(session) {
+            if account.guest {
+                self.runtimes.write().unwrap().remove(&self.runtime_key(session, &account));
+            }
+        }
+    }
+
+    // Every session of an account shares its desktop, a guest's is its session's own.
+    pub fn runtime_key(&self, session:&str, account:&Account) -> String {
+        if account.guest {
+            format!("guest:{session}")
+        } else {
+            format!("user:{}", account.credentials.uid)
+        }
+    }
+
+    // Which desktop a session's events are about, None before logging in.
+    pub fn session_key(&self, session:&str) -> Option<String> {
+        self.account(session).map(|account| self.runtime_key(session, &account))
+    }
+
+    // The runtime to render the desktop with for a session, None before logging in. The first
+    // login of an account gets the configured desktop, later requests get the same runtime
+    // back so what the server renders matches what the island hydrates with.
+    pub fn runtime(&self, session:&str) -> Option<SystemRuntime> {
+        let account = self.account(session)?;
+        let key = self.runtime_key(session, &account);
+        if let Some(json) = self.runtimes.read().unwrap().get(&key) {
+            match snapshot::from_json(json) {
+                Ok(runtime) => return Some(runtime),
+                Err(err) => log::error!("{key} has a bad snapshot: {err}"),
+            }
+        }
+        // The config was laid out once at start, this only fails if the username can't be
+        // a directory name, which registering checks.
+        let runtime = self.config.runtime_for(&account).unwrap_or_else(|_| initial_runtime());
+        self.save_runtime(&key, &runtime);
+        Some(runtime)
+    }
+
+    pub fn save_runtime(&self, key:&str, runtime:&SystemRuntime) {
+        match snapshot::to_json(runtime) {
+            Ok(json) => self.save_snapshot(key, json),
+            Err(err) => log::error!("couldn't snapshot {key}: {err}"),
+        }
+    }
+
+    pub fn save_snapshot(&self, key:&str, json:String) {
+        self.runtimes.write().unwrap().insert(key.to_string(), json);
+    }
+
+    // Saves the runtime a tab sent for account, as long as it only differs from the one kept
+    // here in ways account could have made, see FileSystem::vet. Refused snapshots leave the
+    // kept runtime as it is, the changes tabs made were published as they made them.
+    pub fn save_client_snapshot(&self, key:&str, account:&Account, json:&str) -> Result<(),String> {
+        let mut runtime = snapshot::from_json(json).map_err(|err| err.to_string())?;
+        let kept = self.runtimes.read().unwrap().get(key).cloned().ok_or_else(|| format!("{key} has no runtime"))?;
+        let mut kept = snapshot::from_json(&kept).map_err(|err| err.to_string())?;
+        kept.file_system_mut().set_user(account.credentials);
+        kept.file_system().vet(runtime.file_system_mut()).map_err(|err| err.to_string())?;
+        self.save_runtime(key, &runtime);
+        Ok(())
+    }
+
+    // Keeps the stored runtime up to date with what a tab changed, so a tab opened later
+    // starts from the same files. Tabs can send anything, so the changes are made as account
+    // with the usual permission checks. Returns the ones that were allowed and the ones that
+    // weren't.
+    pub fn apply_changes(&self, key:&str, account:&Account, changes:Vec<events::FsChange>) -> (Vec<events::FsChange>,Vec<file_system::FsError>) {
+        let (mut applied, mut refused) = (Vec::new(), Vec::new());
+        self.update_runtime(key, |runtime| {
+            let file_system = runtime.file_system_mut();
+            file_system.set_user(account.credentials);
+            for change in changes {
+                match file_system.apply_checked(change) {
+                    Ok(changes) => applied.extend(changes),
+                    Err(err) => refused.push(err),
+                }
+            }
+        });
+        (applied, refused)
+    }
+
+    // Changes the stored runtime of key, if there is one.
+    pub fn update_runtime(&self, key:&str, f:impl FnOnce(&mut SystemRuntime)) {
+        let Some(json) = self.runtimes.read().unwrap().get(key).cloned() else { return };
+        match snapshot::from_json(&json) {
+            Ok(mut runtime) => {
+                f(&mut runtime);
+                self.save_runtime(key, &runtime);
+            },
+            Err(err) => log::error!("{key} has a bad snapshot: {err}"),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use super::*;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::Request;
+use tower_cookies::{Cookie,Cookies};
+use tower_cookies::cookie::SameSite;
+
+// Identifies a visitor's browser across requests.
+pub const SESSION_COOKIE: &'static str = "site_os_session";
+
+// The session id of a request, put in its extensions by with_session.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Session(pub String);
+
+// Middleware giving every request a session before anything reads it: the one the browser
+// sent, or a new one with its cookie set on the response. Needs tower_cookies'
+// CookieManagerLayer around it, see main.rs.
+pub async fn with_session<B>(cookies:Cookies, mut request:Request<B>, next:Next<B>) -> Response {
+    let session = match cookies.get(SESSION_COOKIE) {
+        Some(cookie) => cookie.value().to_string(),
+        None => set_session_cookie(&cookies, over_tls(request.uri(), request.headers())),
+    };
+    request.extensions_mut().insert(Session(session));
+    next.run(request).await
+}
+
+// The session id of the request being rendered.
+pub fn current_session() -> Option<String> {
+    use_context::<http::request::Parts>()?.extensions.get::<Session>().map(|session| session.0.clone())
+}
+
+// Sets a cookie with a fresh session id on the response, replacing the browser's.
+pub fn new_session() -> Option<String> {
+    let parts = use_context::<http::request::Parts>()?;
+    let cookies = parts.extensions.get::<Cookies>().cloned()?;
+    Some(set_session_cookie(&cookies, over_tls(&parts.uri, &parts.headers)))
+}
+
+// Whether the browser reached the site over https, directly or through a proxy that ends TLS
+// and says so. The server itself only speaks http, see site-addr in Cargo.toml.
+fn over_tls(uri:&http::Uri, headers:&http::HeaderMap) -> bool {
+    uri.scheme_str() == Some("https")
+        || headers.get("x-forwarded-proto").map_or(false, |proto| proto.as_bytes().eq_ignore_ascii_case(b"https"))
+}
+
+// Secure cookies are only sent over https, so over plain http the browser would drop it.
+fn set_session_cookie(cookies:&Cookies, secure:bool) -> String {
+    let session = uuid::Uuid::new_v4().to_string();
+    let mut cookie = Cookie::new(SESSION_COOKIE, session.clone());
+    cookie.set_path("/");
+    cookie.set_http_only(true);
+    cookie.set_secure(secure);
+    cookie.set_same_site(SameSite::Lax);
+    cookies.add(cookie);
+    session
+}
+
+// The ServerState layered onto the router in main.rs.
+pub fn server_state() -> Option<server_state::ServerState> {
+    use_context::<http::request::Parts>()?.extensions.get::<server_state::ServerState>().cloned()
+}
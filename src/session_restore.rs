@@ -0,0 +1,63 @@
+use super::*;
+use file_system::SystemRuntime;
+use serde::de::DeserializeOwned;
+
+// How long the windows have to stay unchanged before they're sent to the server, so dragging
+// one around doesn't send every step.
+const SAVE_AFTER_MS: u32 = 1000;
+
+// Keeps the server's copy of the runtime up to date with the open windows, so closing the tab
+// doesn't lose them. Whether they're reopened is up to WindowSettings::reopen_windows, see
+// power::boot. Called once by OperatingSystemProvider.
+pub fn provide_session_restore(system:RwSignal<SystemRuntime>) {
+    let windows = create_memo(move |_| system.with(|system| system.windows().clone()));
+    // Dropping the previous Timeout cancels it.
+    let timer = store_value(None::<gloo::timers::callback::Timeout>);
+    create_effect(move |first:Option<()>| {
+        let windows = windows();
+        // The first run has the windows the server sent.
+        if first.is_none() {
+            return;
+        }
+        timer.set_value(Some(gloo::timers::callback::Timeout::new(SAVE_AFTER_MS, move || spawn_local(async move {
+            let json = match serde_json::to_string(&windows) {
+                Ok(json) => json,
+                Err(err) => return log::error!("couldn't save the windows: {err}"),
+            };
+            if let Err(err) = save_windows(json).await {
+                log::error!("couldn't save the windows: {err}");
+            }
+        }))));
+    });
+    on_cleanup(move || timer.set_value(None));
+}
+
+// The state the app in window id saved last, or the default if it saved none or it doesn't
+// parse anymore.
+//
+//     let pane = session_restore::app_state::<Pane>(system, window.0);
+pub fn app_state<T:DeserializeOwned + Default>(system:RwSignal<SystemRuntime>, id:u64) -> T {
+    system.with_untracked(|system| system.windows().get(id).map(|window| window.state.clone()))
+        .and_then(|state| serde_json::from_value(state).ok())
+        .unwrap_or_default()
+}
+
+// Keeps state with window id, it's saved and restored with the window. Apps call this when
+// what they want back changes, like the open document or a scroll position.
+pub fn save_app_state<T:Serialize>(system:RwSignal<SystemRuntime>, id:u64, state:&T) {
+    match serde_json::to_value(state) {
+        Ok(state) => system.update(|system| system.windows_mut().set_state(id, state)),
+        Err(err) => log::error!("couldn't save the state of window {id}: {err}"),
+    }
+}
+
+// Replaces the stored runtime's windows with the WindowManager in json.
+#[server(SaveWindows, "/api")]
+pub async fn save_windows(json:String) -> Result<(),ServerFnError> {
+    let (session, state, account) = accounts::unlocked_account()?;
+    let key = state.runtime_key(&session, &account);
+    let windows = serde_json::from_str::<windows::WindowManager>(&json)
+        .map_err(|err| ServerFnError::Deserialization(err.to_string()))?;
+    state.update_runtime(&key, |runtime| runtime.restore_windows(windows));
+    Ok(())
+}
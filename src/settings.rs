@@ -0,0 +1,421 @@
+use super::*;
+use std::collections::BTreeMap;
+use file_system::SystemRuntime;
+
+pub const MIN_DOCK_SIZE: u32 = 32;
+pub const MAX_DOCK_SIZE: u32 = 96;
+// How long a generated wallpaper url is kept after the wallpaper changes, so the old image
+// doesn't flash away before the new one has loaded.
+const REVOKE_WALLPAPER_AFTER_MS: u32 = 1000;
+
+// Everything System Preferences changes. Kept in the SystemRuntime, so it's saved, exported
+// and restored with the rest of the desktop.
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+#[serde(default)]
+pub struct Settings{
+    pub appearance:Appearance,
+    pub dock:DockSettings,
+    pub date_time:DateTimeFormat,
+    pub shortcuts:Shortcuts,
+    pub windows:WindowSettings,
+    // App paths in the order the touch layout's home screen shows them, see mobile::HomeScreen.
+    pub home_screen:Vec<String>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct Appearance{
+    // A built-in theme or one in theme::THEMES_PATH, see theme::load.
+    pub theme:String,
+    pub accent:Accent,
+    pub wallpaper:Wallpaper,
+}
+
+impl Default for Appearance{
+    fn default() -> Self {
+        Self{theme:theme::DARK.to_string(),accent:Accent::default(),wallpaper:Wallpaper::Public("/bg.png".to_string())}
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,Serialize,Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum Accent{
+    #[default]
+    Blue,
+    Purple,
+    Pink,
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Graphite,
+}
+
+impl Accent{
+    pub const ALL: [Self;8] = [Self::Blue,Self::Purple,Self::Pink,Self::Red,Self::Orange,Self::Yellow,Self::Green,Self::Graphite];
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            Self::Blue => "#0a84ff",
+            Self::Purple => "#bf5af2",
+            Self::Pink => "#ff375f",
+            Self::Red => "#ff453a",
+            Self::Orange => "#ff9f0a",
+            Self::Yellow => "#ffd60a",
+            Self::Green => "#32d74b",
+            Self::Graphite => "#8e8e93",
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum Wallpaper{
+    // A url served from public/, like the config's wallpaper.
+    Public(String),
+    // An image in the FileSystem, shown through an object url.
+    File(String),
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct WindowSettings{
+    // Whether windows dropped at the edges of the desktop snap to them, see layout::snap_at.
+    pub snap_to_edges:bool,
+    pub tiling:layout::Tiling,
+    // Whether the windows open when the tab was closed or the user logged out come back, see
+    // session_restore. Otherwise booting quits every app.
+    pub reopen_windows:bool,
+}
+
+impl Default for WindowSettings{
+    fn default() -> Self {
+        Self{snap_to_edges:true,tiling:layout::Tiling::default(),reopen_windows:true}
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default,Serialize,Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum DockPosition{
+    #[default]
+    Bottom,
+    Left,
+    Right,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct DockSettings{
+    pub position:DockPosition,
+    // Icon size in px, between MIN_DOCK_SIZE and MAX_DOCK_SIZE.
+    pub size:u32,
+    pub autohide:bool,
+}
+
+impl Default for DockSettings{
+    fn default() -> Self {
+        Self{position:DockPosition::default(),size:64,autohide:false}
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct DateTimeFormat{
+    pub hour24:bool,
+    pub show_seconds:bool,
+    pub show_weekday:bool,
+    pub show_date:bool,
+}
+
+impl Default for DateTimeFormat{
+    fn default() -> Self {
+        Self{hour24:false,show_seconds:false,show_weekday:true,show_date:true}
+    }
+}
+
+impl DateTimeFormat{
+    // A time in milliseconds since the unix epoch, in the browser's time zone.
+    pub fn format(&self, millis:i64) -> String {
+        use chrono::TimeZone;
+        let Some(time) = chrono::Local.timestamp_millis_opt(millis).single() else { return String::new() };
+        let mut pattern = String::new();
+        if self.show_weekday {
+            pattern.push_str("%a ");
+        }
+        if self.show_date {
+            pattern.push_str("%b %-d ");
+        }
+        pattern.push_str(if self.hour24 { "%H:%M" } else { "%-I:%M" });
+        if self.show_seconds {
+            pattern.push_str(":%S");
+        }
+        if !self.hour24 {
+            pattern.push_str(" %p");
+        }
+        time.format(&pattern).to_string()
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Serialize,Deserialize)]
+pub enum ShortcutAction{
+    Spotlight,
+    LockScreen,
+    Preferences,
+    // Held down with shift to go backwards.
+    SwitchWindows,
+    Overview,
+    PreviousWorkspace,
+    NextWorkspace,
+    // Take the focused window along.
+    MoveToPreviousWorkspace,
+    MoveToNextWorkspace,
+    SnapLeft,
+    SnapRight,
+    // Toggles.
+    Maximize,
+    // Unsnaps the focused window.
+    RestoreSize,
+}
+
+impl ShortcutAction{
+    pub const ALL: [Self;13] = [
+        Self::Spotlight,Self::LockScreen,Self::Preferences,Self::SwitchWindows,Self::Overview,
+        Self::PreviousWorkspace,Self::NextWorkspace,Self::MoveToPreviousWorkspace,Self::MoveToNextWorkspace,
+        Self::SnapLeft,Self::SnapRight,Self::Maximize,Self::RestoreSize,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Spotlight => "Spotlight Search",
+            Self::LockScreen => "Lock Screen",
+            Self::Preferences => "System Preferences",
+            Self::SwitchWindows => "Switch Windows",
+            Self::Overview => "Mission Control",
+            Self::PreviousWorkspace => "Previous Desktop",
+            Self::NextWorkspace => "Next Desktop",
+            Self::MoveToPreviousWorkspace => "Move Window to Previous Desktop",
+            Self::MoveToNextWorkspace => "Move Window to Next Desktop",
+            Self::SnapLeft => "Snap Window Left",
+            Self::SnapRight => "Snap Window Right",
+            Self::Maximize => "Maximize Window",
+            Self::RestoreSize => "Restore Window Size",
+        }
+    }
+
+    pub fn default_combo(&self) -> KeyCombo {
+        match self {
+            Self::Spotlight => KeyCombo{code:"Space".to_string(),primary:true,alt:false,shift:false},
+            Self::LockScreen => KeyCombo{code:"KeyL".to_string(),primary:true,alt:false,shift:true},
+            Self::Preferences => KeyCombo{code:"Comma".to_string(),primary:true,alt:false,shift:false},
+            Self::SwitchWindows => KeyCombo{code:"Tab".to_string(),primary:false,alt:true,shift:false},
+            Self::Overview => KeyCombo{code:"ArrowUp".to_string(),primary:true,alt:false,shift:false},
+            Self::PreviousWorkspace => KeyCombo{code:"ArrowLeft".to_string(),primary:true,alt:true,shift:false},
+            Self::NextWorkspace => KeyCombo{code:"ArrowRight".to_string(),primary:true,alt:true,shift:false},
+            Self::MoveToPreviousWorkspace => KeyCombo{code:"ArrowLeft".to_string(),primary:true,alt:true,shift:true},
+            Self::MoveToNextWorkspace => KeyCombo{code:"ArrowRight".to_string(),primary:true,alt:true,shift:true},
+            Self::SnapLeft => KeyCombo{code:"ArrowLeft".to_string(),primary:true,alt:false,shift:true},
+            Self::SnapRight => KeyCombo{code:"ArrowRight".to_string(),primary:true,alt:false,shift:true},
+            Self::Maximize => KeyCombo{code:"ArrowUp".to_string(),primary:true,alt:false,shift:true},
+            Self::RestoreSize => KeyCombo{code:"ArrowDown".to_string(),primary:true,alt:false,shift:true},
+        }
+    }
+}
+
+// A key, by KeyboardEvent.code so it doesn't depend on the layout, and its modifiers. primary
+// is Cmd or Ctrl, whichever the keyboard has.
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct KeyCombo{
+    pub code:String,
+    pub primary:bool,
+    pub alt:bool,
+    pub shift:bool,
+}
+
+impl KeyCombo{
+    // None while only modifiers are held.
+    pub fn from_event(ev:&ev::KeyboardEvent) -> Option<Self> {
+        let code = ev.code();
+        if ["Meta","Control","Alt","Shift"].iter().any(|modifier| code.starts_with(modifier)) {
+            return None;
+        }
+        Some(Self{code,primary:ev.meta_key() || ev.ctrl_key(),alt:ev.alt_key(),shift:ev.shift_key()})
+    }
+
+    pub fn matches(&self, ev:&ev::KeyboardEvent) -> bool {
+        ev.code() == self.code
+            && (ev.meta_key() || ev.ctrl_key()) == self.primary
+            && ev.alt_key() == self.alt
+            && ev.shift_key() == self.shift
+    }
+
+    // Whether the modifiers other than shift are still held, the window switcher stays up
+    // until they're released.
+    pub fn modifiers_held(&self, ev:&ev::KeyboardEvent) -> bool {
+        (!self.primary || ev.meta_key() || ev.ctrl_key()) && (!self.alt || ev.alt_key()) && (self.primary || self.alt)
+    }
+
+    // Like "⌘⇧L".
+    pub fn label(&self) -> String {
+        let key = self.code.strip_prefix("Key")
+            .or_else(|| self.code.strip_prefix("Digit"))
+            .unwrap_or(match self.code.as_str() {
+                "Comma" => ",",
+                "Period" => ".",
+                "Slash" => "/",
+                "Tab" => "⇥",
+                "Backquote" => "`",
+                "ArrowUp" => "↑",
+                "ArrowDown" => "↓",
+                "ArrowLeft" => "←",
+                "ArrowRight" => "→",
+                code => code,
+            });
+        format!("{}{}{}{key}",
+            if self.primary { "⌘" } else { "" },
+            if self.alt { "⌥" } else { "" },
+            if self.shift { "⇧" } else { "" })
+    }
+}
+
+// Actions missing from the map, like ones added after the settings were saved, use their
+// default combo.
+#[derive(Debug,Clone,PartialEq,Default,Serialize,Deserialize)]
+pub struct Shortcuts(pub BTreeMap<ShortcutAction,KeyCombo>);
+
+impl Shortcuts{
+    pub fn combo(&self, action:ShortcutAction) -> KeyCombo {
+        self.0.get(&action).cloned().unwrap_or_else(|| action.default_combo())
+    }
+
+    pub fn matches(&self, action:ShortcutAction, ev:&ev::KeyboardEvent) -> bool {
+        self.combo(action).matches(ev)
+    }
+
+    // The action a key press triggers, if any.
+    pub fn action(&self, ev:&ev::KeyboardEvent) -> Option<ShortcutAction> {
+        ShortcutAction::ALL.into_iter().find(|action| self.matches(*action, ev))
+    }
+}
+
+// The Settings of the runtime in context. Reading through the store only notifies when the
+// settings change, not on every change to the runtime, and select narrows that further.
+#[derive(Debug,Clone,Copy)]
+pub struct SettingsStore{
+    system:RwSignal<SystemRuntime>,
+    settings:Memo<Settings>,
+}
+
+impl SettingsStore{
+    pub fn get(&self) -> Settings {
+        self.settings.get()
+    }
+
+    pub fn with<T>(&self, f:impl FnOnce(&Settings) -> T) -> T {
+        self.settings.with(f)
+    }
+
+    pub fn with_untracked<T>(&self, f:impl FnOnce(&Settings) -> T) -> T {
+        self.settings.with_untracked(f)
+    }
+
+    // A memo of one part of the settings, for components that only care about that part.
+    pub fn select<T:Clone + PartialEq + 'static>(&self, f:impl Fn(&Settings) -> T + 'static) -> Memo<T> {
+        let settings = self.settings;
+        create_memo(move |_| settings.with(&f))
+    }
+
+    pub fn update(&self, f:impl FnOnce(&mut Settings)) {
+        self.system.update(|system| f(system.settings_mut()));
+    }
+}
+
+// Provides the SettingsStore and keeps the page's theme and wallpaper in step with it. Called
+// once by OperatingSystemProvider.
+pub fn provide_settings(system:RwSignal<SystemRuntime>) -> SettingsStore {
+    let settings = create_memo(move |_| system.with(|system| system.settings().clone()));
+    let store = SettingsStore{system,settings};
+    provide_context(store);
+    let appearance = store.select(|settings| settings.appearance.clone());
+    // The theme's name and, for theme files, when the file was last changed, so editing the
+    // chosen theme's file shows right away.
+    let theme_version = create_memo(move |_| {
+        let name = appearance.with(|appearance| appearance.theme.clone());
+        let modified = system.with(|system| system.file_system().get_file_metadata(&theme::theme_path(&name))
+            .map(|metadata| metadata.modified)
+            .ok());
+        (name, modified)
+    });
+    let theme = create_memo(move |_| {
+        let (name, _) = theme_version();
+        system.try_update_untracked(|system| theme::load_or_default(system.file_system_mut(), &name))
+            .unwrap_or_default()
+    });
+    // Workspaces can have a wallpaper of their own.
+    let wallpaper = create_memo(move |_| system.with(|system| system.wallpaper().clone()));
+    let object_url = store_value(None::<String>);
+    create_effect(move |_| {
+        let appearance = appearance();
+        let theme = theme();
+        let (url, generated) = match &wallpaper() {
+            Wallpaper::Public(url) => (Some(url.clone()), None),
+            Wallpaper::File(path) => {
+                let url = wallpaper_object_url(system, path);
+                (url.clone(), url)
+            },
+        };
+        if let Some(old) = object_url.get_value() {
+            gloo::timers::callback::Timeout::new(REVOKE_WALLPAPER_AFTER_MS, move || {
+                _ = web_sys::Url::revoke_object_url(&old);
+            }).forget();
+        }
+        object_url.set_value(generated);
+        set_font_stylesheet(theme.font.url.as_deref());
+        let Some(body) = document().body() else { return };
+        _ = body.set_attribute("style", &body_style(&theme, &appearance, url.as_deref()));
+        _ = body.set_attribute("data-theme", &appearance.theme);
+    });
+    store
+}
+
+// Points the font <link> rendered by lib.rs's AppearanceBody at url, adding it if the first
+// theme had no font to load.
+fn set_font_stylesheet(url:Option<&str>) {
+    let link = match document().get_element_by_id(theme::FONT_STYLESHEET_ID) {
+        Some(link) => link,
+        None => {
+            let (Ok(link), Some(head)) = (document().create_element("link"), document().head()) else { return };
+            _ = link.set_attribute("id", theme::FONT_STYLESHEET_ID);
+            _ = link.set_attribute("rel", "stylesheet");
+            _ = head.append_child(&link);
+            link
+        },
+    };
+    _ = link.set_attribute("href", url.unwrap_or_default());
+}
+
+fn wallpaper_object_url(system:RwSignal<SystemRuntime>, path:&str) -> Option<String> {
+    let result = system.try_update_untracked(|system| {
+        let file_system = system.file_system_mut();
+        let mime = file_system.get_file_metadata(path)
+            .map(|metadata| metadata.mime_type.clone().unwrap_or_else(|| mime::OCTET_STREAM.to_string()))
+            .map_err(|err| err.to_string())?;
+        let content = file_system.read_file(path).map_err(|err| err.to_string())?;
+        export::object_url(&mime, &content).map_err(|err| format!("{err:?}"))
+    })?;
+    result.map_err(|err| log::error!("couldn't show {path} as the wallpaper: {err}")).ok()
+}
+
+// The body's style attribute. wallpaper_url is None when the wallpaper can't be shown, like a
+// file in the FileSystem while rendering on the server.
+pub fn body_style(theme:&theme::Theme, appearance:&Appearance, wallpaper_url:Option<&str>) -> String {
+    let background = wallpaper_url.map(|url| format!("background-image: url(\"{url}\");")).unwrap_or_default();
+    format!("
+            {variables}
+            font-family: var(--font-family);
+            color: rgb(var(--color-content));
+            background-color: rgb(var(--color-backdrop));
+            {background}
+            background-repeat: repeat;
+            --accent: {accent};
+            accent-color: var(--accent);", variables = theme.css_variables(), accent = appearance.accent.color())
+}
@@ -0,0 +1,285 @@
+use super::*;
+use file_system::{FsError,SystemRuntime,normalize_path};
+use vfs::MountSource;
+
+// The terminal app, see desktop.toml.
+pub const TERMINAL_PATH: &'static str = "/terminal";
+
+// Older commands are dropped past this many.
+const HISTORY_LEN: usize = 200;
+
+// The command interpreter behind the terminal app. Each terminal window keeps its own Shell,
+// the FileSystem it works on is the shared one in SystemRuntime. It's the window's app state,
+// so the working directory and history come back with the session.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct Shell{
+    cwd:String,
+    history:Vec<HistoryEntry>,
+}
+
+impl Default for Shell{
+    fn default() -> Self {
+        Self{cwd:file_system::DESKTOP_PATH.to_string(),history:Vec::new()}
+    }
+}
+
+// A command that was run and what it printed, as the terminal shows it.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct HistoryEntry{
+    pub cwd:String,
+    pub line:String,
+    pub output:String,
+}
+
+impl Shell{
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    // Oldest first.
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    // Relative paths are relative to the working directory.
+    pub fn absolute(&self, path:&str) -> String {
+        if path.starts_with('/') {
+            normalize_path(path)
+        } else {
+            normalize_path(&format!("{}/{path}", self.cwd))
+        }
+    }
+
+    // Runs one command line and adds it to the history, returning what it prints. `clear`
+    // empties the history instead.
+    pub fn run(&mut self, system:RwSignal<SystemRuntime>, line:&str) -> String {
+        match line.trim() {
+            "" => return String::new(),
+            "clear" => {
+                self.history.clear();
+                return String::new();
+            },
+            _ => {},
+        }
+        let cwd = self.cwd.clone();
+        let output = self.execute(system, line);
+        self.history.push(HistoryEntry{cwd,line:line.trim().to_string(),output:output.clone()});
+        let excess = self.history.len().saturating_sub(HISTORY_LEN);
+        self.history.drain(..excess);
+        output
+    }
+
+    // Arguments are split on whitespace, there is no quoting.
+    fn execute(&mut self, system:RwSignal<SystemRuntime>, line:&str) -> String {
+        let args = line.split_whitespace().collect::<Vec<&str>>();
+        let Some((command,args)) = args.split_first() else { return String::new() };
+        let result = match *command {
+            "pwd" => Ok(self.cwd.clone()),
+            "cd" => self.cd(system, args.first().copied().unwrap_or(file_system::DESKTOP_PATH)),
+            "ls" => self.ls(system, args.first().copied().unwrap_or(".")),
+            "cat" => self.cat(system, args),
+            "mkdir" => self.each(system, args, |system,path| system.file_system_mut().create_dir(path.to_string())),
+            "rm" => self.each(system, args, |system,path| system.file_system_mut().remove_file(path)),
+            "ln" => self.ln(system, args),
+            "mv" => self.mv(system, args),
+            "chmod" => self.chmod(system, args),
+            "mount" => self.mount(system, args),
+            "umount" => self.each(system, args, |system,path| system.unmount(path)),
+            command => Ok(format!("{command}: command not found")),
+        };
+        result.unwrap_or_else(|err| format!("{command}: {err}"))
+    }
+
+    fn cd(&mut self, system:RwSignal<SystemRuntime>, path:&str) -> Result<String,FsError> {
+        let path = self.absolute(path);
+        system.with_untracked(|system| system.file_system().list_dir(&path))?;
+        self.cwd = path;
+        Ok(String::new())
+    }
+
+    fn ls(&self, system:RwSignal<SystemRuntime>, path:&str) -> Result<String,FsError> {
+        let path = self.absolute(path);
+        let entries = system.with_untracked(|system| system.file_system().dir_entries(&path))?;
+        Ok(entries.into_iter()
+            .map(|entry| if entry.file_type == file_system::FileType::Directory { format!("{}/", entry.name) } else { entry.name })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn cat(&self, system:RwSignal<SystemRuntime>, paths:&[&str]) -> Result<String,FsError> {
+        let mut out = String::new();
+        for path in paths {
+            let path = self.absolute(path);
+            let content = system.try_update(|system| system.file_system_mut().read_file(&path).map(|content| content.into_owned()))
+                .unwrap_or_else(|| Err(FsError::NotFound(path.clone())))?;
+            out.push_str(&String::from_utf8_lossy(&content));
+        }
+        Ok(out)
+    }
+
+    fn ln(&self, system:RwSignal<SystemRuntime>, args:&[&str]) -> Result<String,FsError> {
+        let ["-s", target, link] = args else { return Ok("usage: ln -s target link".to_string()) };
+        let link = self.absolute(link);
+        self.update(system, |system| system.file_system_mut().symlink(target, link))?;
+        Ok(String::new())
+    }
+
+    fn mv(&self, system:RwSignal<SystemRuntime>, args:&[&str]) -> Result<String,FsError> {
+        let [from, to] = args else { return Ok("usage: mv from to".to_string()) };
+        let (from,to) = (self.absolute(from),self.absolute(to));
+        self.update(system, |system| system.file_system_mut().rename(&from, &to))?;
+        Ok(String::new())
+    }
+
+    fn chmod(&self, system:RwSignal<SystemRuntime>, args:&[&str]) -> Result<String,FsError> {
+        let [mode, paths @ ..] = args else { return Ok("usage: chmod mode path...".to_string()) };
+        let Ok(mode) = u16::from_str_radix(mode, 8) else { return Ok(format!("invalid mode: {mode}")) };
+        self.each(system, paths, |system,path| system.chmod(path, mode))
+    }
+
+    // mount                         lists mounts
+    // mount -t memory dir
+    // mount -t local prefix dir     localStorage keys under prefix
+    // mount -t server root dir      a folder on the site's server
+    // mount -t public dir           the site's public assets
+    // mount -t s3 bucket_url dir
+    // mount archive.zip             an archive at its own path
+    fn mount(&self, system:RwSignal<SystemRuntime>, args:&[&str]) -> Result<String,FsError> {
+        let (source,dir) = match args {
+            [] => return Ok(system.with_untracked(|system| system.file_system().mounts()).into_iter()
+                .map(|(dir,source)| format!("{source} on {dir} type {}", source.type_name()))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            [archive] => (MountSource::Archive,*archive),
+            ["-t","memory",dir] => (MountSource::Memory,*dir),
+            ["-t","local",prefix,dir] => (MountSource::LocalStorage{prefix:prefix.to_string()},*dir),
+            ["-t","server",root,dir] => (MountSource::Server{root:root.to_string()},*dir),
+            ["-t","public",dir] => (MountSource::PublicAssets,*dir),
+            ["-t","s3",bucket_url,dir] => (MountSource::ObjectStorage{bucket_url:bucket_url.to_string()},*dir),
+            _ => return Ok("usage: mount [-t memory|local|server|public|s3 [source]] dir".to_string()),
+        };
+        let dir = self.absolute(dir);
+        if source.is_remote() {
+            spawn_local(async move {
+                if let Err(err) = vfs::mount_remote(system, dir.clone(), source).await {
+                    log::error!("mount {dir}: {err}");
+                }
+            });
+            return Ok(String::new());
+        }
+        self.update(system, |system| system.mount(&dir, source))?;
+        Ok(String::new())
+    }
+
+    // Applies f to every path argument, stopping at the first error.
+    fn each(&self, system:RwSignal<SystemRuntime>, paths:&[&str], f:impl Fn(&mut SystemRuntime,&str) -> Result<(),FsError>) -> Result<String,FsError> {
+        for path in paths {
+            let path = self.absolute(path);
+            self.update(system, |system| f(system, &path))?;
+        }
+        Ok(String::new())
+    }
+
+    fn update(&self, system:RwSignal<SystemRuntime>, f:impl FnOnce(&mut SystemRuntime) -> Result<(),FsError>) -> Result<(),FsError> {
+        system.try_update(f).unwrap_or(Ok(()))
+    }
+}
+
+// The content of a terminal window, see windows::WindowContent. The up and down arrows go
+// through the history.
+#[component]
+pub fn Terminal() -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let window = expect_context::<windows::WindowId>();
+    let shell = create_rw_signal(session_restore::app_state::<Shell>(system, window.0));
+    let (input, set_input) = create_signal(String::new());
+    // How many commands back the arrows have gone, None while typing a new one.
+    let recalled = create_rw_signal(None::<usize>);
+    let recall = move |back:Option<usize>| {
+        recalled.set(back);
+        let line = back.and_then(|back| shell.with_untracked(|shell| shell.history.iter().rev().nth(back).map(|entry| entry.line.clone())));
+        set_input(line.unwrap_or_default());
+    };
+    let on_keydown = move |ev:ev::KeyboardEvent| match ev.key().as_str() {
+        "Enter" => {
+            // Run outside shell.update, commands update the system and its effects may read the shell.
+            let mut current = shell.get_untracked();
+            current.run(system, &input.get_untracked());
+            session_restore::save_app_state(system, window.0, &current);
+            shell.set(current);
+            recall(None);
+        },
+        "ArrowUp" => {
+            ev.prevent_default();
+            let back = recalled.get_untracked().map_or(0, |back| back + 1);
+            if back < shell.with_untracked(|shell| shell.history.len()) {
+                recall(Some(back));
+            }
+        },
+        "ArrowDown" => {
+            ev.prevent_default();
+            recall(recalled.get_untracked().and_then(|back| back.checked_sub(1)));
+        },
+        _ => {},
+    };
+    view!{
+        <div class="h-full overflow-y-auto p-2 font-mono text-sm">
+            {move || shell.with(|shell| shell.history.iter().map(|entry| view!{
+                <p><span class="text-muted">{entry.cwd.clone()}" $ "</span>{entry.line.clone()}</p>
+                {(!entry.output.is_empty()).then(|| view!{<pre class="whitespace-pre-wrap">{entry.output.clone()}</pre>})}
+            }).collect_view())}
+            <div class="flex gap-2">
+                <span class="text-muted">{move || shell.with(|shell| shell.cwd.clone())}" $"</span>
+                <input type="text" class="flex-1 bg-transparent outline-none"
+                prop:value=input
+                on:input=move |ev| set_input(event_target_value(&ev))
+                on:keydown=on_keydown/>
+            </div>
+        </div>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_system::FileSystem;
+
+    #[test]
+    fn history_comes_back_with_the_window() {
+        let runtime = create_runtime();
+        let system = create_rw_signal(SystemRuntime::new(FileSystem::new(), Vec::new()));
+        let mut shell = Shell::default();
+        assert_eq!(shell.run(system, "pwd"), file_system::DESKTOP_PATH);
+        shell.run(system, "  ");
+        shell.run(system, "cd /");
+        shell.run(system, "nope");
+        let lines = shell.history().iter().map(|entry| (entry.cwd.as_str(),entry.line.as_str(),entry.output.as_str())).collect::<Vec<_>>();
+        assert_eq!(lines, vec![
+            (file_system::DESKTOP_PATH, "pwd", file_system::DESKTOP_PATH),
+            (file_system::DESKTOP_PATH, "cd /", ""),
+            ("/", "nope", "nope: command not found"),
+        ]);
+        let id = system.try_update(|system| system.windows_mut().open(TERMINAL_PATH, "Terminal".to_string())).unwrap();
+        session_restore::save_app_state(system, id, &shell);
+        assert_eq!(session_restore::app_state::<Shell>(system, id), shell);
+        // A window without any state gets a fresh shell.
+        assert_eq!(session_restore::app_state::<Shell>(system, id + 1), Shell::default());
+        runtime.dispose();
+    }
+
+    #[test]
+    fn history_is_bounded_and_cleared() {
+        let runtime = create_runtime();
+        let system = create_rw_signal(SystemRuntime::new(FileSystem::new(), Vec::new()));
+        let mut shell = Shell::default();
+        for i in 0..HISTORY_LEN + 5 {
+            shell.run(system, &format!("echo{i}"));
+        }
+        assert_eq!(shell.history().len(), HISTORY_LEN);
+        assert_eq!(shell.history()[0].line, "echo5");
+        shell.run(system, "clear");
+        assert!(shell.history().is_empty());
+        runtime.dispose();
+    }
+}
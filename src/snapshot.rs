@@ -0,0 +1,508 @@
+use super::*;
+use file_system::SystemRuntime;
+use serde_json::Value;
+use wasm_bindgen_futures::JsFuture;
+use notifications::{Notification,Notifications,Severity,notify};
+
+// Bumped whenever SystemRuntime's serialized shape changes, with the old shape frozen in
+// versions and a step to the new one added to upgrade.
+pub const SNAPSHOT_VERSION: u32 = 6;
+// Starts every binary snapshot, so a disk image can be told apart from a JSON one.
+const BINARY_MAGIC: &[u8;8] = b"SITEOSIM";
+// Name a disk image downloads as.
+pub const DISK_IMAGE_NAME: &'static str = "site_os.img";
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum SnapshotError{
+    Encode(String),
+    Decode(String),
+    // Written by a newer site_os than this one, or not a snapshot at all.
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for SnapshotError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Encode(reason) => write!(f,"couldn't encode snapshot: {reason}"),
+            Self::Decode(reason) => write!(f,"couldn't decode snapshot: {reason}"),
+            Self::UnsupportedVersion(version) => write!(f,"unsupported snapshot version {version}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError{}
+
+#[derive(Serialize)]
+struct SnapshotRef<'a>{
+    version:u32,
+    runtime:&'a SystemRuntime,
+}
+
+pub fn to_json(runtime:&SystemRuntime) -> Result<String,SnapshotError> {
+    serde_json::to_string(&SnapshotRef{version:SNAPSHOT_VERSION,runtime})
+        .map_err(|err| SnapshotError::Encode(err.to_string()))
+}
+
+pub fn from_json(json:&str) -> Result<SystemRuntime,SnapshotError> {
+    let value = serde_json::from_str::<Value>(json).map_err(|err| SnapshotError::Decode(err.to_string()))?;
+    migrate(value)
+}
+
+// The magic bytes followed by deflated MessagePack. Fields are kept by name so old binary
+// snapshots can be migrated like json ones.
+pub fn to_binary(runtime:&SystemRuntime) -> Result<Vec<u8>,SnapshotError> {
+    let packed = rmp_serde::to_vec_named(&SnapshotRef{version:SNAPSHOT_VERSION,runtime})
+        .map_err(|err| SnapshotError::Encode(err.to_string()))?;
+    let mut out = BINARY_MAGIC.to_vec();
+    out.extend(miniz_oxide::deflate::compress_to_vec(&packed, 6));
+    Ok(out)
+}
+
+pub fn from_binary(data:&[u8]) -> Result<SystemRuntime,SnapshotError> {
+    let packed = data.strip_prefix(BINARY_MAGIC.as_slice())
+        .ok_or_else(|| SnapshotError::Decode("not a site_os disk image".to_string()))?;
+    let packed = miniz_oxide::inflate::decompress_to_vec(packed)
+        .map_err(|err| SnapshotError::Decode(format!("{err:?}")))?;
+    let value = rmp_serde::from_slice::<Value>(&packed).map_err(|err| SnapshotError::Decode(err.to_string()))?;
+    migrate(value)
+}
+
+// Either format, told apart by the binary magic.
+pub fn decode(data:&[u8]) -> Result<SystemRuntime,SnapshotError> {
+    if data.starts_with(BINARY_MAGIC) {
+        from_binary(data)
+    } else {
+        from_json(&String::from_utf8_lossy(data))
+    }
+}
+
+// Binary snapshots are migrated the same way, they are decoded to a json Value first.
+fn migrate(mut value:Value) -> Result<SystemRuntime,SnapshotError> {
+    let version = value.get("version").and_then(Value::as_u64)
+        .ok_or_else(|| SnapshotError::Decode("missing version".to_string()))? as u32;
+    if version == 0 || version > SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let mut runtime = value["runtime"].take();
+    for from in version..SNAPSHOT_VERSION {
+        runtime = upgrade(from, runtime)?;
+    }
+    parse(runtime)
+}
+
+fn parse<T:serde::de::DeserializeOwned>(value:Value) -> Result<T,SnapshotError> {
+    serde_json::from_value(value).map_err(|err| SnapshotError::Decode(err.to_string()))
+}
+
+// Upgrades the runtime of a version from snapshot to version from + 1.
+fn upgrade(from:u32, runtime:Value) -> Result<Value,SnapshotError> {
+    use versions::*;
+    match from {
+        1 => step::<V1,V2>(runtime),
+        2 => step::<V2,V3>(runtime),
+        3 => step::<V3,V4>(runtime),
+        4 => step::<V4,V5>(runtime),
+        5 => step::<V5,V6>(runtime),
+        _ => Err(SnapshotError::UnsupportedVersion(from)),
+    }
+}
+
+fn step<Old:serde::de::DeserializeOwned, New:From<Old> + Serialize>(runtime:Value) -> Result<Value,SnapshotError> {
+    serde_json::to_value(New::from(parse::<Old>(runtime)?)).map_err(|err| SnapshotError::Encode(err.to_string()))
+}
+
+// The runtime as each past version serialized it, frozen so upgrading doesn't depend on what
+// the types look like today. Parts no upgrade touches are kept as they are.
+mod versions{
+    use super::*;
+
+    // In every version so far.
+    #[derive(Serialize,Deserialize)]
+    pub struct Base{
+        pub running_app_paths:Vec<String>,
+        pub file_system:Value,
+        pub task_bar_paths:Vec<String>,
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct V1{
+        #[serde(flatten)]
+        pub base:Base,
+    }
+
+    // Settings were added.
+    #[derive(Serialize,Deserialize)]
+    pub struct V2{
+        #[serde(flatten)]
+        pub base:Base,
+        pub settings:Value,
+    }
+
+    impl From<V1> for V2{
+        fn from(V1{base}:V1) -> Self {
+            // Every field of the settings has a default, so an empty object is the defaults.
+            Self{base,settings:Value::Object(Default::default())}
+        }
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct Rect{
+        pub x:f64,
+        pub y:f64,
+        pub width:f64,
+        pub height:f64,
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct WindowV3{
+        pub id:u64,
+        pub app:String,
+        pub title:String,
+        pub rect:Rect,
+        pub minimized:bool,
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct WindowManagerV3{
+        pub next_id:u64,
+        pub windows:Vec<WindowV3>,
+        pub mru:Vec<u64>,
+    }
+
+    // Windows were added.
+    #[derive(Serialize,Deserialize)]
+    pub struct V3{
+        #[serde(flatten)]
+        pub base:Base,
+        pub settings:Value,
+        pub windows:WindowManagerV3,
+    }
+
+    impl From<V2> for V3{
+        fn from(V2{mut base,settings}:V2) -> Self {
+            // Apps running before have no windows, so they're quit.
+            base.running_app_paths.clear();
+            Self{base,settings,windows:WindowManagerV3{next_id:0,windows:Vec::new(),mru:Vec::new()}}
+        }
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct WindowV4{
+        #[serde(flatten)]
+        pub window:WindowV3,
+        pub workspace:u64,
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct Workspace{
+        pub id:u64,
+        pub wallpaper:Option<Value>,
+    }
+
+    // From version 4 on only the windows change.
+    #[derive(Serialize,Deserialize)]
+    pub struct WindowManager<W>{
+        pub next_id:u64,
+        pub windows:Vec<W>,
+        pub mru:Vec<u64>,
+        pub workspaces:Vec<Workspace>,
+        pub next_workspace_id:u64,
+        pub current:u64,
+    }
+
+    impl<W> WindowManager<W>{
+        fn map<N>(self, f:impl FnMut(W) -> N) -> WindowManager<N> {
+            let Self{next_id,windows,mru,workspaces,next_workspace_id,current} = self;
+            WindowManager{next_id,windows:windows.into_iter().map(f).collect(),mru,workspaces,next_workspace_id,current}
+        }
+    }
+
+    // Workspaces were added.
+    #[derive(Serialize,Deserialize)]
+    pub struct V4{
+        #[serde(flatten)]
+        pub base:Base,
+        pub settings:Value,
+        pub windows:WindowManager<WindowV4>,
+    }
+
+    impl From<V3> for V4{
+        fn from(V3{base,settings,windows}:V3) -> Self {
+            // The windows go on the first workspace.
+            let WindowManagerV3{next_id,windows,mru} = windows;
+            let windows = WindowManager{
+                next_id,
+                windows:windows.into_iter().map(|window| WindowV4{window,workspace:1}).collect(),
+                mru,
+                workspaces:vec![Workspace{id:1,wallpaper:None}],
+                next_workspace_id:1,
+                current:1,
+            };
+            Self{base,settings,windows}
+        }
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct WindowV5{
+        #[serde(flatten)]
+        pub window:WindowV4,
+        pub snap:Option<String>,
+        pub restore:Option<Rect>,
+    }
+
+    // Windows can be snapped.
+    #[derive(Serialize,Deserialize)]
+    pub struct V5{
+        #[serde(flatten)]
+        pub base:Base,
+        pub settings:Value,
+        pub windows:WindowManager<WindowV5>,
+    }
+
+    impl From<V4> for V5{
+        fn from(V4{base,settings,windows}:V4) -> Self {
+            Self{base,settings,windows:windows.map(|window| WindowV5{window,snap:None,restore:None})}
+        }
+    }
+
+    #[derive(Serialize,Deserialize)]
+    pub struct WindowV6{
+        #[serde(flatten)]
+        pub window:WindowV5,
+        pub state:Value,
+    }
+
+    // Windows keep their app's state.
+    #[derive(Serialize,Deserialize)]
+    pub struct V6{
+        #[serde(flatten)]
+        pub base:Base,
+        pub settings:Value,
+        pub windows:WindowManager<WindowV6>,
+    }
+
+    impl From<V5> for V6{
+        fn from(V5{base,settings,windows}:V5) -> Self {
+            Self{base,settings,windows:windows.map(|window| WindowV6{window,state:Value::Null})}
+        }
+    }
+}
+
+// Downloads the whole runtime as a binary disk image.
+pub fn export_disk_image(system:RwSignal<SystemRuntime>, notifications:RwSignal<Notifications>) {
+    let result = system.with_untracked(to_binary)
+        .map_err(|err| wasm_bindgen::JsValue::from_str(&err.to_string()))
+        .and_then(|image| export::download(DISK_IMAGE_NAME, mime::OCTET_STREAM, &image));
+    if let Err(err) = result {
+        notify(notifications, Notification::new(Severity::Error, "Exporting the disk image failed").with_body(format!("{err:?}")));
+    }
+}
+
+// Opens the browser's file picker and replaces the runtime with the chosen disk image.
+// Remote mounts in the image are mounted again once their listings arrive.
+pub fn import_disk_image(system:RwSignal<SystemRuntime>, notifications:RwSignal<Notifications>) {
+    import::pick(Some(".img,.json"), false, move |files| {
+        let Some(file) = files.into_iter().next() else { return };
+        spawn_local(async move {
+            let failed = || Notification::new(Severity::Error, format!("Importing {} failed", file.name()));
+            let data = match JsFuture::from(file.array_buffer()).await {
+                Ok(buffer) => js_sys::Uint8Array::new(&buffer).to_vec(),
+                Err(err) => {
+                    notify(notifications, failed().with_body(format!("{err:?}")));
+                    return;
+                },
+            };
+            match decode(&data) {
+                Ok(runtime) => {
+                    restore(system, runtime);
+                    notify(notifications, Notification::new(Severity::Success, format!("Imported {}", file.name())));
+                },
+                Err(err) => {
+                    notify(notifications, failed().with_body(err.to_string()));
+                },
+            }
+        });
+    });
+}
+
+// Swaps in a deserialized runtime and remounts what it couldn't mount itself.
+pub fn restore(system:RwSignal<SystemRuntime>, runtime:SystemRuntime) {
+    system.set(runtime);
+    remount_detached(system);
+}
+
+// Mounts again what the runtime's FileSystem couldn't when it was deserialized, remote mounts
+// once their listings arrive.
+pub fn remount_detached(system:RwSignal<SystemRuntime>) {
+    let detached = system.try_update(|system| system.file_system_mut().take_detached_mounts()).unwrap_or_default();
+    for (dir, source) in detached {
+        if !source.is_remote() {
+            log::error!("{source} on {dir} couldn't be mounted again");
+            continue;
+        }
+        spawn_local(async move {
+            if let Err(err) = vfs::mount_remote(system, dir.clone(), source).await {
+                log::error!("mounting {dir} again failed: {err}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // A file system with only its root, as every version so far has serialized it.
+    fn file_system() -> Value {
+        json!({
+            "tree": {"/": {"name": "", "metadata": {
+                "accessed": 1, "created": 1, "modified": 1, "file_type": "Directory",
+                "img_src": "/hard-disk.png", "link_target": null, "owner": 0, "group": 0,
+                "mode": 493, "mime_type": null,
+            }, "content": []}},
+            "user": {"uid": 0, "gid": 0},
+            "mounts": [],
+        })
+    }
+
+    fn v1() -> Value {
+        json!({"version": 1, "runtime": {
+            "running_app_paths": ["/apps/terminal"],
+            "file_system": file_system(),
+            "task_bar_paths": ["/apps/finder"],
+        }})
+    }
+
+    fn v2() -> Value {
+        let mut snapshot = v1();
+        snapshot["version"] = json!(2);
+        snapshot["runtime"]["settings"] = json!({});
+        snapshot
+    }
+
+    fn window_v3() -> Value {
+        json!({"id": 3, "app": "/apps/finder", "title": "Finder",
+            "rect": {"x": 10.0, "y": 20.0, "width": 300.0, "height": 200.0}, "minimized": false})
+    }
+
+    fn v3() -> Value {
+        let mut snapshot = v2();
+        snapshot["version"] = json!(3);
+        snapshot["runtime"]["running_app_paths"] = json!(["/apps/finder"]);
+        snapshot["runtime"]["windows"] = json!({"next_id": 4, "windows": [window_v3()], "mru": [3]});
+        snapshot
+    }
+
+    fn v4() -> Value {
+        let mut window = window_v3();
+        window["workspace"] = json!(2);
+        let mut snapshot = v3();
+        snapshot["version"] = json!(4);
+        snapshot["runtime"]["windows"] = json!({
+            "next_id": 4, "windows": [window], "mru": [3],
+            "workspaces": [{"id": 1, "wallpaper": null}, {"id": 2, "wallpaper": null}],
+            "next_workspace_id": 2, "current": 2,
+        });
+        snapshot
+    }
+
+    fn v5() -> Value {
+        let mut snapshot = v4();
+        snapshot["version"] = json!(5);
+        let window = &mut snapshot["runtime"]["windows"]["windows"][0];
+        window["snap"] = json!("left");
+        window["restore"] = json!({"x": 1.0, "y": 2.0, "width": 3.0, "height": 4.0});
+        snapshot
+    }
+
+    fn upgraded(snapshot:Value) -> Value {
+        let version = snapshot["version"].as_u64().unwrap() as u32;
+        upgrade(version, snapshot["runtime"].clone()).unwrap()
+    }
+
+    #[test]
+    fn v1_gets_default_settings() {
+        let runtime = upgraded(v1());
+        assert_eq!(runtime["settings"], json!({}));
+        assert_eq!(runtime["running_app_paths"], json!(["/apps/terminal"]));
+        assert_eq!(runtime["file_system"], file_system());
+    }
+
+    #[test]
+    fn v2_quits_apps_without_windows() {
+        let runtime = upgraded(v2());
+        assert_eq!(runtime["running_app_paths"], json!([]));
+        assert_eq!(runtime["windows"], json!({"next_id": 0, "windows": [], "mru": []}));
+        assert_eq!(runtime["task_bar_paths"], json!(["/apps/finder"]));
+    }
+
+    #[test]
+    fn v3_puts_windows_on_the_first_workspace() {
+        let runtime = upgraded(v3());
+        let windows = &runtime["windows"];
+        assert_eq!(windows["workspaces"], json!([{"id": 1, "wallpaper": null}]));
+        assert_eq!(windows["current"], json!(1));
+        assert_eq!(windows["next_workspace_id"], json!(1));
+        assert_eq!(windows["next_id"], json!(4));
+        let mut expected = window_v3();
+        expected["workspace"] = json!(1);
+        assert_eq!(windows["windows"], json!([expected]));
+    }
+
+    #[test]
+    fn v4_windows_are_unsnapped() {
+        let runtime = upgraded(v4());
+        let window = &runtime["windows"]["windows"][0];
+        assert_eq!(window["snap"], Value::Null);
+        assert_eq!(window["restore"], Value::Null);
+        assert_eq!(window["workspace"], json!(2));
+        assert_eq!(runtime["windows"]["current"], json!(2));
+    }
+
+    #[test]
+    fn v5_windows_have_no_state() {
+        let runtime = upgraded(v5());
+        let window = &runtime["windows"]["windows"][0];
+        assert_eq!(window["state"], Value::Null);
+        assert_eq!(window["snap"], json!("left"));
+        assert_eq!(window["restore"], json!({"x": 1.0, "y": 2.0, "width": 3.0, "height": 4.0}));
+    }
+
+    #[test]
+    fn every_version_decodes() {
+        for snapshot in [v1(), v2(), v3(), v4(), v5()] {
+            let version = snapshot["version"].clone();
+            let runtime = from_json(&snapshot.to_string());
+            assert!(runtime.is_ok(), "version {version}: {runtime:?}");
+        }
+    }
+
+    #[test]
+    fn past_snapshots_survive_a_round_trip() {
+        let runtime = from_json(&v5().to_string()).unwrap();
+        assert_eq!(from_json(&to_json(&runtime).unwrap()).unwrap(), runtime);
+        assert_eq!(from_binary(&to_binary(&runtime).unwrap()).unwrap(), runtime);
+        assert_eq!(decode(&to_binary(&runtime).unwrap()).unwrap(), runtime);
+    }
+
+    #[test]
+    fn windows_need_a_workspace() {
+        let mut snapshot = v5();
+        snapshot["runtime"]["windows"]["workspaces"] = json!([]);
+        assert!(matches!(from_json(&snapshot.to_string()), Err(SnapshotError::Decode(_))));
+
+        // A current workspace that's gone falls back to the first one.
+        let mut snapshot = v5();
+        snapshot["runtime"]["windows"]["current"] = json!(7);
+        let runtime = from_json(&snapshot.to_string()).unwrap();
+        assert_eq!(runtime.windows().current_workspace().id, 1);
+    }
+
+    #[test]
+    fn unknown_versions_are_refused() {
+        let mut snapshot = v1();
+        snapshot["version"] = json!(SNAPSHOT_VERSION + 1);
+        assert_eq!(from_json(&snapshot.to_string()), Err(SnapshotError::UnsupportedVersion(SNAPSHOT_VERSION + 1)));
+    }
+}
@@ -0,0 +1,245 @@
+use super::*;
+use file_system::{FileSystem,SystemRuntime};
+
+// Where user themes are read from, one TOML file per theme named after it. See desktop.toml.
+pub const THEMES_PATH: &'static str = "/Themes";
+pub const DARK: &'static str = "dark";
+pub const LIGHT: &'static str = "light";
+// Id of the <link> loading the theme's font.
+pub const FONT_STYLESHEET_ID: &'static str = "themefont";
+
+// How the desktop looks, turned into CSS custom properties by css_variables. Components use
+// them through the colors, radii, blur and font tailwind.config.js defines on top of them,
+// like bg-surface or rounded-theme, instead of fixed colors.
+//
+// A theme file only needs what it changes, the rest comes from the dark theme:
+//
+//     dark = false
+//     [palette]
+//     surface = "#e2e8f0"
+//     content = "#0f172a"
+//     [font]
+//     family = "Georgia"
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(default,deny_unknown_fields)]
+pub struct Theme{
+    // Whether browser drawn controls, like scrollbars, should be dark.
+    pub dark:bool,
+    pub palette:Palette,
+    pub font:Font,
+    pub radii:Radii,
+    // Backdrop blur of panels in px.
+    pub blur:u32,
+    // Picked as the wallpaper when the theme is chosen in System Preferences.
+    pub wallpaper:Option<settings::Wallpaper>,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(default,deny_unknown_fields)]
+pub struct Palette{
+    // Menus, windows and other panels.
+    pub surface:Color,
+    // Text inputs and sidebars.
+    pub field:Color,
+    // Buttons.
+    pub control:Color,
+    // What's selected, like a desktop item or a pressed button.
+    pub selected:Color,
+    // What's under the pointer.
+    pub highlight:Color,
+    // Dimming behind overlays.
+    pub backdrop:Color,
+    // Text and icons.
+    pub content:Color,
+    // Secondary text, like paths under a name.
+    pub muted:Color,
+    pub danger:Color,
+    pub warning:Color,
+    pub success:Color,
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(default,deny_unknown_fields)]
+pub struct Font{
+    // A CSS font family name.
+    pub family:String,
+    // A stylesheet loading the font, like a Google Fonts url. None for fonts every browser has.
+    pub url:Option<String>,
+}
+
+// Corner radii in px.
+#[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize)]
+#[serde(default,deny_unknown_fields)]
+pub struct Radii{
+    pub small:u32,
+    pub large:u32,
+}
+
+// A #rrggbb color.
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(try_from="String",into="String")]
+pub struct Color([u8;3]);
+
+impl Color{
+    pub const fn rgb(r:u8, g:u8, b:u8) -> Self {
+        Self([r,g,b])
+    }
+
+    // Space separated channels, so tailwind can add an alpha with rgb(var(--color) / alpha).
+    fn channels(&self) -> String {
+        let [r,g,b] = self.0;
+        format!("{r} {g} {b}")
+    }
+}
+
+impl TryFrom<String> for Color{
+    type Error = String;
+    fn try_from(hex:String) -> Result<Self,String> {
+        let invalid = || format!("\"{hex}\" isn't a #rrggbb color");
+        let digits = hex.strip_prefix('#').filter(|digits| digits.len() == 6 && digits.is_ascii()).ok_or_else(invalid)?;
+        let channel = |i:usize| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Self([channel(0)?,channel(2)?,channel(4)?]))
+    }
+}
+
+impl From<Color> for String{
+    fn from(color:Color) -> String {
+        let [r,g,b] = color.0;
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+impl Default for Theme{
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Default for Palette{
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Default for Font{
+    fn default() -> Self {
+        Self{family:"Lunasima".to_string(),url:Some("https://fonts.googleapis.com/css?family=Lunasima".to_string())}
+    }
+}
+
+impl Default for Radii{
+    fn default() -> Self {
+        Self{small:4,large:8}
+    }
+}
+
+impl Palette{
+    // Tailwind's slate scale, what the desktop was first drawn with.
+    fn dark() -> Self {
+        Self{
+            surface:Color::rgb(51,65,85),
+            field:Color::rgb(30,41,59),
+            control:Color::rgb(71,85,105),
+            selected:Color::rgb(100,116,139),
+            highlight:Color::rgb(148,163,184),
+            backdrop:Color::rgb(15,23,42),
+            content:Color::rgb(255,255,255),
+            muted:Color::rgb(203,213,225),
+            danger:Color::rgb(239,68,68),
+            warning:Color::rgb(245,158,11),
+            success:Color::rgb(34,197,94),
+        }
+    }
+
+    fn light() -> Self {
+        Self{
+            surface:Color::rgb(241,245,249),
+            field:Color::rgb(255,255,255),
+            control:Color::rgb(226,232,240),
+            selected:Color::rgb(203,213,225),
+            highlight:Color::rgb(148,163,184),
+            backdrop:Color::rgb(226,232,240),
+            content:Color::rgb(15,23,42),
+            muted:Color::rgb(71,85,105),
+            danger:Color::rgb(220,38,38),
+            warning:Color::rgb(217,119,6),
+            success:Color::rgb(22,163,74),
+        }
+    }
+}
+
+impl Theme{
+    pub fn dark() -> Self {
+        Self{dark:true,palette:Palette::dark(),font:Font::default(),radii:Radii::default(),blur:12,wallpaper:None}
+    }
+
+    pub fn light() -> Self {
+        Self{dark:false,palette:Palette::light(),..Self::dark()}
+    }
+
+    pub fn builtin(name:&str) -> Option<Self> {
+        match name {
+            DARK => Some(Self::dark()),
+            LIGHT => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text:&str) -> Result<Self,String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    // What style attributes set on the body so everything under it can use the theme.
+    pub fn css_variables(&self) -> String {
+        let Palette{surface,field,control,selected,highlight,backdrop,content,muted,danger,warning,success} = &self.palette;
+        let colors = [
+            ("surface",surface),("field",field),("control",control),("selected",selected),("highlight",highlight),
+            ("backdrop",backdrop),("content",content),("muted",muted),("danger",danger),("warning",warning),("success",success),
+        ];
+        let mut css = colors.iter()
+            .map(|(name,color)| format!("--color-{name}: {};", color.channels()))
+            .collect::<Vec<String>>()
+            .join(" ");
+        css.push_str(&format!(" --font-family: '{}', sans-serif; --radius: {}px; --radius-large: {}px; --blur: {}px; color-scheme: {};",
+            self.font.family.replace('\'', ""), self.radii.small, self.radii.large, self.blur,
+            if self.dark { "dark" } else { "light" }));
+        css
+    }
+}
+
+// The name config::Theme stands for.
+pub fn builtin_name(theme:config::Theme) -> &'static str {
+    match theme {
+        config::Theme::Dark => DARK,
+        config::Theme::Light => LIGHT,
+    }
+}
+
+pub fn theme_path(name:&str) -> String {
+    format!("{THEMES_PATH}/{name}.toml")
+}
+
+// A built-in theme, or the user's theme file of that name.
+pub fn load(file_system:&mut FileSystem, name:&str) -> Result<Theme,String> {
+    if let Some(theme) = Theme::builtin(name) {
+        return Ok(theme);
+    }
+    let path = theme_path(name);
+    let text = file_system.read_file(&path).map_err(|err| format!("{path}: {err}"))?;
+    Theme::parse(&String::from_utf8_lossy(&text)).map_err(|err| format!("{path}: {err}"))
+}
+
+// Like load, falling back to the dark theme when a theme file is missing or broken.
+pub fn load_or_default(file_system:&mut FileSystem, name:&str) -> Theme {
+    load(file_system, name).unwrap_or_else(|err| {
+        log::error!("couldn't load theme: {err}");
+        Theme::dark()
+    })
+}
+
+// Names of the built-in themes followed by the user's.
+pub fn available(system:&SystemRuntime) -> Vec<String> {
+    let user = system.list_dir(THEMES_PATH).into_iter()
+        .filter_map(|path| path.rsplit('/').next().and_then(|name| name.strip_suffix(".toml")).map(str::to_string));
+    [DARK,LIGHT].into_iter().map(str::to_string).chain(user).collect()
+}
@@ -4,9 +4,9 @@ pub const DROP_DOWN_LIST_ITEM_ID: &'static str = "drop_down_list_item";
 #[component]
 pub fn TopBar() -> impl IntoView {
     view!{
-        <div class="w-full h-6 bg-slate-500 bg-opacity-20 backdrop-blur-md flex justify-start fixed top-0">
+        <div class="w-full h-6 bg-selected bg-opacity-20 backdrop-blur-theme flex justify-start fixed top-0 z-30">
             <TopBarProvider>
-            <div class="flex">
+            <MenuBar>
             <DropDownButton show=DropDownShow::Logo>
                 <TopLeftEye/>    
             </DropDownButton>
@@ -22,6 +22,10 @@ pub fn TopBar() -> impl IntoView {
             <DropDownButton show=DropDownShow::Help>
             "Help"
             </DropDownButton>
+            </MenuBar>
+            <div class="ml-auto flex">
+            <menu_extras::StatusArea/>
+            <notifications::NotificationButton/>
             </div>
             <DropDown/>
             </TopBarProvider>
@@ -29,6 +33,22 @@ pub fn TopBar() -> impl IntoView {
     }
 }
 
+// The menus, which the touch layout collapses into a status bar, see mobile::StatusBarTitle.
+#[island]
+fn MenuBar(children:Children) -> impl IntoView {
+    let mobile = expect_context::<RwSignal<mobile::MobileLayout>>();
+    let set_show = expect_context::<RwSignal<DropDownShow>>().write_only();
+    create_effect(move |_| if mobile().0 {
+        set_show(DropDownShow::None);
+    });
+    view!{
+        <div class="flex" class=("hidden", move || mobile().0)>
+            {children()}
+        </div>
+        {move || mobile().0.then(|| view!{<mobile::StatusBarTitle/>})}
+    }
+}
+
 #[island]
 fn DropDownButton(children:Children,show:DropDownShow) -> impl IntoView{
     let set_show = expect_context::<RwSignal<DropDownShow>>().write_only();
@@ -47,7 +67,7 @@ fn DropDownButton(children:Children,show:DropDownShow) -> impl IntoView{
         <button
         _ref=btn_ref
          id="topbar_btn"
-        class="pl-3 pr-3 rounded-[0.25rem] "
+        class="pl-3 pr-3 rounded-theme "
         on:click=move |ev| {
             if read_show.get_untracked() == show {
                 set_show(DropDownShow::None)
@@ -63,7 +83,7 @@ fn DropDownButton(children:Children,show:DropDownShow) -> impl IntoView{
             }
             ev.stop_immediate_propagation();
         }
-        class=("bg-slate-400", move || read_show() == show)
+        class=("bg-highlight", move || read_show() == show)
         >
         {children()}
         </button>
@@ -84,6 +104,14 @@ pub fn DropDown() -> impl IntoView {
     };
     provide_context::<RwSignal<HoverHighlight>>(create_rw_signal(HoverHighlight(true)));
     let set_hover_highlight = expect_context::<RwSignal<HoverHighlight>>().write_only();
+    let open_spotlight = expect_context::<RwSignal<search::SpotlightOpen>>().write_only();
+    let system = expect_context::<RwSignal<file_system::SystemRuntime>>();
+    let imports = expect_context::<RwSignal<import::Imports>>();
+    let selection = expect_context::<RwSignal<desktop::Selection>>();
+    let power = expect_context::<RwSignal<power::PowerState>>();
+    let notifications = expect_context::<RwSignal<notifications::Notifications>>();
+    let dialogs = expect_context::<dialogs::Dialogs>();
+    let overview = expect_context::<RwSignal<windows::Overview>>();
     let handle = window_event_listener(ev::click, move |ev| {
         let target = event_target::<web_sys::HtmlElement>(&ev);
         let node = div_ref.get_untracked().expect("div to be set here.");
@@ -106,7 +134,7 @@ pub fn DropDown() -> impl IntoView {
     });
     on_cleanup(move || handle.remove());
     view!{
-        <div class="flex flex-col bg-slate-700 bg-opacity-50 backdrop-blur-md rounded-[0.25rem] pt-1 pb-1" 
+        <div class="flex flex-col bg-surface bg-opacity-50 backdrop-blur-theme rounded-theme pt-1 pb-1" 
         style=drop_down_xy_style
         _ref=div_ref id="dropdown">
             {   
@@ -114,12 +142,41 @@ pub fn DropDown() -> impl IntoView {
                     DropDownShow::None => view!{}.into_view(),
                     DropDownShow::Logo => view!{
                         <DropDownListItem name="About site_os"/>
-                        <DropDownListItem name="System Preferences"/>
+                        <DropDownListItem name="System Preferences" on_click=move |_| preferences::open(system)/>
                        // <DropDownListItem name="App Store"/>
+                        <DropDownListItem name="Lock Screen" on_click=move |_| power::lock(power)/>
+                        <DropDownListItem name="Log Out" on_click=move |_| spawn_local(async move {
+                            if dialogs.confirm(dialogs::Modality::System, "Log Out", "Are you sure you want to quit all apps and log out now?").await {
+                                power::log_out(system);
+                            }
+                        })/>
+                        <DropDownListItem name="Restart" on_click=move |_| power::boot(system, power)/>
+                        <DropDownListItem name="Shut Down" on_click=move |_| power::shut_down(system, power)/>
                     }.into_view(),
                     DropDownShow::File => view!{
-                        <DropDownListItem name="New Folder"/>
-                        <DropDownListItem name="Find"/>
+                        <DropDownListItem name="New Folder" on_click=move |_| new_folder(system, dialogs, notifications)/>
+                        <DropDownListItem name="Find" on_click=move |_| open_spotlight(search::SpotlightOpen(true))/>
+                        <DropDownListItem name="Upload…" on_click=move |_| import::pick_files(
+                            file_system::DESKTOP_PATH.to_string(), system, imports)/>
+                        <DropDownListItem name="Download" on_click=move |_| {
+                            let paths = selection.get_untracked().0;
+                            if !paths.is_empty() {
+                                export::download_paths(system, paths);
+                            }
+                        }/>
+                        <DropDownListItem name="Compress" on_click=move |_| {
+                            let paths = selection.get_untracked().0;
+                            if !paths.is_empty() {
+                                compress(system, &paths, notifications);
+                            }
+                        }/>
+                        <DropDownListItem name="Extract" on_click=move |_| {
+                            for path in selection.get_untracked().0 {
+                                extract(system, &path, notifications);
+                            }
+                        }/>
+                        <DropDownListItem name="Export Disk Image" on_click=move |_| snapshot::export_disk_image(system, notifications)/>
+                        <DropDownListItem name="Import Disk Image…" on_click=move |_| snapshot::import_disk_image(system, notifications)/>
                     }.into_view(),
                     DropDownShow::Edit => view!{
                         <DropDownListItem name="Undo"/>
@@ -131,6 +188,13 @@ pub fn DropDown() -> impl IntoView {
                     DropDownShow::View => view!{
                         <DropDownListItem name="As Icons"/>
                         <DropDownListItem name="As List"/>
+                        <DropDownListItem name="Mission Control" on_click=move |_| overview.set(windows::Overview::All)/>
+                        // The windows of the app in front.
+                        <DropDownListItem name="Show All Windows" on_click=move |_| {
+                            if let Some(app) = system.with_untracked(|system| system.windows().focused().map(|window| window.app.clone())) {
+                                windows::show_app_windows(overview, app);
+                            }
+                        }/>
                     }.into_view(),
                     DropDownShow::Help => view!{
                         <DropDownListItem name="site_os Help"/>
@@ -144,15 +208,51 @@ pub fn DropDown() -> impl IntoView {
 }
 
 #[component]
-fn DropDownListItem(name:&'static str) -> impl IntoView{
+fn DropDownListItem(
+    name:&'static str,
+    #[prop(optional,into)]
+    on_click:Option<Callback<()>>,
+) -> impl IntoView{
     let read_hover_highlight = expect_context::<RwSignal<HoverHighlight>>().read_only();
     view!{
-        <div class="ml-3 mr-3 rounded-[0.25rem]" id=DROP_DOWN_LIST_ITEM_ID
-        class=("hover:bg-slate-400",move || read_hover_highlight().0 )>
-        <button class="rounded-md pl-2 pr-2" id=DROP_DOWN_LIST_ITEM_ID>{name}</button>
+        <div class="ml-3 mr-3 rounded-theme" id=DROP_DOWN_LIST_ITEM_ID
+        class=("hover:bg-highlight",move || read_hover_highlight().0 )>
+        <button class="rounded-theme pl-2 pr-2" id=DROP_DOWN_LIST_ITEM_ID
+        on:click=move |_| if let Some(on_click) = &on_click { on_click.call(()) }
+        >{name}</button>
         </div>
     }
 }
+// Asks for a name and creates the folder on the desktop.
+fn new_folder(system:RwSignal<file_system::SystemRuntime>, dialogs:dialogs::Dialogs, notifications:RwSignal<notifications::Notifications>) {
+    spawn_local(async move {
+        let Some(name) = dialogs.prompt(dialogs::Modality::System, "New Folder", "Name of the new folder:", "untitled folder").await else { return };
+        let path = file_system::normalize_path(&format!("{}/{}", file_system::DESKTOP_PATH, name.trim()));
+        let created = system.try_update(|system| system.file_system_mut().create_dir(path)).unwrap_or(Ok(()));
+        if let Err(err) = created {
+            let notification = notifications::Notification::new(notifications::Severity::Error, "Couldn't create the folder").with_body(err.to_string());
+            notifications::notify(notifications, notification);
+        }
+    });
+}
+
+fn compress(system:RwSignal<file_system::SystemRuntime>, paths:&[String], notifications:RwSignal<notifications::Notifications>) {
+    let compressed = system.try_update(|system| archive::compress(system.file_system_mut(), paths));
+    if let Some(Err(err)) = compressed {
+        let notification = notifications::Notification::new(notifications::Severity::Error, "Couldn't compress the selection").with_body(err.to_string());
+        notifications::notify(notifications, notification);
+    }
+}
+
+fn extract(system:RwSignal<file_system::SystemRuntime>, path:&str, notifications:RwSignal<notifications::Notifications>) {
+    let extracted = system.try_update(|system| archive::extract_all(system.file_system_mut(), path));
+    if let Some(Err(err)) = extracted {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        let notification = notifications::Notification::new(notifications::Severity::Error, format!("Couldn't extract {name}")).with_body(err.to_string());
+        notifications::notify(notifications, notification);
+    }
+}
+
 #[derive(Clone,Copy,Debug,Serialize,Deserialize,PartialEq)]
 enum DropDownShow{
     None,
@@ -179,7 +279,7 @@ pub fn TopLeftEye() -> impl IntoView {
         width="32.000000pt" height="32.000000pt" viewBox="0 0 32.000000 32.000000"
         preserveAspectRatio="xMidYMid meet">
 
-        <g class="fill-white" transform="translate(0.000000,32.000000) scale(0.100000,-0.100000)"
+        <g class="fill-content" transform="translate(0.000000,32.000000) scale(0.100000,-0.100000)"
         fill="#000000" stroke="none">
         <path d="M65 212 c-16 -11 -36 -26 -44 -36 -12 -14 -11 -19 5 -37 30 -33 89
         -59 134 -59 45 0 104 26 134 59 19 21 19 21 0 42 -17 19 -65 49 -79 49 -3 0
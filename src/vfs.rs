@@ -0,0 +1,732 @@
+use super::*;
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use file_system::{FileType,FsError,Metadata,SystemRuntime,normalize_path,parent_path};
+use remote_store::{RemoteObject,RemoteStore};
+
+// Where a mounted subtree's contents come from. Enough to mount it again, so it's what
+// `mount` lists and what two FileSystems compare.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub enum MountSource{
+    // Gone when the page is closed.
+    Memory,
+    // A zip or tar file in the FileSystem, mounted at its own path.
+    Archive,
+    // The browser's localStorage, under keys starting with the prefix.
+    LocalStorage{prefix:String},
+    // A directory on the site's server, through server functions.
+    Server{root:String},
+    // The files in the crate's public/ directory, read-only.
+    PublicAssets,
+    // An S3 compatible bucket, e.g. DigitalOcean Spaces.
+    ObjectStorage{bucket_url:String},
+}
+
+impl MountSource{
+    // The -t argument `mount` takes for this kind of source.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Memory => "memory",
+            Self::Archive => "archive",
+            Self::LocalStorage{..} => "local",
+            Self::Server{..} => "server",
+            Self::PublicAssets => "public",
+            Self::ObjectStorage{..} => "s3",
+        }
+    }
+    pub fn is_remote(&self) -> bool {
+        matches!(self, Self::Server{..} | Self::PublicAssets | Self::ObjectStorage{..})
+    }
+}
+
+impl std::fmt::Display for MountSource{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Memory | Self::Archive | Self::PublicAssets => write!(f,"{}",self.type_name()),
+            Self::LocalStorage{prefix} => write!(f,"local:{prefix}"),
+            Self::Server{root} => write!(f,"server:{root}"),
+            Self::ObjectStorage{bucket_url} => write!(f,"{bucket_url}"),
+        }
+    }
+}
+
+// A subtree of the FileSystem served from somewhere other than its tree. Paths given to a
+// backend are relative to its mount point and absolute, "/" being the mount point itself.
+// Permission checks and link resolution stay in FileSystem, backends only store.
+pub trait Backend: std::fmt::Debug {
+    fn source(&self) -> MountSource;
+    fn read_only(&self) -> bool {
+        false
+    }
+    fn metadata(&self, path:&str) -> Option<&Metadata>;
+    fn metadata_mut(&mut self, _path:&str) -> Option<&mut Metadata> {
+        None
+    }
+    // Called by FileSystem after changing what metadata_mut gave it, for backends that have
+    // to store the change somewhere.
+    fn metadata_changed(&mut self, _path:&str) -> Result<(),FsError> {
+        Ok(())
+    }
+    // Names of the direct children of a directory.
+    fn list(&self, path:&str) -> Result<Vec<String>,FsError>;
+    fn read(&self, path:&str) -> Result<Cow<'_,[u8]>,FsError>;
+    fn create(&mut self, path:&str, _metadata:Metadata) -> Result<(),FsError> {
+        Err(FsError::ReadOnly(path.to_string()))
+    }
+    fn write(&mut self, path:&str, _content:Vec<u8>, _modified:i64) -> Result<(),FsError> {
+        Err(FsError::ReadOnly(path.to_string()))
+    }
+    fn remove(&mut self, path:&str) -> Result<(),FsError> {
+        Err(FsError::ReadOnly(path.to_string()))
+    }
+    // Writes not yet sent to wherever the backend stores them, see flush.
+    fn take_pending(&mut self) -> Option<(Rc<dyn RemoteStore>,Vec<Change>)> {
+        None
+    }
+    fn has_pending(&self) -> bool {
+        false
+    }
+    // For backends that cache a RemoteStore, whether path's content has been fetched.
+    fn remote_store(&self) -> Option<Rc<dyn RemoteStore>> {
+        None
+    }
+    fn is_fetched(&self, _path:&str) -> bool {
+        true
+    }
+    fn fill(&mut self, _path:&str, _content:Vec<u8>) {}
+    fn box_clone(&self) -> Box<dyn Backend>;
+    // Whether other is the same kind of backend with the same contents, see PartialEq below.
+    fn box_eq(&self, other:&dyn Backend) -> bool;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl Clone for Box<dyn Backend> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+impl PartialEq for dyn Backend {
+    fn eq(&self, other:&Self) -> bool {
+        self.box_eq(other)
+    }
+}
+
+// box_eq for backends that are PartialEq.
+pub fn same_backend<B:Backend + PartialEq + 'static>(backend:&B, other:&dyn Backend) -> bool {
+    other.as_any().downcast_ref::<B>() == Some(backend)
+}
+
+// Lists the children of path in a map keyed by absolute path, like the FileSystem tree.
+fn list_children<V>(entries:&BTreeMap<String,V>, path:&str) -> Vec<String> {
+    let prefix = if path == "/" { "/".to_string() } else { format!("{path}/") };
+    entries.range(prefix.clone()..)
+        .take_while(|(child,_)| child.starts_with(&prefix))
+        .filter(|(child,_)| child.len() > prefix.len() && !child[prefix.len()..].contains('/'))
+        .map(|(child,_)| child[prefix.len()..].to_string())
+        .collect()
+}
+
+fn check_is_dir(metadata:Option<&Metadata>, path:&str) -> Result<(),FsError> {
+    match metadata {
+        Some(metadata) if metadata.is_dir() => Ok(()),
+        Some(_) => Err(FsError::NotADirectory(path.to_string())),
+        None if path == "/" => Ok(()),
+        None => Err(FsError::NotFound(path.to_string())),
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct MemoryEntry{
+    pub metadata:Metadata,
+    pub content:Vec<u8>,
+}
+
+// A separate in-memory tree, e.g. for /tmp.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct MemoryBackend{
+    entries:BTreeMap<String,MemoryEntry>,
+}
+
+impl MemoryBackend{
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn entries(&self) -> &BTreeMap<String,MemoryEntry> {
+        &self.entries
+    }
+}
+
+impl Backend for MemoryBackend{
+    fn source(&self) -> MountSource {
+        MountSource::Memory
+    }
+    fn metadata(&self, path:&str) -> Option<&Metadata> {
+        self.entries.get(path).map(|entry| &entry.metadata)
+    }
+    fn metadata_mut(&mut self, path:&str) -> Option<&mut Metadata> {
+        self.entries.get_mut(path).map(|entry| &mut entry.metadata)
+    }
+    fn list(&self, path:&str) -> Result<Vec<String>,FsError> {
+        check_is_dir(self.metadata(path), path)?;
+        Ok(list_children(&self.entries, path))
+    }
+    fn read(&self, path:&str) -> Result<Cow<'_,[u8]>,FsError> {
+        self.entries.get(path)
+            .map(|entry| Cow::Borrowed(entry.content.as_slice()))
+            .ok_or_else(|| FsError::NotFound(path.to_string()))
+    }
+    fn create(&mut self, path:&str, metadata:Metadata) -> Result<(),FsError> {
+        check_is_dir(self.metadata(parent_path(path)), parent_path(path))?;
+        self.entries.insert(path.to_string(), MemoryEntry{metadata,content:Vec::new()});
+        Ok(())
+    }
+    fn write(&mut self, path:&str, content:Vec<u8>, modified:i64) -> Result<(),FsError> {
+        let entry = self.entries.get_mut(path).ok_or_else(|| FsError::NotFound(path.to_string()))?;
+        entry.content = content;
+        entry.metadata.modified = modified;
+        Ok(())
+    }
+    fn remove(&mut self, path:&str) -> Result<(),FsError> {
+        if !list_children(&self.entries, path).is_empty() {
+            return Err(FsError::NotEmpty(path.to_string()));
+        }
+        self.entries.remove(path).map(|_| ()).ok_or_else(|| FsError::NotFound(path.to_string()))
+    }
+    fn box_clone(&self) -> Box<dyn Backend> {
+        Box::new(self.clone())
+    }
+    fn box_eq(&self, other:&dyn Backend) -> bool {
+        same_backend(self, other)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Persists to window.localStorage so files survive reloads on this browser. The entries are
+// kept in memory too and written through on every change.
+#[derive(Debug,Clone,PartialEq)]
+pub struct LocalStorageBackend{
+    prefix:String,
+    memory:MemoryBackend,
+}
+
+impl LocalStorageBackend{
+    // Loads everything previously stored under prefix.
+    pub fn load(prefix:String) -> Result<Self,FsError> {
+        let storage = local_storage()?;
+        let mut memory = MemoryBackend::new();
+        let key_prefix = format!("site_os:{prefix}:");
+        let len = storage.length().unwrap_or_default();
+        for i in 0..len {
+            let Ok(Some(key)) = storage.key(i) else { continue };
+            let Some(path) = key.strip_prefix(&key_prefix) else { continue };
+            let Ok(Some(value)) = storage.get_item(&key) else { continue };
+            match serde_json::from_str::<StoredEntry>(&value) {
+                Ok(stored) => {
+                    let content = BASE64.decode(&stored.content).unwrap_or_default();
+                    memory.entries.insert(path.to_string(), MemoryEntry{metadata:stored.metadata,content});
+                },
+                Err(err) => log::error!("skipping {key} in localStorage: {err}"),
+            }
+        }
+        Ok(Self{prefix,memory})
+    }
+
+    fn key(&self, path:&str) -> String {
+        format!("site_os:{}:{path}",self.prefix)
+    }
+
+    fn persist(&self, path:&str) -> Result<(),FsError> {
+        let storage = local_storage()?;
+        let result = match self.memory.entries.get(path) {
+            Some(entry) => {
+                let stored = StoredEntry{metadata:entry.metadata.clone(),content:BASE64.encode(&entry.content)};
+                let value = serde_json::to_string(&stored).map_err(|err| FsError::Io(err.to_string()))?;
+                storage.set_item(&self.key(path), &value)
+            },
+            None => storage.remove_item(&self.key(path)),
+        };
+        // Usually the quota being full.
+        result.map_err(|err| FsError::Io(format!("localStorage: {err:?}")))
+    }
+}
+
+#[derive(Serialize,Deserialize)]
+struct StoredEntry{
+    metadata:Metadata,
+    content:String,
+}
+
+// Only the browser has one. Elsewhere, like on the server deserializing a snapshot, window()
+// would panic, so mounts of it are left detached.
+fn local_storage() -> Result<web_sys::Storage,FsError> {
+    let unavailable = || FsError::Io("localStorage is unavailable".to_string());
+    if !cfg!(target_arch="wasm32") {
+        return Err(unavailable());
+    }
+    window().local_storage().ok().flatten().ok_or_else(unavailable)
+}
+
+impl Backend for LocalStorageBackend{
+    fn source(&self) -> MountSource {
+        MountSource::LocalStorage{prefix:self.prefix.clone()}
+    }
+    fn metadata(&self, path:&str) -> Option<&Metadata> {
+        self.memory.metadata(path)
+    }
+    fn metadata_mut(&mut self, path:&str) -> Option<&mut Metadata> {
+        self.memory.metadata_mut(path)
+    }
+    fn metadata_changed(&mut self, path:&str) -> Result<(),FsError> {
+        self.persist(path)
+    }
+    fn list(&self, path:&str) -> Result<Vec<String>,FsError> {
+        self.memory.list(path)
+    }
+    fn read(&self, path:&str) -> Result<Cow<'_,[u8]>,FsError> {
+        self.memory.read(path)
+    }
+    fn create(&mut self, path:&str, metadata:Metadata) -> Result<(),FsError> {
+        self.memory.create(path, metadata)?;
+        self.persist(path)
+    }
+    fn write(&mut self, path:&str, content:Vec<u8>, modified:i64) -> Result<(),FsError> {
+        self.memory.write(path, content, modified)?;
+        self.persist(path)
+    }
+    fn remove(&mut self, path:&str) -> Result<(),FsError> {
+        self.memory.remove(path)?;
+        self.persist(path)
+    }
+    fn box_clone(&self) -> Box<dyn Backend> {
+        Box::new(self.clone())
+    }
+    fn box_eq(&self, other:&dyn Backend) -> bool {
+        same_backend(self, other)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// A change to a remote backend that hasn't reached its store yet.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Change{
+    CreateDir(String),
+    Write(String,Vec<u8>),
+    Remove(String),
+}
+
+#[derive(Debug,Clone,PartialEq)]
+struct CachedEntry{
+    metadata:Metadata,
+    // None until fetched, see fetch.
+    content:Option<Vec<u8>>,
+}
+
+// Serves a RemoteStore from a cache, since FileSystem operations can't wait on the network.
+// The listing is fetched when mounting and contents on first use. Changes apply to the cache
+// at once and are queued for the store, flush sends them.
+#[derive(Clone)]
+pub struct RemoteBackend{
+    source:MountSource,
+    store:Rc<dyn RemoteStore>,
+    entries:BTreeMap<String,CachedEntry>,
+    pending:Vec<Change>,
+}
+
+impl std::fmt::Debug for RemoteBackend{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteBackend")
+            .field("source",&self.source)
+            .field("entries",&self.entries.len())
+            .field("pending",&self.pending.len())
+            .finish()
+    }
+}
+
+// The store is made from the source, so it's left out.
+impl PartialEq for RemoteBackend{
+    fn eq(&self, other:&Self) -> bool {
+        self.source == other.source && self.entries == other.entries && self.pending == other.pending
+    }
+}
+
+impl RemoteBackend{
+    // Entries are owned by owner, the mount point's owner, since remote stores don't keep one.
+    pub fn new(source:MountSource, store:Rc<dyn RemoteStore>, listing:Vec<RemoteObject>, owner:&Metadata) -> Self {
+        let mut entries = BTreeMap::new();
+        for object in listing {
+            let path = normalize_path(&object.path);
+            // Stores like S3 have no directories, only keys with slashes in them.
+            let mut dir = parent_path(&path).to_string();
+            while dir != "/" && !entries.contains_key(&dir) {
+                entries.insert(dir.clone(), CachedEntry{metadata:remote_metadata(FileType::Directory, &dir, object.modified, owner),content:None});
+                dir = parent_path(&dir).to_string();
+            }
+            let file_type = if object.is_dir { FileType::Directory } else { FileType::File };
+            let content = object.is_dir.then(Vec::new);
+            entries.insert(path.clone(), CachedEntry{metadata:remote_metadata(file_type, &path, object.modified, owner),content});
+        }
+        Self{source,store,entries,pending:Vec::new()}
+    }
+
+}
+
+fn remote_metadata(file_type:FileType, path:&str, modified:i64, owner:&Metadata) -> Metadata {
+    let mut metadata = match file_type {
+        FileType::Directory => Metadata::new(file_type, "/folder.png"),
+        _ => {
+            let mime = mime::from_extension(path).unwrap_or(mime::OCTET_STREAM);
+            let mut metadata = Metadata::new(file_type, mime::icon(mime));
+            metadata.mime_type = Some(mime.to_string());
+            metadata
+        },
+    };
+    metadata.created = modified;
+    metadata.modified = modified;
+    metadata.accessed = modified;
+    metadata.owner = owner.owner;
+    metadata.group = owner.group;
+    metadata
+}
+
+impl Backend for RemoteBackend{
+    fn source(&self) -> MountSource {
+        self.source.clone()
+    }
+    fn read_only(&self) -> bool {
+        self.source == MountSource::PublicAssets
+    }
+    fn metadata(&self, path:&str) -> Option<&Metadata> {
+        self.entries.get(path).map(|entry| &entry.metadata)
+    }
+    fn metadata_mut(&mut self, path:&str) -> Option<&mut Metadata> {
+        self.entries.get_mut(path).map(|entry| &mut entry.metadata)
+    }
+    fn list(&self, path:&str) -> Result<Vec<String>,FsError> {
+        check_is_dir(self.metadata(path), path)?;
+        Ok(list_children(&self.entries, path))
+    }
+    fn read(&self, path:&str) -> Result<Cow<'_,[u8]>,FsError> {
+        let entry = self.entries.get(path).ok_or_else(|| FsError::NotFound(path.to_string()))?;
+        entry.content.as_deref()
+            .map(Cow::Borrowed)
+            .ok_or_else(|| FsError::NotLoaded(path.to_string()))
+    }
+    fn create(&mut self, path:&str, metadata:Metadata) -> Result<(),FsError> {
+        if self.read_only() {
+            return Err(FsError::ReadOnly(path.to_string()));
+        }
+        check_is_dir(self.metadata(parent_path(path)), parent_path(path))?;
+        if metadata.is_dir() {
+            self.pending.push(Change::CreateDir(path.to_string()));
+        }
+        self.entries.insert(path.to_string(), CachedEntry{metadata,content:Some(Vec::new())});
+        Ok(())
+    }
+    fn write(&mut self, path:&str, content:Vec<u8>, modified:i64) -> Result<(),FsError> {
+        if self.read_only() {
+            return Err(FsError::ReadOnly(path.to_string()));
+        }
+        let entry = self.entries.get_mut(path).ok_or_else(|| FsError::NotFound(path.to_string()))?;
+        entry.metadata.modified = modified;
+        entry.content = Some(content.clone());
+        // Only the latest content of a file needs sending.
+        self.pending.retain(|change| !matches!(change, Change::Write(pending,_) if pending == path));
+        self.pending.push(Change::Write(path.to_string(), content));
+        Ok(())
+    }
+    fn remove(&mut self, path:&str) -> Result<(),FsError> {
+        if self.read_only() {
+            return Err(FsError::ReadOnly(path.to_string()));
+        }
+        if !list_children(&self.entries, path).is_empty() {
+            return Err(FsError::NotEmpty(path.to_string()));
+        }
+        self.entries.remove(path).ok_or_else(|| FsError::NotFound(path.to_string()))?;
+        self.pending.push(Change::Remove(path.to_string()));
+        Ok(())
+    }
+    fn take_pending(&mut self) -> Option<(Rc<dyn RemoteStore>,Vec<Change>)> {
+        (!self.pending.is_empty()).then(|| (self.store.clone(), std::mem::take(&mut self.pending)))
+    }
+    fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    fn remote_store(&self) -> Option<Rc<dyn RemoteStore>> {
+        Some(self.store.clone())
+    }
+    fn is_fetched(&self, path:&str) -> bool {
+        self.entries.get(path).map_or(true, |entry| entry.content.is_some())
+    }
+    fn fill(&mut self, path:&str, content:Vec<u8>) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.content = Some(content);
+        }
+    }
+    fn box_clone(&self) -> Box<dyn Backend> {
+        Box::new(self.clone())
+    }
+    fn box_eq(&self, other:&dyn Backend) -> bool {
+        same_backend(self, other)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Mounts a remote source at dir once its listing has arrived.
+pub async fn mount_remote(system:RwSignal<SystemRuntime>, dir:String, source:MountSource) -> Result<(),FsError> {
+    let store = remote_store::connect(&source)?;
+    let listing = store.list().await.map_err(FsError::Io)?;
+    system.try_update(|system| {
+        let file_system = system.file_system_mut();
+        let owner = file_system.get_file_metadata(&dir)?.clone();
+        file_system.mount(&dir, Box::new(RemoteBackend::new(source, store, listing, &owner)))
+    }).unwrap_or(Ok(()))
+}
+
+// Fetches the content of remote files under paths that haven't been read yet, so FileSystem
+// reads of them don't fail with NotLoaded.
+pub async fn fetch(system:RwSignal<SystemRuntime>, paths:&[String]) -> Result<(),FsError> {
+    let wanted = system.with_untracked(|system| system.file_system().unfetched(paths));
+    for (mount_point, store, inner) in wanted {
+        let content = store.get(&inner).await.map_err(FsError::Io)?;
+        system.update(|system| system.file_system_mut().fill_remote(&mount_point, &inner, content));
+    }
+    Ok(())
+}
+
+// Sends queued remote changes to their stores. Errors are logged, the cache keeps the change.
+pub async fn flush(system:RwSignal<SystemRuntime>) {
+    let Some(pending) = system.try_update(|system| system.file_system_mut().take_pending()) else { return };
+    for (store, changes) in pending {
+        for change in changes {
+            let result = match &change {
+                Change::CreateDir(path) => store.create_dir(path).await,
+                Change::Write(path,content) => store.put(path, content).await,
+                Change::Remove(path) => store.delete(path).await,
+            };
+            if let Err(err) = result {
+                log::error!("syncing {change:?} failed: {err}");
+            }
+        }
+    }
+}
+
+// Flushes remote changes whenever some are queued. Called once by OperatingSystemProvider.
+pub fn provide_sync(system:RwSignal<SystemRuntime>) {
+    create_effect(move |_| {
+        if system.with(|system| system.file_system().has_pending()) {
+            spawn_local(flush(system));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use file_system::FileSystem;
+
+    fn file() -> Metadata {
+        Metadata::new(FileType::File, "/text.png")
+    }
+
+    fn dir() -> Metadata {
+        Metadata::new(FileType::Directory, "/folder.png")
+    }
+
+    // Nothing is ever sent, the tests only look at what's queued.
+    struct NoStore;
+
+    impl RemoteStore for NoStore{
+        fn list(&self) -> remote_store::StoreFuture<Vec<RemoteObject>> {
+            Box::pin(async { Ok::<_,String>(Vec::new()) })
+        }
+        fn get(&self, path:&str) -> remote_store::StoreFuture<Vec<u8>> {
+            let path = path.to_string();
+            Box::pin(async move { Err::<Vec<u8>,_>(format!("{path} isn't stored")) })
+        }
+        fn put(&self, _path:&str, _content:&[u8]) -> remote_store::StoreFuture<()> {
+            Box::pin(async { Ok::<_,String>(()) })
+        }
+        fn create_dir(&self, _path:&str) -> remote_store::StoreFuture<()> {
+            Box::pin(async { Ok::<_,String>(()) })
+        }
+        fn delete(&self, _path:&str) -> remote_store::StoreFuture<()> {
+            Box::pin(async { Ok::<_,String>(()) })
+        }
+    }
+
+    fn object(path:&str, is_dir:bool) -> RemoteObject {
+        RemoteObject{path:path.to_string(),is_dir,size:0,modified:1000}
+    }
+
+    fn remote(source:MountSource) -> RemoteBackend {
+        let listing = vec![object("docs/notes.txt", false), object("docs/old/a.txt", false), object("empty", true)];
+        RemoteBackend::new(source, Rc::new(NoStore), listing, &dir())
+    }
+
+    // A MemoryBackend noting which paths FileSystem said had their metadata changed.
+    #[derive(Debug,Clone,PartialEq,Default)]
+    struct Recording{
+        memory:MemoryBackend,
+        changed:Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Backend for Recording{
+        fn source(&self) -> MountSource {
+            MountSource::Memory
+        }
+        fn metadata(&self, path:&str) -> Option<&Metadata> {
+            self.memory.metadata(path)
+        }
+        fn metadata_mut(&mut self, path:&str) -> Option<&mut Metadata> {
+            self.memory.metadata_mut(path)
+        }
+        fn metadata_changed(&mut self, path:&str) -> Result<(),FsError> {
+            self.changed.borrow_mut().push(path.to_string());
+            Ok(())
+        }
+        fn list(&self, path:&str) -> Result<Vec<String>,FsError> {
+            self.memory.list(path)
+        }
+        fn read(&self, path:&str) -> Result<Cow<'_,[u8]>,FsError> {
+            self.memory.read(path)
+        }
+        fn create(&mut self, path:&str, metadata:Metadata) -> Result<(),FsError> {
+            self.memory.create(path, metadata)
+        }
+        fn write(&mut self, path:&str, content:Vec<u8>, modified:i64) -> Result<(),FsError> {
+            self.memory.write(path, content, modified)
+        }
+        fn remove(&mut self, path:&str) -> Result<(),FsError> {
+            self.memory.remove(path)
+        }
+        fn box_clone(&self) -> Box<dyn Backend> {
+            Box::new(self.clone())
+        }
+        fn box_eq(&self, other:&dyn Backend) -> bool {
+            same_backend(self, other)
+        }
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn memory_backend_stores_a_tree() {
+        let mut memory = MemoryBackend::new();
+        memory.create("/docs", dir()).unwrap();
+        memory.create("/docs/a.txt", file()).unwrap();
+        memory.write("/docs/a.txt", b"hello".to_vec(), 5).unwrap();
+        assert_eq!(memory.list("/").unwrap(), vec!["docs"]);
+        assert_eq!(memory.list("/docs").unwrap(), vec!["a.txt"]);
+        assert_eq!(memory.read("/docs/a.txt").unwrap().as_ref(), b"hello");
+        assert_eq!(memory.metadata("/docs/a.txt").unwrap().modified, 5);
+        assert_eq!(memory.list("/docs/a.txt"), Err(FsError::NotADirectory("/docs/a.txt".to_string())));
+        assert_eq!(memory.create("/missing/b.txt", file()), Err(FsError::NotFound("/missing".to_string())));
+        assert_eq!(memory.remove("/docs"), Err(FsError::NotEmpty("/docs".to_string())));
+        memory.remove("/docs/a.txt").unwrap();
+        memory.remove("/docs").unwrap();
+        assert!(memory.entries().is_empty());
+    }
+
+    #[test]
+    fn remote_backend_implies_directories_from_keys() {
+        let remote = remote(MountSource::ObjectStorage{bucket_url:"https://bucket.example".to_string()});
+        assert_eq!(remote.list("/").unwrap(), vec!["docs", "empty"]);
+        assert_eq!(remote.list("/docs").unwrap(), vec!["notes.txt", "old"]);
+        assert!(remote.metadata("/docs/old").unwrap().is_dir());
+        assert_eq!(remote.metadata("/docs/notes.txt").unwrap().modified, 1000);
+        assert_eq!(remote.list("/empty").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn remote_backend_reads_once_filled() {
+        let mut remote = remote(MountSource::Server{root:"site".to_string()});
+        assert!(!remote.is_fetched("/docs/notes.txt"));
+        assert_eq!(remote.read("/docs/notes.txt"), Err(FsError::NotLoaded("/docs/notes.txt".to_string())));
+        remote.fill("/docs/notes.txt", b"notes".to_vec());
+        assert!(remote.is_fetched("/docs/notes.txt"));
+        assert_eq!(remote.read("/docs/notes.txt").unwrap().as_ref(), b"notes");
+    }
+
+    #[test]
+    fn remote_backend_queues_only_the_latest_write() {
+        let mut remote = remote(MountSource::Server{root:"site".to_string()});
+        remote.create("/new", dir()).unwrap();
+        remote.create("/new/a.txt", file()).unwrap();
+        remote.write("/new/a.txt", b"one".to_vec(), 1).unwrap();
+        remote.write("/new/a.txt", b"two".to_vec(), 2).unwrap();
+        remote.remove("/empty").unwrap();
+        assert!(remote.has_pending());
+        let (_, changes) = remote.take_pending().unwrap();
+        assert_eq!(changes, vec![
+            Change::CreateDir("/new".to_string()),
+            Change::Write("/new/a.txt".to_string(), b"two".to_vec()),
+            Change::Remove("/empty".to_string()),
+        ]);
+        assert!(!remote.has_pending());
+        assert!(remote.take_pending().is_none());
+    }
+
+    #[test]
+    fn public_assets_are_read_only() {
+        let mut remote = remote(MountSource::PublicAssets);
+        assert!(remote.read_only());
+        assert_eq!(remote.create("/b.txt", file()), Err(FsError::ReadOnly("/b.txt".to_string())));
+        assert_eq!(remote.write("/docs/notes.txt", Vec::new(), 0), Err(FsError::ReadOnly("/docs/notes.txt".to_string())));
+        assert_eq!(remote.remove("/empty"), Err(FsError::ReadOnly("/empty".to_string())));
+        assert!(!remote.has_pending());
+    }
+
+    #[test]
+    fn backends_compare_by_content() {
+        let mut one = MemoryBackend::new();
+        one.create("/a.txt", file()).unwrap();
+        let mut two = one.clone();
+        let (first,second):(Box<dyn Backend>,Box<dyn Backend>) = (Box::new(one.clone()), Box::new(two.clone()));
+        assert!(*first == *second);
+        two.write("/a.txt", b"changed".to_vec(), 1).unwrap();
+        let second:Box<dyn Backend> = Box::new(two);
+        assert!(*first != *second);
+        // Same source, different kind of backend.
+        let recording:Box<dyn Backend> = Box::new(Recording{memory:one,..Default::default()});
+        assert!(*first != *recording);
+        let remote:Box<dyn Backend> = Box::new(remote(MountSource::PublicAssets));
+        let mut filled = remote.clone();
+        assert!(*remote == *filled);
+        filled.fill("/docs/notes.txt", b"notes".to_vec());
+        assert!(*remote != *filled);
+    }
+
+    #[test]
+    fn metadata_changes_reach_the_backend() {
+        let recording = Recording::default();
+        let changed = recording.changed.clone();
+        let mut fs = FileSystem::new();
+        fs.create_dir("/mnt".to_string()).unwrap();
+        fs.mount("/mnt", Box::new(recording)).unwrap();
+        fs.create_file("/mnt/a.txt".to_string(), file(), b"a".to_vec()).unwrap();
+        fs.chmod("/mnt/a.txt", 0o600).unwrap();
+        fs.chown("/mnt/a.txt", 1000, 1000).unwrap();
+        // The mount point's metadata is in the tree, not the backend.
+        fs.chmod("/mnt", 0o700).unwrap();
+        assert_eq!(*changed.borrow(), vec!["/a.txt", "/a.txt"]);
+        assert_eq!(fs.get_file_metadata("/mnt/a.txt").unwrap().mode, 0o600);
+    }
+
+    #[test]
+    fn local_storage_needs_a_browser() {
+        assert_eq!(LocalStorageBackend::load("home".to_string()), Err(FsError::Io("localStorage is unavailable".to_string())));
+    }
+}
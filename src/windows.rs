@@ -0,0 +1,812 @@
+use super::*;
+use file_system::SystemRuntime;
+use layout::{Rect,Snap,Tiling};
+use settings::{KeyCombo,SettingsStore,ShortcutAction};
+
+// Size of a newly opened window.
+const DEFAULT_SIZE: (f64,f64) = (640.0, 420.0);
+// Where the first window opens, the ones after it are moved down and right by CASCADE_STEP
+// until CASCADE_LEN are open, then it starts over.
+const CASCADE_ORIGIN: (f64,f64) = (80.0, 56.0);
+const CASCADE_STEP: f64 = 24.0;
+const CASCADE_LEN: usize = 8;
+// Windows can't be resized smaller than this.
+const MIN_SIZE: (f64,f64) = (240.0, 160.0);
+// Windows are kept below the topbar.
+pub const TOPBAR_HEIGHT: f64 = 24.0;
+// How much of a window has to stay on screen so it can be dragged back.
+const REACHABLE: f64 = 48.0;
+// Space around and between the tiles of the overview.
+const OVERVIEW_GAP: f64 = 48.0;
+// Dropping a window this close to an edge of the desktop snaps it there.
+const SNAP_WITHIN: f64 = 8.0;
+// Space between tiled windows and around them.
+const TILE_GAP: f64 = 8.0;
+// Size of a thumbnail in the window switcher.
+const THUMBNAIL_SIZE: (f64,f64) = (160.0, 112.0);
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Window{
+    pub id:u64,
+    // Path of the app the window belongs to, see SystemRuntime::run_app.
+    pub app:String,
+    pub title:String,
+    // In px from the top left of the page.
+    pub rect:Rect,
+    pub minimized:bool,
+    // Id of the Workspace it's on.
+    pub workspace:u64,
+    // Set while it's snapped, rect is then kept at the snap's part of the desktop.
+    pub snap:Option<Snap>,
+    // Where it was before it was snapped, to go back to.
+    pub restore:Option<Rect>,
+    // Whatever the app saved to be restored with the window, Null if nothing. See
+    // session_restore::save_app_state.
+    pub state:serde_json::Value,
+}
+
+// The windows of running apps and the workspaces they're on. Kept in the runtime so they're
+// saved with it.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+#[serde(try_from="StoredWindowManager")]
+pub struct WindowManager{
+    next_id:u64,
+    // Back to front.
+    windows:Vec<Window>,
+    // Ids, the most recently focused first.
+    mru:Vec<u64>,
+    // In the order they're shown, there's always at least one.
+    workspaces:Vec<Workspace>,
+    next_workspace_id:u64,
+    // Id of the workspace showing.
+    current:u64,
+}
+
+// A WindowManager as saved, checked before it's used, see TryFrom.
+#[derive(Deserialize)]
+struct StoredWindowManager{
+    next_id:u64,
+    windows:Vec<Window>,
+    mru:Vec<u64>,
+    workspaces:Vec<Workspace>,
+    next_workspace_id:u64,
+    current:u64,
+}
+
+// One with no workspaces is refused. A current workspace that's gone falls back to the first.
+impl TryFrom<StoredWindowManager> for WindowManager{
+    type Error = String;
+    fn try_from(stored:StoredWindowManager) -> Result<Self,String> {
+        let StoredWindowManager{next_id,windows,mru,workspaces,next_workspace_id,current} = stored;
+        let first = workspaces.first().ok_or("there has to be at least one workspace")?.id;
+        let current = if workspaces.iter().any(|workspace| workspace.id == current) { current } else { first };
+        Ok(Self{next_id,windows,mru,workspaces,next_workspace_id,current})
+    }
+}
+
+impl Default for WindowManager{
+    fn default() -> Self {
+        Self{
+            next_id:0,
+            windows:Vec::new(),
+            mru:Vec::new(),
+            workspaces:vec![Workspace::new(1)],
+            next_workspace_id:1,
+            current:1,
+        }
+    }
+}
+
+impl WindowManager{
+    // Opens a focused window in front of the others, on the current workspace.
+    pub fn open(&mut self, app:&str, title:String) -> u64 {
+        self.next_id += 1;
+        let step = (self.windows.len() % CASCADE_LEN) as f64 * CASCADE_STEP;
+        let rect = Rect::new(CASCADE_ORIGIN.0 + step, CASCADE_ORIGIN.1 + step, DEFAULT_SIZE.0, DEFAULT_SIZE.1);
+        self.windows.push(Window{id:self.next_id,app:app.to_string(),title,rect,minimized:false,workspace:self.current,snap:None,restore:None,state:serde_json::Value::Null});
+        self.mru.insert(0, self.next_id);
+        self.next_id
+    }
+
+    pub fn close(&mut self, id:u64) -> Option<Window> {
+        let idx = self.windows.iter().position(|window| window.id == id)?;
+        self.mru.retain(|mru| *mru != id);
+        Some(self.windows.remove(idx))
+    }
+
+    pub fn close_app(&mut self, app:&str) {
+        self.windows.retain(|window| window.app != app);
+        let windows = &self.windows;
+        self.mru.retain(|id| windows.iter().any(|window| window.id == *id));
+    }
+
+    pub fn clear(&mut self) {
+        self.windows.clear();
+        self.mru.clear();
+    }
+
+    // Raises the window above the others, unminimizing it and switching to its workspace.
+    pub fn focus(&mut self, id:u64) {
+        let Some(idx) = self.windows.iter().position(|window| window.id == id) else { return };
+        let mut window = self.windows.remove(idx);
+        window.minimized = false;
+        self.current = window.workspace;
+        self.windows.push(window);
+        self.mru.retain(|mru| *mru != id);
+        self.mru.insert(0, id);
+    }
+
+    // The most recently focused window of the current workspace that isn't minimized.
+    pub fn focused(&self) -> Option<&Window> {
+        self.current_windows().find(|window| !window.minimized)
+    }
+
+    // Focus goes to the window used before it.
+    pub fn minimize(&mut self, id:u64) {
+        if let Some(window) = self.windows.iter_mut().find(|window| window.id == id) {
+            window.minimized = true;
+        }
+    }
+
+    // Moving or resizing a snapped window unsnaps it.
+    pub fn set_rect(&mut self, id:u64, rect:Rect) {
+        if let Some(window) = self.windows.iter_mut().find(|window| window.id == id) {
+            window.rect = rect;
+            window.snap = None;
+            window.restore = None;
+        }
+    }
+
+    pub fn set_state(&mut self, id:u64, state:serde_json::Value) {
+        if let Some(window) = self.windows.iter_mut().find(|window| window.id == id) {
+            window.state = state;
+        }
+    }
+
+    // area is the part of the page windows go in.
+    pub fn snap(&mut self, id:u64, snap:Snap, area:Rect) {
+        let Some(window) = self.windows.iter_mut().find(|window| window.id == id) else { return };
+        if window.snap.is_none() {
+            window.restore = Some(window.rect);
+        }
+        window.snap = Some(snap);
+        window.rect = snap.rect(area);
+    }
+
+    // Puts a snapped window back where it was before.
+    pub fn unsnap(&mut self, id:u64) {
+        let Some(window) = self.windows.iter_mut().find(|window| window.id == id) else { return };
+        if let Some(rect) = window.restore.take() {
+            window.rect = rect;
+        }
+        window.snap = None;
+    }
+
+    pub fn toggle_maximized(&mut self, id:u64, area:Rect) {
+        match self.get(id).and_then(|window| window.snap) {
+            Some(Snap::Maximized) => self.unsnap(id),
+            _ => self.snap(id, Snap::Maximized, area),
+        }
+    }
+
+    // Fits snapped windows to a new area, like after the page was resized.
+    pub fn relayout(&mut self, area:Rect) {
+        for window in &mut self.windows {
+            if let Some(snap) = window.snap {
+                window.rect = snap.rect(area);
+            }
+        }
+    }
+
+    // Lays out the windows of the current workspace that aren't minimized, in the order they
+    // were opened.
+    pub fn tile(&mut self, tiling:Tiling, area:Rect) {
+        let ids = self.tiled_ids();
+        for (id, rect) in ids.iter().zip(layout::tile(tiling, ids.len(), area, TILE_GAP)) {
+            self.set_rect(*id, rect);
+        }
+    }
+
+    // The windows tile lays out, in order.
+    pub fn tiled_ids(&self) -> Vec<u64> {
+        let mut ids = self.windows.iter()
+            .filter(|window| window.workspace == self.current && !window.minimized)
+            .map(|window| window.id)
+            .collect::<Vec<u64>>();
+        ids.sort();
+        ids
+    }
+
+    pub fn get(&self, id:u64) -> Option<&Window> {
+        self.windows.iter().find(|window| window.id == id)
+    }
+
+    // Back to front.
+    pub fn windows(&self) -> &[Window] {
+        &self.windows
+    }
+
+    // The most recently focused first.
+    pub fn mru(&self) -> impl Iterator<Item=&Window> {
+        self.mru.iter().filter_map(|id| self.get(*id))
+    }
+
+    // The windows of the current workspace, the most recently focused first.
+    pub fn current_windows(&self) -> impl Iterator<Item=&Window> {
+        self.mru().filter(|window| window.workspace == self.current)
+    }
+
+    // The most recently focused first.
+    pub fn app_windows<'a>(&'a self, app:&'a str) -> impl Iterator<Item=&'a Window> {
+        self.mru().filter(move |window| window.app == app)
+    }
+
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    pub fn current_workspace(&self) -> &Workspace {
+        self.workspaces.iter().find(|workspace| workspace.id == self.current)
+            .expect("current is always one of the workspaces")
+    }
+
+    // Adds an empty workspace after the others.
+    pub fn add_workspace(&mut self) -> u64 {
+        self.next_workspace_id += 1;
+        self.workspaces.push(Workspace::new(self.next_workspace_id));
+        self.next_workspace_id
+    }
+
+    // The windows on it move to the workspace before it, or after it for the first one. The
+    // last workspace can't be removed.
+    pub fn remove_workspace(&mut self, id:u64) {
+        let Some(idx) = self.workspace_index(id) else { return };
+        if self.workspaces.len() == 1 {
+            return;
+        }
+        self.workspaces.remove(idx);
+        let neighbour = self.workspaces[idx.saturating_sub(1)].id;
+        self.windows.iter_mut()
+            .filter(|window| window.workspace == id)
+            .for_each(|window| window.workspace = neighbour);
+        if self.current == id {
+            self.current = neighbour;
+        }
+    }
+
+    pub fn switch_workspace(&mut self, id:u64) {
+        if self.workspace_index(id).is_some() {
+            self.current = id;
+        }
+    }
+
+    // Switches to the workspace step places from the current one. Returns false when there's
+    // none there, it doesn't wrap around.
+    pub fn switch_workspace_by(&mut self, step:isize) -> bool {
+        let Some(id) = self.neighbour_workspace(step) else { return false };
+        self.current = id;
+        true
+    }
+
+    // Moves a window to the workspace step places from the current one and follows it there.
+    pub fn move_window_by(&mut self, id:u64, step:isize) -> bool {
+        let Some(workspace) = self.neighbour_workspace(step) else { return false };
+        self.move_window(id, workspace);
+        self.focus(id);
+        true
+    }
+
+    pub fn move_window(&mut self, id:u64, workspace:u64) {
+        if self.workspace_index(workspace).is_none() {
+            return;
+        }
+        if let Some(window) = self.windows.iter_mut().find(|window| window.id == id) {
+            window.workspace = workspace;
+        }
+    }
+
+    // None goes back to the wallpaper chosen in System Preferences.
+    pub fn set_workspace_wallpaper(&mut self, id:u64, wallpaper:Option<settings::Wallpaper>) {
+        if let Some(workspace) = self.workspaces.iter_mut().find(|workspace| workspace.id == id) {
+            workspace.wallpaper = wallpaper;
+        }
+    }
+
+    fn workspace_index(&self, id:u64) -> Option<usize> {
+        self.workspaces.iter().position(|workspace| workspace.id == id)
+    }
+
+    fn neighbour_workspace(&self, step:isize) -> Option<u64> {
+        let idx = self.workspace_index(self.current)? as isize + step;
+        usize::try_from(idx).ok().and_then(|idx| self.workspaces.get(idx)).map(|workspace| workspace.id)
+    }
+}
+
+// A virtual desktop, with its own windows and optionally its own wallpaper.
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Workspace{
+    pub id:u64,
+    pub wallpaper:Option<settings::Wallpaper>,
+}
+
+impl Workspace{
+    fn new(id:u64) -> Self {
+        Self{id,wallpaper:None}
+    }
+}
+
+// The window a component is drawn in, provided by WindowFrame to its content.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct WindowId(pub u64);
+
+// What the overview is showing, if anything.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Overview{
+    Hidden,
+    // Every window that isn't minimized, like Mission Control.
+    All,
+    // The windows of the app at this path, for "Show All Windows" in the View menu.
+    App(String),
+}
+
+// Called once by OperatingSystemProvider.
+pub fn provide_overview() -> RwSignal<Overview> {
+    let overview = create_rw_signal(Overview::Hidden);
+    provide_context(overview);
+    overview
+}
+
+pub fn toggle_overview(overview:RwSignal<Overview>) {
+    overview.update(|overview| *overview = if *overview == Overview::Hidden { Overview::All } else { Overview::Hidden });
+}
+
+pub fn show_app_windows(overview:RwSignal<Overview>, app:String) {
+    overview.set(Overview::App(app));
+}
+
+// The part of the page windows go in, below the topbar. Only call in the browser.
+pub fn desktop_area() -> Rect {
+    let size = |value:Result<wasm_bindgen::JsValue,wasm_bindgen::JsValue>| value.ok().and_then(|value| value.as_f64()).unwrap_or_default();
+    let window = window();
+    Rect::new(0.0, TOPBAR_HEIGHT, size(window.inner_width()), size(window.inner_height()) - TOPBAR_HEIGHT)
+}
+
+fn app_icon(system:&SystemRuntime, app:&str) -> String {
+    system.file_system().get_file_metadata(app).map(|metadata| metadata.img_src.clone()).unwrap_or_default()
+}
+
+// The window switcher while it's up: the windows it cycles through and the one selected.
+#[derive(Debug,Clone,PartialEq)]
+struct Switcher{
+    ids:Vec<u64>,
+    selected:usize,
+}
+
+// The windows, the window switcher and the overview. Also handles the shortcuts that aren't
+// owned by a component of their own.
+#[island]
+pub fn WindowLayer() -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let overview = expect_context::<RwSignal<Overview>>();
+    let switcher = create_rw_signal(None::<Switcher>);
+    listen_for_shortcuts(system, overview, switcher);
+    workspaces::listen_for_swipes(system);
+    lay_out_windows(system);
+    // Stacking is done with z-index so reordering doesn't move the windows in the DOM.
+    let ids = create_memo(move |_| system.with(|system| {
+        let mut ids = system.windows().windows().iter().map(|window| window.id).collect::<Vec<u64>>();
+        ids.sort();
+        ids
+    }));
+    view!{
+        <div class="fixed inset-0 z-20 pointer-events-none">
+            <For
+            each=ids
+            key=|id| *id
+            children=move |id| view!{<WindowFrame id/>}
+            />
+        </div>
+        <OverviewLayer overview/>
+        <SwitcherView switcher/>
+    }
+}
+
+// Keeps snapped windows fitted to the page as it's resized, and tiled windows laid out as
+// windows come and go.
+fn lay_out_windows(system:RwSignal<SystemRuntime>) {
+    let settings = expect_context::<SettingsStore>();
+    let tiling = settings.select(|settings| settings.windows.tiling);
+    let tiled = create_memo(move |_| system.with(|system| system.windows().tiled_ids()));
+    // Only known in the browser.
+    let area = create_rw_signal(None::<Rect>);
+    create_effect(move |_| area.set(Some(desktop_area())));
+    let resize = window_event_listener(ev::resize, move |_| area.set(Some(desktop_area())));
+    on_cleanup(move || resize.remove());
+    // Only rerun when the area, tiling or the windows to tile change, not when the rects set
+    // here do.
+    create_effect(move |_| {
+        let Some(area) = area() else { return };
+        let tiling = tiling();
+        tiled.track();
+        system.update(|system| {
+            system.windows_mut().relayout(area);
+            system.windows_mut().tile(tiling, area);
+        });
+    });
+}
+
+// What the snapping shortcuts do to the focused window, None unsnaps it. Windows are left
+// alone while tiling lays them out.
+fn snap_focused(system:RwSignal<SystemRuntime>, settings:SettingsStore, snap:Option<Snap>) {
+    if settings.with_untracked(|settings| settings.windows.tiling) != Tiling::Off {
+        return;
+    }
+    let area = desktop_area();
+    system.update(|system| {
+        let Some(id) = system.windows().focused().map(|window| window.id) else { return };
+        let windows = system.windows_mut();
+        match snap {
+            Some(Snap::Maximized) => windows.toggle_maximized(id, area),
+            Some(snap) => windows.snap(id, snap, area),
+            None => windows.unsnap(id),
+        }
+    });
+}
+
+fn listen_for_shortcuts(system:RwSignal<SystemRuntime>, overview:RwSignal<Overview>, switcher:RwSignal<Option<Switcher>>) {
+    let settings = expect_context::<SettingsStore>();
+    let power = expect_context::<RwSignal<power::PowerState>>();
+    // Forward selects the window used before the focused one, backward the least recent.
+    let cycle = move |step:isize| switcher.update(|switcher| match switcher {
+        Some(switcher) => switcher.selected = (switcher.selected as isize + step).rem_euclid(switcher.ids.len() as isize) as usize,
+        None => {
+            let ids = system.with_untracked(|system| system.windows().current_windows().map(|window| window.id).collect::<Vec<u64>>());
+            if !ids.is_empty() {
+                let selected = step.rem_euclid(ids.len() as isize) as usize;
+                *switcher = Some(Switcher{ids,selected});
+            }
+        },
+    });
+    let keydown = window_event_listener(ev::keydown, move |ev| {
+        let shortcuts = settings.with_untracked(|settings| settings.shortcuts.clone());
+        let switch = shortcuts.combo(ShortcutAction::SwitchWindows);
+        let backward = KeyCombo{shift:!switch.shift,..switch.clone()};
+        if switch.matches(&ev) || backward.matches(&ev) {
+            ev.prevent_default();
+            cycle(if switch.matches(&ev) { 1 } else { -1 });
+            return;
+        }
+        if ev.key() == "Escape" {
+            if switcher.with_untracked(Option::is_some) {
+                switcher.set(None);
+            } else if overview.with_untracked(|overview| *overview != Overview::Hidden) {
+                overview.set(Overview::Hidden);
+            }
+            return;
+        }
+        match shortcuts.action(&ev) {
+            Some(ShortcutAction::LockScreen) => {
+                ev.prevent_default();
+                power::lock(power);
+            },
+            Some(ShortcutAction::Preferences) => {
+                ev.prevent_default();
+                preferences::open(system);
+            },
+            Some(ShortcutAction::Overview) => {
+                ev.prevent_default();
+                toggle_overview(overview);
+            },
+            Some(ShortcutAction::PreviousWorkspace) => {
+                ev.prevent_default();
+                system.update(|system| { system.windows_mut().switch_workspace_by(-1); });
+            },
+            Some(ShortcutAction::NextWorkspace) => {
+                ev.prevent_default();
+                system.update(|system| { system.windows_mut().switch_workspace_by(1); });
+            },
+            Some(ShortcutAction::MoveToPreviousWorkspace) => {
+                ev.prevent_default();
+                workspaces::move_focused_window(system, -1);
+            },
+            Some(ShortcutAction::MoveToNextWorkspace) => {
+                ev.prevent_default();
+                workspaces::move_focused_window(system, 1);
+            },
+            Some(ShortcutAction::SnapLeft) => {
+                ev.prevent_default();
+                snap_focused(system, settings, Some(Snap::Left));
+            },
+            Some(ShortcutAction::SnapRight) => {
+                ev.prevent_default();
+                snap_focused(system, settings, Some(Snap::Right));
+            },
+            Some(ShortcutAction::Maximize) => {
+                ev.prevent_default();
+                snap_focused(system, settings, Some(Snap::Maximized));
+            },
+            Some(ShortcutAction::RestoreSize) => {
+                ev.prevent_default();
+                snap_focused(system, settings, None);
+            },
+            // Spotlight listens for its own, SwitchWindows is handled above.
+            Some(ShortcutAction::Spotlight) | Some(ShortcutAction::SwitchWindows) | None => {},
+        }
+    });
+    // Letting go of the modifiers picks the selected window.
+    let keyup = window_event_listener(ev::keyup, move |ev| {
+        let switch = settings.with_untracked(|settings| settings.shortcuts.combo(ShortcutAction::SwitchWindows));
+        if switch.modifiers_held(&ev) {
+            return;
+        }
+        if let Some(Switcher{ids,selected}) = switcher.try_update(Option::take).flatten() {
+            system.update(|system| system.windows_mut().focus(ids[selected]));
+        }
+    });
+    // The keyup never comes if the page loses focus while the switcher is up.
+    let blur = window_event_listener(ev::blur, move |_| switcher.set(None));
+    on_cleanup(move || [keydown, keyup, blur].into_iter().for_each(|handle| handle.remove()));
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum GrabKind{
+    Move,
+    Resize,
+}
+
+// A title bar or resize handle being dragged, from where the pointer went down.
+#[derive(Debug,Clone,Copy,PartialEq)]
+struct Grab{
+    kind:GrabKind,
+    start:(f64,f64),
+    rect:Rect,
+}
+
+#[component]
+fn WindowFrame(id:u64) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    provide_context(WindowId(id));
+    let window = create_memo(move |_| system.with(|system| system.windows().get(id).cloned()));
+    let stacking = create_memo(move |_| system.with(|system| system.windows().windows().iter().position(|window| window.id == id)));
+    let focused = create_memo(move |_| system.with(|system| system.windows().focused().map(|window| window.id)) == Some(id));
+    let settings = expect_context::<SettingsStore>().select(|settings| settings.windows);
+    // The touch layout shows the focused window only, filling the page below the status bar.
+    let mobile = expect_context::<RwSignal<mobile::MobileLayout>>();
+    // While a window is dragged its rect is kept here, the runtime is only updated on release.
+    let drag_rect = create_rw_signal(None::<Rect>);
+    let grab = store_value(None::<Grab>);
+    // What the window snaps to if it's dropped now.
+    let snap_target = create_rw_signal(None::<Snap>);
+    let Some(Window{app,title,..}) = window.get_untracked() else { return view!{}.into_view() };
+    let rect = move || drag_rect().or_else(|| window.with(|window| window.as_ref().map(|window| window.rect))).unwrap_or_default();
+    let style = move || match mobile().0 {
+        true => format!("left:0;top:{TOPBAR_HEIGHT}px;right:0;bottom:0;z-index:{};", stacking().unwrap_or_default()),
+        false => format!("{}z-index:{};", rect().style(), stacking().unwrap_or_default()),
+    };
+    // Minimized, or on another workspace.
+    let hidden = create_memo(move |_| system.with(|system| system.windows().get(id)
+        .map_or(true, |window| window.minimized || window.workspace != system.windows().current_workspace().id))
+        || (mobile().0 && !focused()));
+    // Tiled windows are placed by the layout, full screen ones stay put.
+    let floating = move || settings.with_untracked(|settings| settings.tiling) == Tiling::Off && !mobile.get_untracked().0;
+    let start = move |kind:GrabKind, ev:ev::PointerEvent| {
+        if ev.button() != 0 || !floating() {
+            return;
+        }
+        if let Some(target) = ev.current_target().and_then(|target| target.dyn_into::<web_sys::Element>().ok()) {
+            _ = target.set_pointer_capture(ev.pointer_id());
+        }
+        let pointer = (ev.client_x() as f64, ev.client_y() as f64);
+        let mut rect = rect();
+        // A snapped window gets its size back as soon as it's dragged, staying under the pointer.
+        let restore = window.with_untracked(|window| window.as_ref().filter(|window| window.snap.is_some()).and_then(|window| window.restore));
+        if let (GrabKind::Move, Some(restore)) = (kind, restore) {
+            let along = (pointer.0 - rect.x) / rect.width.max(1.0);
+            rect = Rect::new(pointer.0 - restore.width * along, rect.y, restore.width, restore.height);
+        }
+        grab.set_value(Some(Grab{kind,start:pointer,rect}));
+    };
+    let drag = move |ev:ev::PointerEvent| {
+        let Some(Grab{kind,start,rect}) = grab.get_value() else { return };
+        let (x, y) = (ev.client_x() as f64, ev.client_y() as f64);
+        let (dx, dy) = (x - start.0, y - start.1);
+        let area = desktop_area();
+        drag_rect.set(Some(match kind {
+            GrabKind::Move => Rect::new(rect.x + dx, rect.y + dy, rect.width, rect.height).keep_reachable(area, REACHABLE),
+            GrabKind::Resize => Rect::new(rect.x, rect.y, (rect.width + dx).max(MIN_SIZE.0), (rect.height + dy).max(MIN_SIZE.1)),
+        }));
+        if kind == GrabKind::Move && settings.with_untracked(|settings| settings.snap_to_edges) {
+            snap_target.set(layout::snap_at(x, y, area, SNAP_WITHIN));
+        }
+    };
+    let release = move |_:ev::PointerEvent| {
+        grab.set_value(None);
+        let snap = snap_target.get_untracked();
+        snap_target.set(None);
+        let Some(rect) = drag_rect.get_untracked() else { return };
+        system.update(|system| {
+            system.windows_mut().set_rect(id, rect);
+            if let Some(snap) = snap {
+                system.windows_mut().snap(id, snap, desktop_area());
+            }
+        });
+        drag_rect.set(None);
+    };
+    view!{
+        {move || snap_target().map(|snap| view!{
+            <div class="absolute rounded-theme-lg border-2 border-selected bg-selected bg-opacity-20"
+            style=format!("{}z-index:{};", snap.rect(desktop_area()).style(), stacking().unwrap_or_default())/>
+        })}
+        <div class="absolute pointer-events-auto flex flex-col overflow-hidden shadow-2xl \
+            bg-surface bg-opacity-80 backdrop-blur-theme"
+        class=("rounded-theme-lg", move || !mobile().0)
+        class=("hidden", hidden)
+        style=style
+        data-window=id
+        on:pointerdown=move |_| if !focused.get_untracked() {
+            system.update(|system| system.windows_mut().focus(id));
+        }>
+            <div class="h-7 shrink-0 flex items-center gap-2 px-3 select-none bg-field"
+            class=("bg-opacity-50", move || !focused())
+            on:pointerdown=move |ev| start(GrabKind::Move, ev)
+            on:dblclick=move |_| if floating() {
+                system.update(|system| system.windows_mut().toggle_maximized(id, desktop_area()));
+            }
+            on:pointermove=drag
+            on:pointerup=release
+            on:pointercancel=release>
+                <button class="w-3 h-3 rounded-full bg-danger" title="Close"
+                on:pointerdown=|ev| ev.stop_propagation()
+                on:click=move |_| system.update(|system| system.close_window(id))/>
+                <button class="w-3 h-3 rounded-full bg-warning" title="Minimize"
+                on:pointerdown=|ev| ev.stop_propagation()
+                on:click=move |_| system.update(|system| system.windows_mut().minimize(id))/>
+                <span class="flex-1 text-center text-sm truncate" class=("text-muted", move || !focused())>{title}</span>
+            </div>
+            <div class="relative flex-1 min-h-0">
+                <WindowContent app=app.clone()/>
+                <dialogs::WindowDialogs app/>
+            </div>
+            <div class="absolute bottom-0 right-0 w-4 h-4 cursor-se-resize"
+            class=("hidden", move || mobile().0)
+            on:pointerdown=move |ev| start(GrabKind::Resize, ev)
+            on:pointermove=drag
+            on:pointerup=release
+            on:pointercancel=release/>
+        </div>
+    }.into_view()
+}
+
+// What goes in a window, by the app it belongs to.
+#[component]
+fn WindowContent(app:String) -> impl IntoView {
+    match app.as_str() {
+        preferences::PREFERENCES_PATH => view!{<preferences::Preferences/>}.into_view(),
+        finder::FINDER_PATH => view!{<finder::Finder/>}.into_view(),
+        shell::TERMINAL_PATH => view!{<shell::Terminal/>}.into_view(),
+        _ => view!{<AppPlaceholder app/>}.into_view(),
+    }
+}
+
+// For apps that don't draw anything yet.
+#[component]
+fn AppPlaceholder(app:String) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let icon = system.with_untracked(|system| app_icon(system, &app));
+    view!{
+        <div class="h-full flex flex-col gap-2 items-center justify-center text-muted">
+            <img class="w-16" src=icon/>
+            <p class="text-sm">{app}</p>
+        </div>
+    }
+}
+
+// A window in miniature: its title bar and the app's icon, filling what it's put in.
+#[component]
+fn Thumbnail(window:Window) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let icon = system.with_untracked(|system| app_icon(system, &window.app));
+    view!{
+        <div class="w-full h-full flex flex-col rounded-theme overflow-hidden shadow-lg bg-surface">
+            <div class="h-3 shrink-0 bg-field"/>
+            <div class="flex-1 min-h-0 flex items-center justify-center">
+                <img class="max-w-[50%] max-h-[50%]" src=icon/>
+            </div>
+        </div>
+    }
+}
+
+// Windows in the order the switcher cycles through them, most recently used first.
+#[component]
+fn SwitcherView(switcher:RwSignal<Option<Switcher>>) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let cell = Rect::new(0.0, 0.0, THUMBNAIL_SIZE.0, THUMBNAIL_SIZE.1);
+    move || switcher().map(|Switcher{ids,selected}| {
+        let windows = system.with_untracked(|system| ids.iter().filter_map(|id| system.windows().get(*id).cloned()).collect::<Vec<Window>>());
+        view!{
+            <div class="fixed top-1/3 left-1/2 translate-x-[-50%] z-40 flex gap-2 p-3 max-w-[90vw] overflow-x-auto \
+                rounded-theme-lg bg-surface bg-opacity-50 backdrop-blur-theme">
+                {windows.into_iter().enumerate().map(|(idx,window)| {
+                    let id = window.id;
+                    let title = window.title.clone();
+                    let minimized = window.minimized;
+                    let fit = Rect::new(0.0, 0.0, window.rect.width, window.rect.height).fit_into(cell);
+                    view!{
+                        <button class="flex flex-col gap-1 items-center p-2 rounded-theme"
+                        class=("bg-selected", idx == selected)
+                        on:click=move |_| {
+                            switcher.set(None);
+                            system.update(|system| system.windows_mut().focus(id));
+                        }>
+                            <div class="relative" style=cell.style()>
+                                <div class="absolute" class=("opacity-50", minimized) style=fit.style()>
+                                    <Thumbnail window/>
+                                </div>
+                            </div>
+                            <span class="w-40 text-sm truncate">{title}</span>
+                        </button>
+                    }
+                }).collect_view()}
+            </div>
+        }
+    })
+}
+
+// Every window of the current workspace that isn't minimized scaled down side by side, or
+// just one app's, under the workspace strip. Clicking one brings it to the front, dragging it
+// onto a workspace in the strip moves it there.
+#[component]
+fn OverviewLayer(overview:RwSignal<Overview>) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let dragging = create_rw_signal(None::<u64>);
+    let tiles = move || {
+        let shown = overview();
+        system.with(|system| {
+            let current = system.windows().current_workspace().id;
+            let windows = system.windows().windows().iter()
+                .filter(|window| !window.minimized && window.workspace == current && match &shown {
+                    Overview::Hidden => false,
+                    Overview::All => true,
+                    Overview::App(app) => window.app == *app,
+                })
+                .cloned()
+                .collect::<Vec<Window>>();
+            let rects = windows.iter().map(|window| window.rect).collect::<Vec<Rect>>();
+            let area = desktop_area();
+            let area = Rect::new(area.x, area.y + workspaces::STRIP_HEIGHT, area.width, area.height - workspaces::STRIP_HEIGHT);
+            windows.into_iter().zip(layout::overview(&rects, area.inset(OVERVIEW_GAP / 2.0), OVERVIEW_GAP)).collect::<Vec<_>>()
+        })
+    };
+    move || (overview() != Overview::Hidden).then(|| view!{
+        <div class="fixed inset-0 top-6 z-30 bg-backdrop bg-opacity-40 backdrop-blur-theme"
+        on:click=move |_| overview.set(Overview::Hidden)>
+            <workspaces::WorkspaceStrip dragging/>
+            {move || {
+                let tiles = tiles();
+                if tiles.is_empty() {
+                    return view!{<p class="absolute inset-0 flex items-center justify-center text-muted pointer-events-none">"No Windows"</p>}.into_view();
+                }
+                tiles.into_iter().map(|(window,rect)| {
+                    let id = window.id;
+                    let title = window.title.clone();
+                    // The tiles are laid out in page coordinates, this layer starts below the topbar.
+                    let rect = Rect::new(rect.x, rect.y - TOPBAR_HEIGHT, rect.width, rect.height);
+                    view!{
+                        <div class="absolute cursor-pointer transition-transform hover:scale-105" style=rect.style()
+                        draggable="true"
+                        on:dragstart=move |_| dragging.set(Some(id))
+                        on:dragend=move |_| dragging.set(None)
+                        on:click=move |ev| {
+                            ev.stop_propagation();
+                            system.update(|system| system.windows_mut().focus(id));
+                            overview.set(Overview::Hidden);
+                        }>
+                            <Thumbnail window/>
+                            <span class="absolute left-0 right-0 -bottom-6 text-sm text-center truncate">{title}</span>
+                        </div>
+                    }
+                }).collect_view()
+            }}
+        </div>
+    })
+}
@@ -0,0 +1,165 @@
+use super::*;
+use file_system::SystemRuntime;
+use layout::Rect;
+use settings::Wallpaper;
+
+// Height of the workspace strip at the top of the overview.
+pub const STRIP_HEIGHT: f64 = 112.0;
+// Width of a workspace in the strip, it has the page's proportions.
+const PREVIEW_WIDTH: f64 = 128.0;
+// How far a swipe has to go to switch workspaces, in px.
+const SWIPE_DISTANCE: f64 = 150.0;
+// A trackpad swipe ends once no scrolling came in for this long.
+const SWIPE_IDLE_MS: f64 = 300.0;
+// Fingers a touch swipe takes, swipes with fewer are left to the page.
+const SWIPE_FINGERS: u32 = 3;
+
+// Moves the focused window to the workspace step places from the current one and switches
+// there with it.
+pub fn move_focused_window(system:RwSignal<SystemRuntime>, step:isize) {
+    system.update(|system| {
+        let Some(id) = system.windows().focused().map(|window| window.id) else { return };
+        system.windows_mut().move_window_by(id, step);
+    });
+}
+
+// A horizontal trackpad swipe in progress.
+#[derive(Debug,Clone,Copy,Default)]
+struct Swipe{
+    distance:f64,
+    // When the last scroll of it came in, from js_sys::Date::now.
+    last:f64,
+    // Whether it already switched, one swipe only moves one workspace.
+    done:bool,
+}
+
+// Switches workspaces on a horizontal two finger trackpad swipe over the desktop, or a three
+// finger touch swipe anywhere. Swiping left brings in the workspace on the right.
+pub fn listen_for_swipes(system:RwSignal<SystemRuntime>) {
+    let swipe = store_value(Swipe::default());
+    let switch = move |step:isize| system.update(|system| { system.windows_mut().switch_workspace_by(step); });
+    let wheel = window_event_listener(ev::wheel, move |ev| {
+        // Windows scroll their own content.
+        let in_window = event_target::<web_sys::Element>(&ev).closest("[data-window]").ok().flatten().is_some();
+        if in_window || ev.delta_x().abs() <= ev.delta_y().abs() {
+            return;
+        }
+        let now = js_sys::Date::now();
+        let step = swipe.try_update_value(|swipe| {
+            if now - swipe.last > SWIPE_IDLE_MS {
+                *swipe = Swipe::default();
+            }
+            swipe.last = now;
+            swipe.distance += ev.delta_x();
+            if swipe.done || swipe.distance.abs() < SWIPE_DISTANCE {
+                return 0;
+            }
+            swipe.done = true;
+            swipe.distance.signum() as isize
+        }).unwrap_or_default();
+        if step != 0 {
+            switch(step);
+        }
+    });
+    let touch_start = store_value(None::<f64>);
+    let touchstart = window_event_listener(ev::touchstart, move |ev| {
+        let touches = ev.touches();
+        touch_start.set_value((touches.length() >= SWIPE_FINGERS)
+            .then(|| touches.get(0).map(|touch| touch.client_x() as f64))
+            .flatten());
+    });
+    let touchend = window_event_listener(ev::touchend, move |ev| {
+        let Some(start) = touch_start.try_update_value(Option::take).flatten() else { return };
+        let Some(touch) = ev.changed_touches().get(0) else { return };
+        let distance = touch.client_x() as f64 - start;
+        if distance.abs() >= SWIPE_DISTANCE {
+            switch(if distance < 0.0 { 1 } else { -1 });
+        }
+    });
+    on_cleanup(move || [wheel, touchstart, touchend].into_iter().for_each(|handle| handle.remove()));
+}
+
+// The workspaces side by side at the top of the overview, with a button to add one. Windows
+// dragged from the overview can be dropped on them.
+#[component]
+pub fn WorkspaceStrip(dragging:RwSignal<Option<u64>>) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let ids = create_memo(move |_| system.with(|system| {
+        system.windows().workspaces().iter().map(|workspace| workspace.id).collect::<Vec<u64>>()
+    }));
+    // Only rendered in the browser, the overview starts hidden.
+    let area = windows::desktop_area();
+    let scale = PREVIEW_WIDTH / area.width.max(1.0);
+    view!{
+        <div class="flex justify-center items-center gap-4 p-3" style=format!("height:{STRIP_HEIGHT}px;")
+        on:click=|ev| ev.stop_propagation()>
+            <For
+            each=ids
+            key=|id| *id
+            children=move |id| {
+                let number = Signal::derive(move || ids.with(|ids| ids.iter().position(|other| *other == id).unwrap_or_default() + 1));
+                view!{<WorkspacePreview id number scale dragging/>}
+            }
+            />
+            <button class="w-8 h-8 rounded-full bg-control hover:bg-highlight" title="Add Desktop"
+            on:click=move |_| system.update(|system| { system.windows_mut().add_workspace(); })>"+"</button>
+        </div>
+    }
+}
+
+// A workspace in miniature: its wallpaper with its windows drawn as outlines.
+#[component]
+fn WorkspacePreview(id:u64, number:Signal<usize>, scale:f64, dragging:RwSignal<Option<u64>>) -> impl IntoView {
+    let system = expect_context::<RwSignal<SystemRuntime>>();
+    let current = create_memo(move |_| system.with(|system| system.windows().current_workspace().id) == id);
+    let removable = create_memo(move |_| system.with(|system| system.windows().workspaces().len() > 1));
+    let drop_target = create_rw_signal(false);
+    let area = windows::desktop_area();
+    // Wallpapers from the FileSystem only have a url while they're showing on the body.
+    let background = move || match system.with(|system| system.workspace_wallpaper(id).clone()) {
+        Wallpaper::Public(url) => format!("background-image:url({url});"),
+        Wallpaper::File(_) => String::new(),
+    };
+    let style = move || format!("width:{}px;height:{}px;{}", area.width * scale, area.height * scale, background());
+    let outlines = move || system.with(|system| {
+        system.windows().windows().iter()
+            .filter(|window| window.workspace == id && !window.minimized)
+            .map(|window| {
+                let Rect{x,y,width,height} = window.rect;
+                Rect::new(x * scale, (y - area.y) * scale, width * scale, height * scale)
+            })
+            .collect::<Vec<Rect>>()
+    });
+    view!{
+        <div class="group relative flex flex-col items-center gap-1">
+            <button class="relative overflow-hidden rounded-theme bg-field bg-cover"
+            class=("ring-2", move || current() || drop_target())
+            class=("ring-selected", current)
+            class=("ring-highlight", drop_target)
+            style=style
+            on:click=move |_| system.update(|system| system.windows_mut().switch_workspace(id))
+            on:dragover=move |ev| if dragging.get_untracked().is_some() {
+                // Allows the drop.
+                ev.prevent_default();
+                drop_target.set(true);
+            }
+            on:dragleave=move |_| drop_target.set(false)
+            on:drop=move |ev| {
+                ev.prevent_default();
+                drop_target.set(false);
+                if let Some(window) = dragging.get_untracked() {
+                    system.update(|system| system.windows_mut().move_window(window, id));
+                    dragging.set(None);
+                }
+            }>
+                {move || outlines().into_iter().map(|rect| view!{
+                    <div class="absolute rounded-sm border border-content bg-surface bg-opacity-80" style=rect.style()/>
+                }).collect_view()}
+            </button>
+            <span class="text-xs">{move || format!("Desktop {}", number())}</span>
+            <button class="absolute -top-2 -left-2 w-5 h-5 rounded-full text-xs bg-control hidden" title="Remove Desktop"
+            class=("group-hover:block", removable)
+            on:click=move |_| system.update(|system| system.windows_mut().remove_workspace(id))>"✕"</button>
+        </div>
+    }
+}
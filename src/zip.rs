@@ -0,0 +1,419 @@
+// Minimal zip archive writer and reader. The writer only stores, the reader also inflates
+// deflated entries so archives made elsewhere can be mounted, see archive.rs.
+use chrono::{Datelike,Timelike};
+
+const LOCAL_HEADER_SIG: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIG: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x06054b50;
+const END_OF_CENTRAL_DIR_LEN: usize = 22;
+// Extended timestamp extra field, holds the modified time in unix seconds.
+const EXTENDED_TIMESTAMP_ID: u16 = 0x5455;
+const VERSION: u16 = 20;
+// Made by unix, so readers honour the mode bits in the external attributes.
+const VERSION_MADE_BY: u16 = VERSION | (3 << 8);
+// Names are utf8.
+const FLAG_UTF8: u16 = 1 << 11;
+pub const METHOD_STORED: u16 = 0;
+pub const METHOD_DEFLATED: u16 = 8;
+const UNIX_DIR_FLAG: u32 = 0o040000;
+const UNIX_FILE_FLAG: u32 = 0o100000;
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum ZipError{
+    // Not a zip, or cut short.
+    Malformed(&'static str),
+    UnsupportedMethod(u16),
+    CrcMismatch(String),
+    NotFound(String),
+    // Entries of 4 GiB or more, or past 4 GiB into the archive, need zip64, which isn't written.
+    TooLarge(String),
+}
+
+impl std::fmt::Display for ZipError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(reason) => write!(f,"malformed zip archive: {reason}"),
+            Self::UnsupportedMethod(method) => write!(f,"unsupported zip compression method {method}"),
+            Self::CrcMismatch(name) => write!(f,"{name}: checksum mismatch"),
+            Self::NotFound(name) => write!(f,"{name}: not in archive"),
+            Self::TooLarge(name) => write!(f,"{name}: too large for a zip archive"),
+        }
+    }
+}
+
+impl std::error::Error for ZipError{}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct ZipEntry{
+    // Slash separated, directories end with '/'.
+    pub name:String,
+    // Milliseconds since the unix epoch, second precision.
+    pub modified:i64,
+    pub mode:u16,
+    pub method:u16,
+    pub crc32:u32,
+    pub compressed_size:u32,
+    pub size:u32,
+    header_offset:u32,
+}
+
+impl ZipEntry{
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+struct CentralRecord{
+    name:String,
+    modified:i64,
+    mode:u16,
+    is_dir:bool,
+    crc32:u32,
+    size:u32,
+    offset:u32,
+}
+
+#[derive(Default)]
+pub struct ZipWriter{
+    out:Vec<u8>,
+    central:Vec<CentralRecord>,
+}
+
+impl ZipWriter{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a directory entry, a trailing '/' is added if missing.
+    pub fn add_dir(&mut self, name:&str, modified:i64, mode:u16) -> Result<(),ZipError> {
+        let name = if name.ends_with('/') { name.to_string() } else { format!("{name}/") };
+        self.add(name, modified, mode, true, &[])
+    }
+
+    pub fn add_file(&mut self, name:&str, modified:i64, mode:u16, content:&[u8]) -> Result<(),ZipError> {
+        self.add(name.to_string(), modified, mode, false, content)
+    }
+
+    fn add(&mut self, name:String, modified:i64, mode:u16, is_dir:bool, content:&[u8]) -> Result<(),ZipError> {
+        // The entry has to end below 4 GiB too, or the central directory's offset won't fit.
+        let end = self.out.len() + 30 + name.len() + 9 + content.len();
+        let (Ok(size), Ok(offset), Ok(_)) = (u32::try_from(content.len()), u32::try_from(self.out.len()), u32::try_from(end)) else {
+            return Err(ZipError::TooLarge(name));
+        };
+        let record = CentralRecord{
+            crc32:crc32(content),
+            size,
+            offset,
+            name,
+            modified,
+            mode,
+            is_dir,
+        };
+        let (time,date) = dos_date_time(modified);
+        put_u32(&mut self.out, LOCAL_HEADER_SIG);
+        put_u16(&mut self.out, VERSION);
+        put_u16(&mut self.out, FLAG_UTF8);
+        put_u16(&mut self.out, METHOD_STORED);
+        put_u16(&mut self.out, time);
+        put_u16(&mut self.out, date);
+        put_u32(&mut self.out, record.crc32);
+        put_u32(&mut self.out, record.size);
+        put_u32(&mut self.out, record.size);
+        put_u16(&mut self.out, record.name.len() as u16);
+        put_u16(&mut self.out, 9);
+        self.out.extend_from_slice(record.name.as_bytes());
+        put_extended_timestamp(&mut self.out, modified);
+        self.out.extend_from_slice(content);
+        self.central.push(record);
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let central_offset = self.out.len() as u32;
+        for record in self.central.iter() {
+            let (time,date) = dos_date_time(record.modified);
+            let type_flag = if record.is_dir { UNIX_DIR_FLAG } else { UNIX_FILE_FLAG };
+            put_u32(&mut self.out, CENTRAL_HEADER_SIG);
+            put_u16(&mut self.out, VERSION_MADE_BY);
+            put_u16(&mut self.out, VERSION);
+            put_u16(&mut self.out, FLAG_UTF8);
+            put_u16(&mut self.out, METHOD_STORED);
+            put_u16(&mut self.out, time);
+            put_u16(&mut self.out, date);
+            put_u32(&mut self.out, record.crc32);
+            put_u32(&mut self.out, record.size);
+            put_u32(&mut self.out, record.size);
+            put_u16(&mut self.out, record.name.len() as u16);
+            put_u16(&mut self.out, 9);
+            // comment length, disk number, internal attributes
+            put_u16(&mut self.out, 0);
+            put_u16(&mut self.out, 0);
+            put_u16(&mut self.out, 0);
+            put_u32(&mut self.out, (type_flag | record.mode as u32) << 16);
+            put_u32(&mut self.out, record.offset);
+            self.out.extend_from_slice(record.name.as_bytes());
+            put_extended_timestamp(&mut self.out, record.modified);
+        }
+        let central_len = self.out.len() as u32 - central_offset;
+        put_u32(&mut self.out, END_OF_CENTRAL_DIR_SIG);
+        put_u16(&mut self.out, 0);
+        put_u16(&mut self.out, 0);
+        put_u16(&mut self.out, self.central.len() as u16);
+        put_u16(&mut self.out, self.central.len() as u16);
+        put_u32(&mut self.out, central_len);
+        put_u32(&mut self.out, central_offset);
+        put_u16(&mut self.out, 0);
+        self.out
+    }
+}
+
+// Reads entries lazily, only the central directory is parsed up front.
+pub struct ZipReader<'a>{
+    data:&'a [u8],
+    entries:Vec<ZipEntry>,
+}
+
+impl<'a> ZipReader<'a>{
+    pub fn new(data:&'a [u8]) -> Result<Self,ZipError> {
+        if data.len() < END_OF_CENTRAL_DIR_LEN {
+            return Err(ZipError::Malformed("too short"));
+        }
+        // The end record is followed by a comment of up to u16::MAX bytes.
+        let search_start = data.len().saturating_sub(END_OF_CENTRAL_DIR_LEN + u16::MAX as usize);
+        let end = (search_start..=data.len() - END_OF_CENTRAL_DIR_LEN).rev()
+            .find(|&i| get_u32(data, i) == Some(END_OF_CENTRAL_DIR_SIG))
+            .ok_or(ZipError::Malformed("no end of central directory"))?;
+        let count = get_u16(data, end + 10).ok_or(ZipError::Malformed("truncated end record"))?;
+        let mut pos = get_u32(data, end + 16).ok_or(ZipError::Malformed("truncated end record"))? as usize;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let truncated = ZipError::Malformed("truncated central directory");
+            if get_u32(data, pos) != Some(CENTRAL_HEADER_SIG) {
+                return Err(truncated);
+            }
+            let field16 = |offset:usize| get_u16(data, pos + offset).ok_or(truncated.clone());
+            let field32 = |offset:usize| get_u32(data, pos + offset).ok_or(truncated.clone());
+            let method = field16(10)?;
+            let (time,date) = (field16(12)?,field16(14)?);
+            let crc32 = field32(16)?;
+            let compressed_size = field32(20)?;
+            let size = field32(24)?;
+            let name_len = field16(28)? as usize;
+            let extra_len = field16(30)? as usize;
+            let comment_len = field16(32)? as usize;
+            let external = field32(38)?;
+            let header_offset = field32(42)?;
+            let name_start = pos + 46;
+            let name = data.get(name_start..name_start + name_len).ok_or(truncated.clone())?;
+            let extra = data.get(name_start + name_len..name_start + name_len + extra_len).ok_or(truncated.clone())?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            let modified = extended_timestamp(extra).unwrap_or_else(|| from_dos_date_time(time, date));
+            let mode = match (external >> 16) as u16 & 0o777 {
+                0 if name.ends_with('/') => 0o755,
+                0 => 0o644,
+                mode => mode,
+            };
+            entries.push(ZipEntry{name,modified,mode,method,crc32,compressed_size,size,header_offset});
+            pos = name_start + name_len + extra_len + comment_len;
+        }
+        Ok(Self{data,entries})
+    }
+
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, name:&str) -> Option<&ZipEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn read(&self, entry:&ZipEntry) -> Result<Vec<u8>,ZipError> {
+        read_entry(self.data, entry)
+    }
+
+    pub fn read_by_name(&self, name:&str) -> Result<Vec<u8>,ZipError> {
+        let entry = self.entry(name).ok_or_else(|| ZipError::NotFound(name.to_string()))?;
+        self.read(entry)
+    }
+}
+
+// Decompresses one entry of the archive in data and checks its crc. Entries stay valid for
+// the same data, so the central directory doesn't have to be parsed again.
+pub fn read_entry(data:&[u8], entry:&ZipEntry) -> Result<Vec<u8>,ZipError> {
+    let truncated = ZipError::Malformed("truncated local header");
+    let pos = entry.header_offset as usize;
+    if get_u32(data, pos) != Some(LOCAL_HEADER_SIG) {
+        return Err(truncated);
+    }
+    let name_len = get_u16(data, pos + 26).ok_or(truncated.clone())? as usize;
+    let extra_len = get_u16(data, pos + 28).ok_or(truncated.clone())? as usize;
+    let start = pos + 30 + name_len + extra_len;
+    let raw = data.get(start..start + entry.compressed_size as usize)
+        .ok_or(ZipError::Malformed("truncated entry data"))?;
+    let content = match entry.method {
+        METHOD_STORED => raw.to_vec(),
+        METHOD_DEFLATED => miniz_oxide::inflate::decompress_to_vec(raw)
+            .map_err(|_| ZipError::Malformed("bad deflate stream"))?,
+        method => return Err(ZipError::UnsupportedMethod(method)),
+    };
+    if crc32(&content) != entry.crc32 {
+        return Err(ZipError::CrcMismatch(entry.name.clone()));
+    }
+    Ok(content)
+}
+
+fn put_u16(out:&mut Vec<u8>, value:u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out:&mut Vec<u8>, value:u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn get_u16(data:&[u8], pos:usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn get_u32(data:&[u8], pos:usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+// 9 bytes: id, size, flags (mtime present), mtime.
+fn put_extended_timestamp(out:&mut Vec<u8>, modified:i64) {
+    put_u16(out, EXTENDED_TIMESTAMP_ID);
+    put_u16(out, 5);
+    out.push(1);
+    put_u32(out, (modified / 1000).clamp(0, u32::MAX as i64) as u32);
+}
+
+fn extended_timestamp(mut extra:&[u8]) -> Option<i64> {
+    while extra.len() >= 4 {
+        let id = get_u16(extra, 0)?;
+        let len = get_u16(extra, 2)? as usize;
+        let field = extra.get(4..4 + len)?;
+        if id == EXTENDED_TIMESTAMP_ID && field.first()? & 1 != 0 {
+            return Some(get_u32(field, 1)? as i64 * 1000);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+// Zip's native timestamp, local time with two second precision. Dates before 1980 can't be
+// represented and are clamped.
+fn dos_date_time(millis:i64) -> (u16,u16) {
+    let Some(date_time) = chrono::DateTime::from_timestamp_millis(millis) else { return (0,0x21) };
+    if date_time.year() < 1980 {
+        return (0,0x21);
+    }
+    let time = (date_time.hour() << 11) | (date_time.minute() << 5) | (date_time.second() / 2);
+    let date = ((date_time.year() as u32 - 1980) << 9) | (date_time.month() << 5) | date_time.day();
+    (time as u16, date as u16)
+}
+
+fn from_dos_date_time(time:u16, date:u16) -> i64 {
+    chrono::NaiveDate::from_ymd_opt(1980 + (date >> 9) as i32, ((date >> 5) & 0xf) as u32, (date & 0x1f) as u32)
+        .and_then(|date| date.and_hms_opt((time >> 11) as u32, ((time >> 5) & 0x3f) as u32, ((time & 0x1f) * 2) as u32))
+        .map(|date_time| date_time.and_utc().timestamp_millis())
+        .unwrap_or_default()
+}
+
+const CRC32_TABLE: [u32;256] = {
+    let mut table = [0u32;256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32(data:&[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc,byte| CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2023-11-14 22:13:20 UTC
+    const MODIFIED: i64 = 1_700_000_000_000;
+
+    fn archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new();
+        writer.add_dir("docs", MODIFIED, 0o750).unwrap();
+        writer.add_file("docs/notes.txt", MODIFIED + 1_500, 0o600, b"hello zip").unwrap();
+        writer.add_file("docs/empty", 0, 0o644, b"").unwrap();
+        writer.add_file("héllo.txt", MODIFIED, 0o644, "ünïcode".as_bytes()).unwrap();
+        writer.finish()
+    }
+
+    #[test]
+    fn written_archives_read_back() {
+        let data = archive();
+        let reader = ZipReader::new(&data).unwrap();
+        let names = reader.entries().iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["docs/", "docs/notes.txt", "docs/empty", "héllo.txt"]);
+
+        let dir = reader.entry("docs/").unwrap();
+        assert!(dir.is_dir());
+        assert_eq!((dir.mode, dir.modified), (0o750, MODIFIED));
+
+        let notes = reader.entry("docs/notes.txt").unwrap();
+        assert!(!notes.is_dir());
+        // Times are kept to the second.
+        assert_eq!((notes.mode, notes.modified, notes.method), (0o600, MODIFIED + 1_000, METHOD_STORED));
+        assert_eq!(reader.read(notes).unwrap(), b"hello zip");
+        assert_eq!(reader.read_by_name("docs/empty").unwrap(), b"");
+        assert_eq!(reader.read_by_name("héllo.txt").unwrap(), "ünïcode".as_bytes());
+        assert_eq!(reader.read_by_name("missing"), Err(ZipError::NotFound("missing".to_string())));
+    }
+
+    #[test]
+    fn dos_times_round_trip() {
+        let (time, date) = dos_date_time(MODIFIED);
+        assert_eq!(from_dos_date_time(time, date), MODIFIED);
+        // Before 1980 is clamped to its start.
+        let (time, date) = dos_date_time(0);
+        assert_eq!(from_dos_date_time(time, date), 315_532_800_000);
+    }
+
+    #[test]
+    fn corrupted_entries_are_caught() {
+        let mut data = archive();
+        let reader = ZipReader::new(&data).unwrap();
+        let notes = reader.entry("docs/notes.txt").unwrap().clone();
+        let start = data.windows(9).position(|window| window == b"hello zip").unwrap();
+        data[start] = b'j';
+        assert_eq!(read_entry(&data, &notes), Err(ZipError::CrcMismatch("docs/notes.txt".to_string())));
+        assert!(ZipReader::new(&data[..10]).is_err());
+    }
+
+    #[test]
+    fn deflated_entries_are_inflated() {
+        let content = b"deflate me, deflate me, deflate me".repeat(10);
+        let mut data = archive();
+        let reader = ZipReader::new(&data).unwrap();
+        let mut entry = reader.entry("docs/notes.txt").unwrap().clone();
+        // Swaps the stored content for a deflated copy of something else.
+        let deflated = miniz_oxide::deflate::compress_to_vec(&content, 6);
+        let start = entry.header_offset as usize + 30 + entry.name.len() + 9;
+        data.splice(start..start + entry.compressed_size as usize, deflated.iter().copied());
+        entry.method = METHOD_DEFLATED;
+        entry.compressed_size = deflated.len() as u32;
+        entry.crc32 = crc32(&content);
+        assert_eq!(read_entry(&data, &entry).unwrap(), content);
+    }
+
+    #[test]
+    fn crc32_matches_the_standard() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+    }
+}
@@ -1,9 +1,35 @@
 /** @type {import('tailwindcss').Config} */
+
+// Colors come from the theme's custom properties, see src/theme.rs.
+const themeColor = (name) => `rgb(var(--color-${name}) / <alpha-value>)`;
+
 module.exports = {
   content: ["*.html", "./src/**/*.rs"],
   theme: {
     extend: {
-  
+      colors: {
+        surface: themeColor("surface"),
+        field: themeColor("field"),
+        control: themeColor("control"),
+        selected: themeColor("selected"),
+        highlight: themeColor("highlight"),
+        backdrop: themeColor("backdrop"),
+        content: themeColor("content"),
+        muted: themeColor("muted"),
+        danger: themeColor("danger"),
+        warning: themeColor("warning"),
+        success: themeColor("success"),
+      },
+      borderRadius: {
+        theme: "var(--radius)",
+        "theme-lg": "var(--radius-large)",
+      },
+      backdropBlur: {
+        theme: "var(--blur)",
+      },
+      fontFamily: {
+        theme: "var(--font-family)",
+      },
     },
   },
   plugins: [],